@@ -18,7 +18,7 @@ const FILES: &[&str] = &[
 ];
 
 fn assemble(file: &str) {
-    Assembler::from_file(String::from(file))
+    let _ = Assembler::from_file(String::from(file))
         .map(|assembler| assembler.assemble(false))
         .unwrap();
 }
@@ -0,0 +1,560 @@
+extern crate lc3lib;
+extern crate serde_json;
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    io::{self, BufRead, ErrorKind, Write},
+    process,
+};
+
+use serde_json::{json, Value};
+
+use lc3lib::{
+    assembler::Assembler,
+    lexer::tokenizer::{Span, Tokenizer, DIRECTIVES, MNEMONICS},
+    notifier::{DiagType, Diagnostic},
+    parser,
+    session::Session,
+    token::Token,
+    types::SymbolTable,
+};
+
+/// How many words of a line's encoding are shown when hovering over it
+const HOVER_WORDS: usize = 8;
+
+/// A label in a document, either where it's defined or where it's used
+struct Reference {
+    /// The name the assembler knows it by, i.e. qualified if it's a local label
+    name: String,
+    /// The name as it's written
+    text: String,
+    span: Span,
+    definition: bool,
+}
+
+/// What's known about an open document, as of the last time it changed
+struct Document {
+    path: String,
+    tokens: Vec<(Token, Span)>,
+    references: Vec<Reference>,
+    symbols: SymbolTable,
+    /// The address and value of each word assembled from each line
+    words: BTreeMap<u64, Vec<(u16, u16)>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    fn analyse(path: String, text: &str) -> Self {
+        let mut session = Session::new();
+        let assembled = Assembler::from_source(path.clone(), text.to_string())
+            .assemble_with(&mut session, false);
+
+        let mut words = BTreeMap::new();
+        let (symbols, diagnostics) = match assembled {
+            Ok(output) => {
+                let diagnostics = output.warnings().to_vec();
+                let (symbols, segments) = output.into_program();
+
+                segments.iter().for_each(|segment| {
+                    segment.words().enumerate().for_each(|(offset, word)| {
+                        let address = segment.origin().wrapping_add(offset as u16);
+
+                        if let Some((file, line)) = session.source(address) {
+                            if *file == path {
+                                words
+                                    .entry(line)
+                                    .or_insert_with(Vec::new)
+                                    .push((address, word));
+                            }
+                        }
+                    });
+                });
+
+                (symbols, diagnostics)
+            }
+            Err(diagnostics) => (SymbolTable::new(), diagnostics),
+        };
+
+        // The tokens are needed for where they are, so lex the document separately from
+        // assembling it (and without caring about what it complains about)
+        let mut lexing = Session::new();
+        lexing.add_file(path.clone());
+
+        let lines = text
+            .lines()
+            .enumerate()
+            .map(|(number, line)| {
+                Tokenizer::new(&mut lexing, &path, line, number as u64 + 1).spans()
+            })
+            .collect::<Vec<_>>();
+
+        let references = Self::references(&lines);
+
+        Self {
+            path,
+            tokens: lines.into_iter().flatten().collect(),
+            references,
+            symbols,
+            words,
+            diagnostics,
+        }
+    }
+
+    /// Find every label in the document, with local labels qualified by the parser itself
+    /// (with the global label they come after)
+    fn references(lines: &[Vec<(Token, Span)>]) -> Vec<Reference> {
+        let mut references = Vec::new();
+        let mut scope: Option<String> = None;
+        // Local labels without a global label are complained about when assembling
+        let mut ignored = Session::new();
+
+        lines.iter().for_each(|line| {
+            let mut tokens = line
+                .iter()
+                .map(|(token, _)| token.clone())
+                .collect::<VecDeque<_>>();
+
+            let mut first = match tokens.pop_front() {
+                Some(first) => first,
+                None => return,
+            };
+
+            if let Some(global) = parser::global_scope(&first, tokens.front()) {
+                scope = Some(global);
+            }
+
+            parser::qualify_local_labels(&mut ignored, &mut first, &mut tokens, scope.as_deref());
+
+            std::iter::once(first)
+                .chain(tokens)
+                .zip(line)
+                .enumerate()
+                .for_each(|(index, (qualified, (token, span)))| {
+                    if let (Token::Label(qualified), Token::Label(label)) = (qualified, token) {
+                        references.push(Reference {
+                            name: qualified.token().clone(),
+                            text: label.token().clone(),
+                            span: *span,
+                            definition: index == 0,
+                        });
+                    }
+                });
+        });
+
+        references
+    }
+
+    fn token_at(&self, line: u64, character: u64) -> Option<&(Token, Span)> {
+        self.tokens
+            .iter()
+            .find(|(_, span)| span.line == line && span.start <= character && character < span.end)
+    }
+
+    fn reference_at(&self, line: u64, character: u64) -> Option<&Reference> {
+        self.references.iter().find(|reference| {
+            reference.span.line == line
+                && reference.span.start <= character
+                && character < reference.span.end
+        })
+    }
+
+    fn hover(&self, line: u64, character: u64) -> Option<String> {
+        if let Some(reference) = self.reference_at(line, character) {
+            let symbol = self.symbols.get(&reference.name)?;
+
+            return Some(if symbol.is_constant() {
+                format!(
+                    "**{}** = #{} (x{:04X})",
+                    reference.text,
+                    symbol.value(),
+                    symbol.address()
+                )
+            } else {
+                format!("**{}** is at x{:04X}", reference.text, symbol.address())
+            });
+        }
+
+        self.token_at(line, character)?;
+        let words = self.words.get(&line)?;
+
+        let mut hover = words
+            .iter()
+            .take(HOVER_WORDS)
+            .map(|(address, word)| {
+                let bits = format!("{:016b}", word);
+                format!(
+                    "x{:04X}  x{:04X}  {} {} {} {}",
+                    address,
+                    word,
+                    &bits[0..4],
+                    &bits[4..8],
+                    &bits[8..12],
+                    &bits[12..16]
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if words.len() > HOVER_WORDS {
+            hover.push(format!("... and {} more words", words.len() - HOVER_WORDS));
+        }
+
+        Some(format!("```\n{}\n```", hover.join("\n")))
+    }
+
+    /// The labels that can be used on a line, i.e. the global ones and the local ones
+    /// belonging to the global label the line comes after
+    fn completions(&self, line: u64) -> Vec<Value> {
+        let scope = self
+            .references
+            .iter()
+            .rev()
+            .find(|reference| {
+                reference.definition
+                    && reference.span.line <= line
+                    && !reference.text.starts_with('.')
+            })
+            .map(|reference| reference.name.as_str());
+
+        let mut labels = self
+            .references
+            .iter()
+            .filter(|reference| reference.definition)
+            .filter(|reference| {
+                !reference.text.starts_with('.')
+                    || scope.is_some_and(|scope| {
+                        reference.name == format!("{}{}", scope, reference.text)
+                    })
+            })
+            .map(|reference| {
+                let constant = self
+                    .symbols
+                    .get(&reference.name)
+                    .is_some_and(|symbol| symbol.is_constant());
+
+                // Constant and Reference
+                (reference.text.clone(), if constant { 21 } else { 18 })
+            })
+            .collect::<Vec<_>>();
+        labels.sort();
+        labels.dedup();
+
+        MNEMONICS
+            .iter()
+            .chain(DIRECTIVES.iter())
+            // Keyword
+            .map(|keyword| (keyword.to_string(), 14))
+            .chain(labels)
+            .map(|(label, kind)| json!({ "label": label, "kind": kind }))
+            .collect()
+    }
+}
+
+/// Turn a line and columns (from 1) into an LSP range (from 0)
+fn range(line: u64, start: u64, end: u64) -> Value {
+    json!({
+        "start": { "line": line.saturating_sub(1), "character": start.saturating_sub(1) },
+        "end": { "line": line.saturating_sub(1), "character": end.saturating_sub(1) },
+    })
+}
+
+/// The path to the file a `file://` URI refers to
+fn path(uri: &str) -> String {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+
+    while let Some((&byte, after)) = rest.split_first() {
+        match (
+            byte,
+            after
+                .get(0..2)
+                .and_then(|hex| std::str::from_utf8(hex).ok()),
+        ) {
+            (b'%', Some(hex)) if u8::from_str_radix(hex, 16).is_ok() => {
+                bytes.push(u8::from_str_radix(hex, 16).unwrap());
+                rest = &after[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = after;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+}
+
+impl Server {
+    /// Handle a message from the client, handing back any messages for it
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let id = message.get("id").cloned();
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    // The whole document is sent each time it changes
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "lc3-lsp" },
+            }),
+            "shutdown" => {
+                self.shut_down = true;
+                Value::Null
+            }
+            "exit" => process::exit(if self.shut_down { 0 } else { 1 }),
+            "textDocument/didOpen" => {
+                let document = &params["textDocument"];
+                return self.update(
+                    document["uri"].as_str().unwrap_or_default(),
+                    document["text"].as_str().unwrap_or_default(),
+                );
+            }
+            "textDocument/didChange" => {
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                    .unwrap_or_default();
+                return self.update(
+                    params["textDocument"]["uri"].as_str().unwrap_or_default(),
+                    text,
+                );
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                self.documents.remove(uri);
+                return vec![Self::publish(uri, Vec::new())];
+            }
+            "textDocument/definition" => self
+                .locate(params, |reference, definition| {
+                    definition.definition && definition.name == reference.name
+                })
+                .into_iter()
+                .next()
+                .unwrap_or(Value::Null),
+            "textDocument/references" => {
+                let declarations = params["context"]["includeDeclaration"]
+                    .as_bool()
+                    .unwrap_or(true);
+
+                Value::Array(self.locate(params, |reference, other| {
+                    other.name == reference.name && (declarations || !other.definition)
+                }))
+            }
+            "textDocument/hover" => self
+                .position(params)
+                .and_then(|(document, line, character)| document.hover(line, character))
+                .map_or(
+                    Value::Null,
+                    |hover| json!({ "contents": { "kind": "markdown", "value": hover } }),
+                ),
+            "textDocument/completion" => self
+                .position(params)
+                .map_or(Value::Null, |(document, line, _)| {
+                    Value::Array(document.completions(line))
+                }),
+            _ if id.is_none() => return Vec::new(),
+            _ => {
+                return vec![json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32601, "message": format!("Unknown method {}", method) },
+                })]
+            }
+        };
+
+        match id {
+            Some(id) => vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })],
+            None => Vec::new(),
+        }
+    }
+
+    /// Re-analyse a document that's been opened or changed, publishing its diagnostics
+    fn update(&mut self, uri: &str, text: &str) -> Vec<Value> {
+        let document = Document::analyse(path(uri), text);
+
+        let diagnostics = document
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let severity = match diagnostic.severity() {
+                    DiagType::Error => 1,
+                    DiagType::Warning => 2,
+                    DiagType::Note => 3,
+                };
+
+                // Anything wrong in an included file is shown at the top of the document
+                let (range, message) = if diagnostic.file() == document.path {
+                    let end = diagnostic.column() + diagnostic.width().max(1) as u64;
+                    (
+                        range(diagnostic.line(), diagnostic.column(), end),
+                        diagnostic.message().to_string(),
+                    )
+                } else {
+                    (
+                        range(1, 1, 1),
+                        format!(
+                            "{}:{}: {}",
+                            diagnostic.file(),
+                            diagnostic.line(),
+                            diagnostic.message()
+                        ),
+                    )
+                };
+
+                let mut published = json!({
+                    "range": range,
+                    "severity": severity,
+                    "source": "lc3as",
+                    "message": message,
+                });
+
+                // For a warning, this is the lint to name in a `; lc3as: allow(...)` comment
+                if let Some(code) = diagnostic.code() {
+                    published["code"] = json!(code);
+                }
+
+                published
+            })
+            .collect();
+
+        self.documents.insert(uri.to_string(), document);
+
+        vec![Self::publish(uri, diagnostics)]
+    }
+
+    fn publish(uri: &str, diagnostics: Vec<Value>) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        })
+    }
+
+    /// The document and position (as a line and column, from 1) a request is about
+    fn position(&self, params: &Value) -> Option<(&Document, u64, u64)> {
+        let document = self
+            .documents
+            .get(params["textDocument"]["uri"].as_str()?)?;
+        let position = &params["position"];
+
+        Some((
+            document,
+            position["line"].as_u64()? + 1,
+            position["character"].as_u64()? + 1,
+        ))
+    }
+
+    /// The locations of the labels that `matches` the label at the position requested
+    fn locate(
+        &self,
+        params: &Value,
+        matches: impl Fn(&Reference, &Reference) -> bool,
+    ) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        let (document, reference) =
+            match self
+                .position(params)
+                .and_then(|(document, line, character)| {
+                    document
+                        .reference_at(line, character)
+                        .map(|reference| (document, reference))
+                }) {
+                Some(found) => found,
+                None => return Vec::new(),
+            };
+
+        document
+            .references
+            .iter()
+            .filter(|other| matches(reference, other))
+            .map(|other| {
+                json!({
+                    "uri": uri,
+                    "range": range(other.span.line, other.span.start, other.span.end),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Read a message from the client, if there are any more
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(ErrorKind::InvalidData, "Message without a Content-Length")
+    })?;
+
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(io::Error::from)
+}
+
+fn write_message<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn main() {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let stdout = io::stdout();
+    let mut output = stdout.lock();
+
+    let mut server = Server::default();
+
+    loop {
+        let message = match read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(error) => {
+                eprintln!("lc3-lsp: {}", error);
+                process::exit(1);
+            }
+        };
+
+        for response in server.handle(&message) {
+            if let Err(error) = write_message(&mut output, &response) {
+                eprintln!("lc3-lsp: {}", error);
+                process::exit(1);
+            }
+        }
+    }
+
+    // The client went away without asking the server to exit
+    process::exit(1);
+}
@@ -0,0 +1,401 @@
+extern crate clap;
+extern crate lc3lib;
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::File,
+    io::{self, BufRead, Read, Write},
+    process,
+};
+
+use clap::{App, Arg};
+
+use lc3lib::{
+    assembler::Assembler, notifier::Colour, session::Session, simulator::Simulator,
+    types::SymbolTable,
+};
+
+/// How many instructions `continue` (and friends) will run before giving up, in case
+/// the program never stops
+const STEP_LIMIT: u64 = 10_000_000;
+
+const HELP: &str = "\
+Commands:
+  break (b) LABEL|xADDR   Stop before the instruction at the label or address
+  delete (d) LABEL|xADDR  Remove a breakpoint
+  breakpoints             List the breakpoints
+  continue (c)            Run until a breakpoint, or the program halts
+  step (s)                Run a single instruction
+  next (n)                Run a single instruction, running any subroutine or TRAP it calls
+  finish (f)              Run until the current subroutine returns
+  registers (r)           Show the registers and condition codes
+  print (p) LABEL|xADDR [COUNT]
+                          Show COUNT (default 1) words of memory
+  list (l)                Show the source line of the next instruction
+  help (h)                Show this message
+  quit (q)                Leave the debugger
+An empty line repeats the last command.";
+
+/// Why running the program stopped
+enum Stop {
+    Breakpoint,
+    Halted,
+    Finished,
+    Limit,
+    Fault(String),
+}
+
+struct Debugger<I: Read, O: Write> {
+    simulator: Simulator<I, O>,
+    session: Session,
+    symbols: SymbolTable,
+    /// The labels at each address, for showing addresses symbolically
+    labels: HashMap<u16, String>,
+    breakpoints: BTreeSet<u16>,
+}
+
+impl<I: Read, O: Write> Debugger<I, O> {
+    fn new(simulator: Simulator<I, O>, session: Session, symbols: SymbolTable) -> Self {
+        let mut labels = HashMap::new();
+        symbols
+            .values()
+            .filter(|symbol| !symbol.is_constant())
+            .for_each(|symbol| {
+                let label = labels
+                    .entry(symbol.address())
+                    .or_insert_with(|| symbol.symbol().clone());
+
+                // Be consistent about which label is shown when there are several
+                if symbol.symbol() < label {
+                    *label = symbol.symbol().clone();
+                }
+            });
+
+        Self {
+            simulator,
+            session,
+            symbols,
+            labels,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    /// Work out the address given as a label or a (hex or decimal) number
+    fn address(&self, location: &str) -> Result<u16, String> {
+        if let Some(symbol) = self.symbols.get(location) {
+            return Ok(symbol.address());
+        }
+
+        let parsed = if let Some(hex) = location
+            .strip_prefix('x')
+            .or_else(|| location.strip_prefix("0x"))
+        {
+            u16::from_str_radix(hex, 16).ok()
+        } else {
+            location.trim_start_matches('#').parse::<u16>().ok()
+        };
+
+        parsed.ok_or_else(|| format!("{} isn't a label or an address", location))
+    }
+
+    /// Show an address along with the nearest label before it (if it's close enough to
+    /// be useful), e.g. `x3004 <LOOP+2>`
+    fn describe(&self, address: u16) -> String {
+        let nearest = (0..=address.min(0xFF)).find_map(|offset| {
+            self.labels
+                .get(&(address - offset))
+                .map(|name| (name, offset))
+        });
+
+        match nearest {
+            Some((name, 0)) => format!("x{:04X} <{}>", address, name),
+            Some((name, offset)) => format!("x{:04X} <{}+{}>", address, name, offset),
+            None => format!("x{:04X}", address),
+        }
+    }
+
+    /// The source line the word at `address` was assembled from
+    fn source(&self, address: u16) -> Option<String> {
+        self.session.source(address).map(|(file, line)| {
+            format!(
+                "{}:{}: {}",
+                file,
+                line,
+                self.session.files().get_line(file, line).trim()
+            )
+        })
+    }
+
+    fn show_location(&self) {
+        let pc = self.simulator.pc();
+
+        match self.source(pc) {
+            Some(source) => println!("{}  {}", self.describe(pc), source),
+            None => println!("{}  x{:04X}", self.describe(pc), self.simulator.memory(pc)),
+        }
+    }
+
+    fn show_registers(&self) {
+        (0..8).for_each(|register| {
+            let value = self.simulator.register(register);
+            print!(
+                "R{} x{:04X} #{:<6}{}",
+                register,
+                value,
+                value as i16,
+                if register % 4 == 3 { "\n" } else { "  " }
+            );
+        });
+
+        println!(
+            "PC {}  PSR x{:04X}  CC {}  {} mode",
+            self.describe(self.simulator.pc()),
+            self.simulator.psr(),
+            self.simulator.condition(),
+            if self.simulator.is_supervisor() {
+                "supervisor"
+            } else {
+                "user"
+            }
+        );
+    }
+
+    fn show_memory(&self, address: u16, count: u16) {
+        (0..count).for_each(|offset| {
+            let address = address.wrapping_add(offset);
+            let value = self.simulator.memory(address);
+
+            println!(
+                "{}  x{:04X} #{}",
+                self.describe(address),
+                value,
+                value as i16
+            );
+        });
+    }
+
+    /// Run instructions until `done` says to stop (after each one), a breakpoint is
+    /// reached, or the program halts
+    fn run_until(&mut self, mut done: impl FnMut(&Simulator<I, O>, u16) -> bool) -> Stop {
+        if self.simulator.is_halted() {
+            return Stop::Halted;
+        }
+
+        for step in 0..STEP_LIMIT {
+            if step > 0 && self.breakpoints.contains(&self.simulator.pc()) {
+                return Stop::Breakpoint;
+            }
+
+            let instruction = self.simulator.memory(self.simulator.pc());
+
+            if let Err(error) = self.simulator.step() {
+                return Stop::Fault(error.to_string());
+            }
+
+            if self.simulator.is_halted() {
+                return Stop::Halted;
+            }
+
+            if done(&self.simulator, instruction) {
+                return Stop::Finished;
+            }
+        }
+
+        Stop::Limit
+    }
+
+    fn report(&self, stop: Stop) {
+        match stop {
+            Stop::Breakpoint => print!("Breakpoint at "),
+            Stop::Halted => {
+                println!("The program has halted");
+                return;
+            }
+            Stop::Limit => print!("Gave up after {} instructions at ", STEP_LIMIT),
+            Stop::Fault(error) => println!("{}", error),
+            Stop::Finished => {}
+        }
+
+        self.show_location();
+    }
+
+    /// Run a command, handing back whether to keep going
+    fn command(&mut self, line: &str) -> bool {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+
+        match words.as_slice() {
+            ["break" | "b", location] => match self.address(location) {
+                Ok(address) => {
+                    self.breakpoints.insert(address);
+                    println!("Breakpoint at {}", self.describe(address));
+                }
+                Err(error) => println!("{}", error),
+            },
+            ["delete" | "d", location] => match self.address(location) {
+                Ok(address) if self.breakpoints.remove(&address) => {
+                    println!("Removed the breakpoint at {}", self.describe(address));
+                }
+                Ok(address) => println!("There's no breakpoint at {}", self.describe(address)),
+                Err(error) => println!("{}", error),
+            },
+            ["breakpoints"] => self
+                .breakpoints
+                .iter()
+                .for_each(|address| println!("{}", self.describe(*address))),
+            ["continue" | "c"] => {
+                let stop = self.run_until(|_, _| false);
+                self.report(stop);
+            }
+            ["step" | "s"] => {
+                let stop = self.run_until(|_, _| true);
+                self.report(stop);
+            }
+            ["next" | "n"] => {
+                let pc = self.simulator.pc();
+                let after = pc.wrapping_add(1);
+                let stack = self.simulator.register(6);
+                let calls = matches!(self.simulator.memory(pc) >> 12, 0x4 | 0xF);
+
+                // Subroutines and TRAPs come back to the next instruction, so run until
+                // then (as long as it isn't a recursive call coming back early)
+                let stop = self.run_until(|simulator, _| {
+                    !calls || simulator.pc() == after && simulator.register(6) == stack
+                });
+                self.report(stop);
+            }
+            ["finish" | "f"] => {
+                let mut depth = 0;
+
+                let stop = self.run_until(|simulator, instruction| {
+                    match instruction >> 12 {
+                        // JSR and JSRR
+                        0x4 => depth += 1,
+                        // TRAP, unless the simulator ran it itself, as there's no routine
+                        0xF if simulator.memory(instruction & 0xFF) != 0 => depth += 1,
+                        // RET
+                        0xC if instruction >> 6 & 0x7 == 7 => {
+                            if depth == 0 {
+                                return true;
+                            }
+                            depth -= 1;
+                        }
+                        _ => {}
+                    }
+                    false
+                });
+                self.report(stop);
+            }
+            ["registers" | "r"] => self.show_registers(),
+            ["print" | "p", location] | ["print" | "p", location, _] => {
+                let count = match words.get(2).map(|count| count.parse::<u16>()) {
+                    Some(Ok(count)) => count,
+                    Some(Err(_)) => {
+                        println!("{} isn't a number of words", words[2]);
+                        return true;
+                    }
+                    None => 1,
+                };
+
+                match self.address(location) {
+                    Ok(address) => self.show_memory(address, count),
+                    Err(error) => println!("{}", error),
+                }
+            }
+            ["list" | "l"] => self.show_location(),
+            ["help" | "h"] => println!("{}", HELP),
+            ["quit" | "q"] => return false,
+            _ => println!("Unknown command '{}', try 'help'", line.trim()),
+        }
+
+        true
+    }
+}
+
+fn main() {
+    let args = App::new("LC3DB")
+        .arg(
+            Arg::with_name("file")
+                .help("The program to debug")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("input")
+                .help("A file to give to the program as its input (for GETC, IN and KBDR), instead of stdin")
+                .long("input")
+                .short("i")
+                .takes_value(true),
+        )
+        .get_matches();
+
+    let file = args.value_of("file").unwrap();
+
+    let mut session = Session::new();
+    let program = Assembler::from_file(file.to_string())
+        .map_err(|error| vec![error.to_string()])
+        .and_then(|assembler| {
+            assembler
+                .assemble_with(&mut session, false)
+                .map_err(|diagnostics| {
+                    diagnostics
+                        .iter()
+                        .map(|diagnostic| diagnostic.coloured(session.files()))
+                        .collect()
+                })
+        });
+
+    let program = match program {
+        Ok(output) => output.into_program(),
+        Err(errors) => {
+            errors.iter().for_each(|error| eprintln!("{}", error));
+            eprintln!("Can't debug {}, as it failed to assemble", file);
+            process::exit(1);
+        }
+    };
+
+    let input: Box<dyn Read> = match args.value_of("input") {
+        Some(input) => match File::open(input) {
+            Ok(input) => Box::new(input),
+            Err(error) => {
+                eprintln!("{}: {}", input, error);
+                process::exit(1);
+            }
+        },
+        None => Box::new(io::stdin()),
+    };
+
+    let mut simulator = Simulator::new(input, io::stdout());
+    simulator.load_program(&program);
+
+    let (symbols, _) = program;
+    let mut debugger = Debugger::new(simulator, session, symbols);
+
+    debugger.show_location();
+
+    let stdin = io::stdin();
+    let mut last = String::new();
+
+    loop {
+        print!("(lc3db) ");
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+
+        if line.trim().is_empty() {
+            line = last.clone();
+        }
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if !debugger.command(&line) {
+            break;
+        }
+
+        last = line;
+    }
+}
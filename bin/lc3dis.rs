@@ -0,0 +1,75 @@
+extern crate clap;
+extern crate lc3lib;
+
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    process,
+};
+
+use clap::{App, Arg};
+
+use lc3lib::{
+    disassembler::{read_symbols, Disassembler},
+    types::SymbolTable,
+    writer::read_object,
+};
+
+fn main() {
+    let args = App::new("LC3DIS")
+        .arg(
+            Arg::with_name("file")
+                .help("The object (.obj) to disassemble")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("symbols")
+                .help("The symbol table (.sym) written alongside the object, for naming labels")
+                .long("symbols")
+                .short("s")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .help("Where to write the source (instead of stdout)")
+                .long("output")
+                .short("o")
+                .takes_value(true),
+        )
+        .get_matches();
+
+    let file = args.value_of("file").unwrap();
+
+    let segments = match File::open(file).and_then(|object| read_object(BufReader::new(object))) {
+        Ok(segments) => segments,
+        Err(error) => {
+            eprintln!("{}: {}", file, error);
+            process::exit(1);
+        }
+    };
+
+    let symbols = match args.value_of("symbols") {
+        Some(symbols) => {
+            match File::open(symbols).and_then(|input| read_symbols(BufReader::new(input))) {
+                Ok(symbols) => symbols,
+                Err(error) => {
+                    eprintln!("{}: {}", symbols, error);
+                    process::exit(1);
+                }
+            }
+        }
+        None => SymbolTable::new(),
+    };
+
+    let source = Disassembler::new(&segments, &symbols).disassemble();
+
+    match args.value_of("output") {
+        Some(output) => {
+            if let Err(error) = fs::write(output, source) {
+                eprintln!("Unable to write {}: {}", output, error);
+                process::exit(1);
+            }
+        }
+        None => print!("{}", source),
+    }
+}
@@ -0,0 +1,131 @@
+extern crate clap;
+extern crate lc3lib;
+
+use std::{fs, process};
+
+use clap::{App, Arg};
+
+use lc3lib::formatter::{Case, Formatter, Immediates};
+
+fn case(name: &str) -> Case {
+    match name {
+        "upper" => Case::Upper,
+        "lower" => Case::Lower,
+        _ => Case::Keep,
+    }
+}
+
+fn main() {
+    let args = App::new("LC3FMT")
+        .arg(
+            Arg::with_name("files")
+                .help("The source files to format")
+                .multiple(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("write")
+                .help("Rewrite the files in place, instead of printing them")
+                .long("write")
+                .short("w"),
+        )
+        .arg(
+            Arg::with_name("check")
+                .help("Don't change anything, but fail if any file isn't already formatted")
+                .long("check")
+                .conflicts_with("write"),
+        )
+        .arg(
+            Arg::with_name("instruction-column")
+                .help("The column mnemonics and directives start in")
+                .long("instruction-column")
+                .takes_value(true)
+                .default_value("12"),
+        )
+        .arg(
+            Arg::with_name("comment-column")
+                .help("The column comments after an instruction start in")
+                .long("comment-column")
+                .takes_value(true)
+                .default_value("40"),
+        )
+        .arg(
+            Arg::with_name("mnemonic-case")
+                .help("The case to write mnemonics and directives in")
+                .long("mnemonic-case")
+                .takes_value(true)
+                .possible_values(&["upper", "lower", "keep"])
+                .default_value("upper"),
+        )
+        .arg(
+            Arg::with_name("register-case")
+                .help("The case to write registers in")
+                .long("register-case")
+                .takes_value(true)
+                .possible_values(&["upper", "lower", "keep"])
+                .default_value("upper"),
+        )
+        .arg(
+            Arg::with_name("immediates")
+                .help("How to write immediates, e.g. #10 (decimal) or x000A (hex)")
+                .long("immediates")
+                .takes_value(true)
+                .possible_values(&["decimal", "hex", "keep"])
+                .default_value("keep"),
+        )
+        .get_matches();
+
+    let column = |name: &str| match args.value_of(name).unwrap().parse::<usize>() {
+        Ok(column) => column,
+        Err(_) => {
+            eprintln!("--{} needs to be a number", name);
+            process::exit(1);
+        }
+    };
+
+    let formatter = Formatter::new()
+        .instruction_column(column("instruction-column"))
+        .comment_column(column("comment-column"))
+        .mnemonics(case(args.value_of("mnemonic-case").unwrap()))
+        .registers(case(args.value_of("register-case").unwrap()))
+        .immediates(match args.value_of("immediates").unwrap() {
+            "decimal" => Immediates::Decimal,
+            "hex" => Immediates::Hexadecimal,
+            _ => Immediates::Keep,
+        });
+
+    let mut failed = false;
+
+    for file in args.values_of("files").unwrap() {
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("{}: {}", file, error);
+                failed = true;
+                continue;
+            }
+        };
+
+        let formatted = formatter.format(&source);
+
+        if args.is_present("check") {
+            if formatted != source {
+                println!("{} isn't formatted", file);
+                failed = true;
+            }
+        } else if args.is_present("write") {
+            if formatted != source {
+                if let Err(error) = fs::write(file, formatted) {
+                    eprintln!("Unable to write {}: {}", file, error);
+                    failed = true;
+                }
+            }
+        } else {
+            print!("{}", formatted);
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
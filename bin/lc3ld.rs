@@ -0,0 +1,64 @@
+extern crate clap;
+extern crate lc3lib;
+
+use std::{fs::File, io::BufReader, process};
+
+use clap::{App, Arg};
+
+use lc3lib::{
+    linker::{link, Object},
+    writer::Writer,
+};
+
+fn main() {
+    let args = App::new("LC3LD")
+        .arg(
+            Arg::with_name("objects")
+                .help("The objects (.rel) written by lc3as --relocatable, which are linked at their .ORIG addresses")
+                .multiple(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .help("Where to write the linked program (the other formats are written alongside it)")
+                .long("output")
+                .short("o")
+                .takes_value(true)
+                .default_value("a.obj"),
+        )
+        .get_matches();
+
+    let objects = args
+        .values_of("objects")
+        .unwrap()
+        .map(|file| {
+            File::open(file)
+                .and_then(|input| Object::read(file.to_string(), BufReader::new(input)))
+                .map_err(|error| format!("{}: {}", file, error))
+        })
+        .collect::<Result<Vec<_>, _>>();
+
+    let objects = match objects {
+        Ok(objects) => objects,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+
+    let output = args.value_of("output").unwrap();
+
+    match link(objects) {
+        Ok(program) => {
+            if let Err(error) = Writer::new().register_all(output).write(program) {
+                eprintln!("Unable to write {}: {}", output, error);
+                process::exit(1);
+            }
+        }
+        Err(errors) => {
+            errors.iter().for_each(|error| eprintln!("{}", error));
+            eprintln!("Linking failed");
+            process::exit(1);
+        }
+    }
+}
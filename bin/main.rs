@@ -1,9 +1,259 @@
 extern crate clap;
+extern crate colored;
 extern crate lc3lib;
 
+use std::{
+    fs::File,
+    io::{self, BufWriter, IsTerminal},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
 use clap::{App, Arg};
 
-use lc3lib::{assembler::Assembler, notifier, writer::Writer};
+use lc3lib::{
+    assembler::{Assembler, Output},
+    linker::Object,
+    lint::{Level, Lint},
+    notifier::{self, Colour, DiagType, Diagnostic, NoColour, Note, Sarif},
+    session::Session,
+    writer::Writer,
+};
+
+/// How diagnostics are written out
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Rendered with the source they point at, in colour unless it's been turned off
+    Human { colour: bool },
+    /// One JSON object per line for each diagnostic, with the rest of the report going
+    /// to stderr so that stdout can be read as it is
+    Json,
+    /// A SARIF log of every file's diagnostics once they've all been assembled, again
+    /// with the rest of the report going to stderr
+    Sarif,
+}
+
+/// What happened to a single file, ready to be reported once everything is done
+struct Report {
+    file: String,
+    format: Format,
+    diagnostics: Vec<String>,
+    /// The diagnostics as they were raised, for the SARIF log
+    raised: Vec<Diagnostic>,
+    errors: usize,
+    warnings: usize,
+    failure: Option<String>,
+}
+
+impl Report {
+    fn new(file: &str, format: Format) -> Self {
+        Self {
+            file: file.to_string(),
+            format,
+            diagnostics: Vec::new(),
+            raised: Vec::new(),
+            errors: 0,
+            warnings: 0,
+            failure: None,
+        }
+    }
+
+    fn tally(&mut self, session: &Session, diagnostics: &[Diagnostic]) {
+        diagnostics
+            .iter()
+            .for_each(|diagnostic| match diagnostic.severity() {
+                DiagType::Error => self.errors += 1,
+                DiagType::Warning => self.warnings += 1,
+                DiagType::Note => {}
+            });
+
+        match self.format {
+            Format::Human { colour } => diagnostics.iter().for_each(|diagnostic| {
+                self.diagnostics.push(if colour {
+                    diagnostic.coloured(session.files())
+                } else {
+                    diagnostic.uncoloured(session.files())
+                });
+            }),
+            Format::Json => notifier::to_json(diagnostics)
+                .iter()
+                .for_each(|object| self.diagnostics.push(object.to_string())),
+            Format::Sarif => self.raised.extend_from_slice(diagnostics),
+        }
+    }
+
+    /// Why the file failed, if none of the errors raised for it say so already
+    fn unexplained_failure(&self) -> Option<&str> {
+        match self.failure {
+            Some(ref reason)
+                if !reason.is_empty()
+                    && !self
+                        .raised
+                        .iter()
+                        .any(|diagnostic| diagnostic.severity() == DiagType::Error) =>
+            {
+                Some(reason)
+            }
+            _ => None,
+        }
+    }
+
+    fn print(&self, quiet: bool) {
+        let status = |line: String| match self.format {
+            Format::Human { .. } => println!("{}", line),
+            Format::Json | Format::Sarif => eprintln!("{}", line),
+        };
+
+        status(format!("Assembling file {}", self.file));
+
+        if !quiet {
+            self.diagnostics
+                .iter()
+                .for_each(|diagnostic| println!("{}", diagnostic));
+        }
+
+        status(format!(
+            "{}: {} error{}, {} warning{}",
+            self.file,
+            self.errors,
+            if self.errors == 1 { "" } else { "s" },
+            self.warnings,
+            if self.warnings == 1 { "" } else { "s" }
+        ));
+
+        status(match self.failure {
+            Some(ref reason) if reason.is_empty() => format!("Assembly failed for {}", self.file),
+            Some(ref reason) => format!("Assembly failed for {}: {}", self.file, reason),
+            None => String::from("Assembly successful"),
+        });
+    }
+}
+
+/// Write the program out as a relocatable object, for lc3ld to link later
+fn write_relocatable(file: &str, output: Output) -> std::io::Result<()> {
+    let path = Path::new(file).with_extension("rel");
+    let (program, linkage) = output.into_parts();
+
+    Object::new(file.to_string(), program, linkage).write(&mut BufWriter::new(File::create(path)?))
+}
+
+/// Parse a `-D NAME[=value]` option, where the value can be written in any of the
+/// forms an immediate can be (and defaults to 1)
+fn parse_define(define: &str) -> Option<(String, i16)> {
+    let (name, value) = match define.split_once('=') {
+        Some((name, value)) => (name, value),
+        None => (define, "1"),
+    };
+
+    let value = value.trim_start_matches('#');
+    let value = if let Some(hex) = value.strip_prefix('x').or_else(|| value.strip_prefix("0x")) {
+        u16::from_str_radix(hex, 16).ok().map(|value| value as i16)
+    } else if let Some(binary) = value.strip_prefix('b').or_else(|| value.strip_prefix("0b")) {
+        u16::from_str_radix(binary, 2)
+            .ok()
+            .map(|value| value as i16)
+    } else {
+        value.parse::<i16>().ok()
+    };
+
+    if name.is_empty() {
+        None
+    } else {
+        value.map(|value| (name.to_string(), value))
+    }
+}
+
+/// Parse a `-W` option: `error` to make every warning an error, `error=NAME` to make
+/// one lint's warnings errors, `no-NAME` to turn a lint off, or `NAME` to turn it back on.
+/// Every warning being made an error is given without a lint.
+fn parse_warning(option: &str) -> Option<(Option<Lint>, Level)> {
+    if option == "error" {
+        Some((None, Level::Error))
+    } else if let Some(name) = option.strip_prefix("error=") {
+        Lint::from_name(name).map(|lint| (Some(lint), Level::Error))
+    } else if let Some(name) = option.strip_prefix("no-") {
+        Lint::from_name(name).map(|lint| (Some(lint), Level::Allow))
+    } else {
+        Lint::from_name(option).map(|lint| (Some(lint), Level::Warn))
+    }
+}
+
+fn assemble(
+    file: &str,
+    print_ast: bool,
+    relocatable: bool,
+    defines: &[(String, i16)],
+    includes: &[PathBuf],
+    warnings: &[(Option<Lint>, Level)],
+    format: Format,
+) -> Report {
+    let mut report = Report::new(file, format);
+
+    let assembler = match Assembler::from_file(file.to_string()) {
+        Ok(assembler) => assembler,
+        Err(error) => {
+            // Raised like any other error, so that JSON and SARIF readers see it too
+            let diagnostic = Diagnostic::Note(Note::new(
+                DiagType::Error,
+                file.to_string(),
+                0,
+                0,
+                format!("Unable to read the file: {}", error),
+            ))
+            .with_code("io-error");
+
+            report.tally(&Session::new(), &[diagnostic]);
+            report.failure = Some(error.to_string());
+            return report;
+        }
+    };
+
+    let mut session = Session::new();
+    defines
+        .iter()
+        .for_each(|(name, value)| session.define(name.clone(), *value));
+    includes
+        .iter()
+        .for_each(|directory| session.add_include_directory(directory.clone()));
+    warnings.iter().for_each(|(lint, level)| match lint {
+        Some(lint) => session.set_lint_level(*lint, *level),
+        None => session.set_warnings_as_errors(true),
+    });
+
+    match assembler.assemble_with(&mut session, print_ast) {
+        Ok(output) => {
+            report.tally(&session, output.warnings());
+
+            let written = if relocatable {
+                write_relocatable(file, output)
+            } else if output.linkage().is_relocatable() {
+                report.failure = Some(String::from(
+                    "it refers to external symbols, so assemble it with --relocatable and link it with lc3ld",
+                ));
+                return report;
+            } else {
+                Writer::new()
+                    .register_all(file)
+                    .write(output.into_program())
+            };
+
+            if let Err(error) = written {
+                report.failure = Some(error.to_string());
+            }
+        }
+        Err(diagnostics) => {
+            report.tally(&session, &diagnostics);
+            report.failure = Some(String::new());
+        }
+    }
+
+    report
+}
 
 fn main() {
     let args = App::new("LC3AS")
@@ -19,35 +269,221 @@ fn main() {
                 .long("quiet")
                 .short("q"),
         )
+        .arg(
+            Arg::with_name("relocatable")
+                .help("Write a relocatable object (.rel) to be linked with lc3ld")
+                .long("relocatable")
+                .short("c"),
+        )
+        .arg(
+            Arg::with_name("define")
+                .help("Define a symbol for .IF/.IFDEF/.IFNDEF, e.g. -D DEBUG or -D LEVEL=2")
+                .short("D")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("include")
+                .help("Search a directory for .INCLUDE files, after the including file's own")
+                .short("I")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("warning")
+                .help(
+                    "Turn a warning off (-W no-NAME) or on (-W NAME), or make it an error \
+                     (-W error=NAME), or make every warning an error (-Werror)",
+                )
+                .short("W")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("diagnostics format")
+                .help(
+                    "How to write diagnostics: rendered for people, as JSON (one object per \
+                     line), or as a SARIF log",
+                )
+                .long("diagnostics-format")
+                .takes_value(true)
+                .possible_values(&["human", "json", "sarif"])
+                .default_value("human"),
+        )
+        .arg(
+            Arg::with_name("color")
+                .help(
+                    "When to colour diagnostics: always, never, or only when they're written \
+                     to a terminal",
+                )
+                .long("color")
+                .takes_value(true)
+                .possible_values(&["auto", "always", "never"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .help("How many files to assemble at once (defaults to the number of CPUs)")
+                .long("jobs")
+                .short("j")
+                .takes_value(true),
+        )
         .get_matches();
 
-    let files: Vec<&str> = args.values_of("files").unwrap().collect();
-    let should_print_ast = args.is_present("print-ast");
-
-    notifier::register(
-        String::from("lc3as"),
-        if args.is_present("quiet") {
-            notifier::Notifier::Standard(notifier::Stdout::Quiet)
-        } else {
-            notifier::Notifier::Standard(notifier::Stdout::Colour)
-        },
-    );
-
-    files.into_iter().for_each(move |file| {
-        Assembler::from_file(file.to_string())
-            .map(|assembler| {
-                println!("Assembling file {}", file);
-                assembler
-                    .assemble(should_print_ast)
-                    .map(|program| Writer::new().register_all(file).write(program))
-                    .map_or_else(
-                        || println!("Assembly failed for {}", file),
-                        |_| println!("Assembly successful"),
-                    )
+    let files: Vec<String> = args.values_of("files").unwrap().map(String::from).collect();
+    let should_print_ast = args.is_present("print ast");
+    let quiet = args.is_present("quiet");
+    let relocatable = args.is_present("relocatable");
+    let colour = match args.value_of("color") {
+        Some("always") => true,
+        Some("never") => false,
+        _ => io::stdout().is_terminal(),
+    };
+    // Otherwise colours are left out whenever stdout isn't a terminal
+    colored::control::set_override(colour);
+
+    let format = match args.value_of("diagnostics format") {
+        Some("json") => Format::Json,
+        Some("sarif") => Format::Sarif,
+        _ => Format::Human { colour },
+    };
+
+    let defines = args.values_of("define").map_or_else(Vec::new, |defines| {
+        defines
+            .map(|define| {
+                parse_define(define).unwrap_or_else(|| {
+                    eprintln!("-D expects NAME or NAME=value, but found {}", define);
+                    process::exit(2);
+                })
             })
-            .expect("There was a problem with the file");
+            .collect()
+    });
+
+    let includes: Vec<PathBuf> = args
+        .values_of("include")
+        .map_or_else(Vec::new, |includes| includes.map(PathBuf::from).collect());
 
-        // Clear all notifications
-        notifier::clear(None);
+    let warnings = args.values_of("warning").map_or_else(Vec::new, |warnings| {
+        warnings
+            .map(|warning| {
+                parse_warning(warning).unwrap_or_else(|| {
+                    eprintln!(
+                        "-W expects error, error=NAME, no-NAME or NAME, where NAME is one of: {}",
+                        Lint::ALL
+                            .iter()
+                            .map(|lint| lint.name())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                    process::exit(2);
+                })
+            })
+            .collect()
     });
+
+    let jobs = match args.value_of("jobs").map(str::parse::<usize>) {
+        Some(Ok(jobs)) if jobs > 0 => jobs,
+        Some(_) => {
+            eprintln!("--jobs expects a positive number");
+            process::exit(2);
+        }
+        None => thread::available_parallelism().map_or(1, usize::from),
+    };
+
+    let files = Arc::new(files);
+    let next = Arc::new(AtomicUsize::new(0));
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let defines = Arc::new(defines);
+    let includes = Arc::new(includes);
+    let warnings = Arc::new(warnings);
+
+    let workers = (0..jobs.min(files.len()))
+        .map(|_| {
+            let files = Arc::clone(&files);
+            let next = Arc::clone(&next);
+            let reports = Arc::clone(&reports);
+            let defines = Arc::clone(&defines);
+            let includes = Arc::clone(&includes);
+            let warnings = Arc::clone(&warnings);
+
+            thread::spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+
+                if let Some(file) = files.get(index) {
+                    let report = assemble(
+                        file,
+                        should_print_ast,
+                        relocatable,
+                        &defines,
+                        &includes,
+                        &warnings,
+                        format,
+                    );
+                    reports.lock().unwrap().push((index, report));
+                } else {
+                    break;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    workers.into_iter().for_each(|worker| {
+        if worker.join().is_err() {
+            eprintln!("A worker thread panicked while assembling");
+        }
+    });
+
+    let mut reports = reports.lock().unwrap().drain(..).collect::<Vec<_>>();
+    reports.sort_by_key(|(index, _)| *index);
+
+    // Anything that never made it into a report must have panicked along the way
+    let failed = files.len() - reports.len()
+        + reports
+            .iter()
+            .filter(|(_, report)| report.failure.is_some())
+            .count();
+
+    reports.iter().for_each(|(_, report)| report.print(quiet));
+
+    if files.len() > 1 {
+        let summary = format!(
+            "{} of {} files assembled successfully",
+            files.len() - failed,
+            files.len()
+        );
+
+        match format {
+            Format::Human { .. } => println!("{}", summary),
+            Format::Json | Format::Sarif => eprintln!("{}", summary),
+        }
+    }
+
+    if format == Format::Sarif && !quiet {
+        let mut sarif = Sarif::new();
+        reports.iter().for_each(|(_, report)| {
+            sarif.add(&report.raised);
+
+            if let Some(reason) = report.unexplained_failure() {
+                sarif.fail(&report.file, reason);
+            }
+        });
+
+        // Nor did any file that was lost to a worker panicking
+        if reports.len() < files.len() {
+            files
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !reports.iter().any(|(done, _)| done == index))
+                .for_each(|(_, file)| sarif.fail(file, "the assembler panicked"));
+        }
+
+        println!("{:#}", sarif.to_json());
+    }
+
+    if failed > 0 {
+        process::exit(1);
+    }
 }
@@ -2,52 +2,56 @@ use std::{
     collections::HashMap,
     fs::File,
     io::{BufReader, Error, Read},
-    sync::Mutex,
 };
 
 use crate::{
-    lexer, notifier, parser,
-    token::{tokens::traits::Assemble, traits::Requirements, Token},
-    types::{Program, SymbolTable},
+    err, lexer,
+    linker::Linkage,
+    lint,
+    notifier::{DiagType, Diagnostic, Highlight, Source},
+    parser,
+    session::Session,
+    token::{
+        tokens::{expression, traits::Assemble},
+        traits::Requirements,
+        Token,
+    },
+    types::{Program, Segment, SymbolTable},
 };
 
-#[derive(Default)]
-pub struct FileController {
-    files: HashMap<String, Vec<String>>,
+/// A successfully assembled program, along with any warnings raised along the way
+pub struct Output {
+    program: Program,
+    linkage: Linkage,
+    warnings: Vec<Diagnostic>,
 }
 
-pub fn add_file(file: String) {
-    let mut guard = FILE_CONTROLLER.lock().unwrap();
-    guard.add_file(file);
-}
-
-pub fn add_line(file: &str, line: String) {
-    let mut guard = FILE_CONTROLLER.lock().unwrap();
-    guard.add_line(&file, line);
-}
-
-#[must_use]
-pub fn get_line(file: &str, line: u64) -> String {
-    let guard = FILE_CONTROLLER.lock().unwrap();
-    guard.get_line(file, line)
-}
+impl Output {
+    #[must_use]
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
 
-impl FileController {
-    fn add_file(&mut self, file: String) {
-        self.files.insert(file, Vec::new());
+    #[must_use]
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
     }
 
-    pub fn add_line(&mut self, file: &str, line: String) {
-        self.files.get_mut(file).unwrap().push(line);
+    /// The symbols the program shares with others, and the references it needs
+    /// the linker to resolve
+    #[must_use]
+    pub fn linkage(&self) -> &Linkage {
+        &self.linkage
     }
 
     #[must_use]
-    pub fn get_line(&self, file: &str, line: u64) -> String {
-        self.files.get(file).unwrap()[(line - 1) as usize].clone()
+    pub fn into_program(self) -> Program {
+        self.program
     }
 
-    pub fn remove(&mut self, file: &str) {
-        self.files.remove(file);
+    #[must_use]
+    pub fn into_parts(self) -> (Program, Linkage) {
+        (self.program, self.linkage)
     }
 }
 
@@ -62,8 +66,6 @@ impl Assembler {
     /// # Errors
     ///   If the file fails to be opened/read from
     pub fn from_file(file: String) -> Result<Self, Error> {
-        add_file(file.to_string());
-
         let mut content = String::new();
 
         BufReader::new(File::open(file.clone())?).read_to_string(&mut content)?;
@@ -71,65 +73,178 @@ impl Assembler {
         Ok(Self { file, content })
     }
 
+    /// Create an assembler for source that's already been read, e.g. from an editor,
+    /// naming the file it belongs to
+    #[must_use]
+    pub fn from_source(file: String, content: String) -> Self {
+        Self { file, content }
+    }
+
     #[must_use]
     pub fn from_string(content: String) -> Self {
         let file = String::from("temp.asm");
-        add_file(file.clone());
         Self { file, content }
     }
 
     #[must_use]
-    pub(crate) fn lex(&self) -> Option<Vec<Token>> {
-        lexer::lex(&self.file, &self.content)
+    pub(crate) fn lex(&self, session: &mut Session) -> Option<Vec<Token>> {
+        lexer::lex(session, &self.file, &self.content)
     }
 
-    #[must_use]
-    fn parse(ast: Vec<Token>) -> Option<(Vec<Token>, SymbolTable)> {
-        parser::parse(ast)
+    /// Assemble the program in a session of its own
+    ///
+    /// # Errors
+    ///   If anything went wrong while assembling, every diagnostic that was raised
+    ///   (not only the errors) is returned instead of the program
+    pub fn assemble(self, do_print_ast: bool) -> Result<Output, Vec<Diagnostic>> {
+        self.assemble_with(&mut Session::new(), do_print_ast)
     }
 
-    #[must_use]
-    pub fn assemble(self, _do_print_ast: bool) -> Option<Program> {
-        self.lex()
-            .and_then(Self::parse)
-            .and_then(Self::do_second_pass)
+    /// Assemble the program in the session given, so that its notifiers hear about
+    /// any diagnostics as they're raised, and its files can be used to render them
+    ///
+    /// # Errors
+    ///   If anything went wrong while assembling, every diagnostic that was raised
+    ///   (not only the errors) is returned instead of the program
+    pub fn assemble_with(
+        self,
+        session: &mut Session,
+        _do_print_ast: bool,
+    ) -> Result<Output, Vec<Diagnostic>> {
+        let program = self
+            .lex(session)
+            .and_then(|tokens| parser::parse(session, tokens))
+            .map(|(tokens, symbols)| {
+                // Lints are only worth hearing about once the program itself makes sense
+                if session.error_count() == 0 {
+                    lint::lint(session, &tokens, &symbols);
+                }
+                (tokens, symbols)
+            })
+            .and_then(|parsed| Self::do_second_pass(session, parsed));
+
+        let diagnostics = session.take_diagnostics();
+        let linkage = session.take_linkage();
+
+        match program {
+            Some(program) => Ok(Output {
+                program,
+                linkage,
+                warnings: diagnostics,
+            }),
+            None => Err(diagnostics),
+        }
     }
 
-    fn do_second_pass((tokens, symbols): (Vec<Token>, SymbolTable)) -> Option<Program> {
+    fn do_second_pass(
+        session: &mut Session,
+        (tokens, symbols): (Vec<Token>, SymbolTable),
+    ) -> Option<Program> {
         let mut program_counter: i16 = 0;
 
-        // Initially order the symbols by their addresses, so that the search for a
-        // symbol is O(1)
-        let mut syms = symbols.values().collect::<Vec<_>>();
-        syms.sort_by_key(|sym| sym.address());
-
-        let mut syms = syms.iter().peekable();
-
-        let listings = tokens
-            .into_iter()
-            .flat_map(|token| {
-                // Ignore anything that doesn't have a memory requirement (which should basically be just
-                // labels, origins and ends)
-                let symbol = if token.memory_requirement() > 0 {
-                    match syms.peek() {
-                        Some(&&symbol) if symbol.address() == program_counter as u16 => {
-                            let _ = syms.next();
-                            symbol.symbol()
-                        }
-                        _ => "",
-                    }
-                } else {
-                    ""
-                };
-
-                token.assembled(&mut program_counter, &symbols, symbol)
-            })
-            .collect();
-
-        (notifier::error_count() == 0).then(|| (symbols, listings))
+        // Index the symbols by their addresses, so that finding the symbol for an
+        // address is O(1). Only the first word at an address gets the symbol, and
+        // constants aren't at an address at all.
+        let mut names = HashMap::new();
+        symbols
+            .values()
+            .filter(|symbol| !symbol.is_constant())
+            .for_each(|symbol| {
+                let _ = names.entry(symbol.address()).or_insert(symbol.symbol());
+            });
+
+        let mut segments: Vec<Segment> = Vec::new();
+        let mut origins = Vec::new();
+
+        tokens.into_iter().for_each(|mut token| {
+            expression::resolve(session, &mut token, &symbols, program_counter, false);
+
+            if let Token::Orig(_) = token {
+                origins.push((
+                    token.source(),
+                    token.column(),
+                    token.line(),
+                    token.token().len(),
+                ));
+
+                let header = token
+                    .assembled(session, &mut program_counter, &symbols, "")
+                    .pop();
+
+                segments.push(Segment::new(program_counter as u16, header));
+                return;
+            }
+
+            // Ignore anything that doesn't have a memory requirement (which should basically be just
+            // labels and ends)
+            let symbol = if token.memory_requirement() > 0 {
+                names.remove(&(program_counter as u16)).map_or("", |s| s)
+            } else {
+                ""
+            };
+
+            let origin = program_counter as u16;
+            let (file, line) = (token.file().to_string(), token.line());
+            let listings = token.assembled(session, &mut program_counter, &symbols, symbol);
+
+            if listings.is_empty() {
+                return;
+            }
+
+            if segments.is_empty() {
+                // Anything before the first .ORIG ends up in a segment of its own
+                segments.push(Segment::new(origin, None));
+            }
+
+            (0..listings.len()).for_each(|offset| {
+                session.add_source(origin.wrapping_add(offset as u16), &file, line);
+            });
+
+            segments.last_mut().unwrap().extend(listings);
+        });
+
+        Self::check_overlaps(session, &segments, &origins);
+
+        (session.error_count() == 0).then(|| (symbols, segments))
     }
-}
 
-lazy_static! {
-    pub static ref FILE_CONTROLLER: Mutex<FileController> = Mutex::new(FileController::default());
+    /// Complain about any segment that would overwrite (part of) another once loaded
+    fn check_overlaps(
+        session: &mut Session,
+        segments: &[Segment],
+        origins: &[(Source, u64, u64, usize)],
+    ) {
+        // Segments without a .ORIG can only come first, so they have nothing to point at
+        let offset = segments.len() - origins.len();
+
+        segments
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .for_each(|(index, segment)| {
+                if let Some(other) = segments[..index]
+                    .iter()
+                    .find(|other| segment.overlaps(other))
+                {
+                    let (file, column, line, width) = &origins[index - offset];
+
+                    err!(
+                        session,
+                        "overlapping-segments",
+                        Highlight,
+                        file.clone(),
+                        *column,
+                        *line,
+                        *width,
+                        format!(
+                            "Segment x{:04X}-x{:04X} overlaps the segment at x{:04X}-x{:04X}",
+                            segment.origin(),
+                            segment.end(),
+                            other.origin(),
+                            other.end()
+                        )
+                    );
+                }
+            });
+    }
 }
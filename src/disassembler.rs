@@ -0,0 +1,394 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+    io::{BufRead, Error, ErrorKind},
+};
+
+use crate::{lexer::tokenizer::Tokenizer, token::Symbol, types::SymbolTable};
+
+/// The names of the standard TRAP routines, indexed from `x20`
+const TRAPS: [&str; 6] = ["GETC", "OUT", "PUTS", "IN", "PUTSP", "HALT"];
+
+/// Read a symbol table written by `Format::SymbolTable`
+///
+/// # Errors
+///   If the symbol table can't be read, or one of its symbols is malformed
+pub fn read_symbols<R: BufRead>(input: R) -> Result<SymbolTable, Error> {
+    let mut symbols = SymbolTable::new();
+
+    // The first two lines are the header
+    for (number, line) in input.lines().enumerate().skip(2) {
+        let line = line?;
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+
+        let (name, address) = match fields.as_slice() {
+            [] => continue,
+            [name, address, ..] => (*name, u16::from_str_radix(address, 16).ok()),
+            [_] => (line.as_str(), None),
+        };
+
+        let address = address.ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Malformed symbol on line {}", number + 1),
+            )
+        })?;
+
+        let symbol = if fields.get(2) == Some(&"(constant)") {
+            Symbol::constant(address as i16, name.to_string())
+        } else {
+            Symbol::new(address, name.to_string())
+        };
+
+        symbols.insert(name.to_string(), symbol);
+    }
+
+    Ok(symbols)
+}
+
+/// What a word was decoded as, i.e. how it's written out and where it might go next
+struct Decoded {
+    text: String,
+    /// Whether running it can carry on to the next word
+    falls_through: bool,
+    /// Where it might jump to (for BR and JSR)
+    jumps_to: Option<u16>,
+}
+
+/// Turns the segments of an object back into source that assembles to the same words
+pub struct Disassembler<'a> {
+    segments: &'a [(u16, Vec<u16>)],
+    labels: HashMap<u16, String>,
+}
+
+impl<'a> Disassembler<'a> {
+    /// Get ready to disassemble the segments, naming addresses with the (address) symbols
+    /// given where possible
+    #[must_use]
+    pub fn new(segments: &'a [(u16, Vec<u16>)], symbols: &SymbolTable) -> Self {
+        let mut labels = HashMap::new();
+
+        symbols
+            .values()
+            .filter(|symbol| !symbol.is_constant())
+            // Qualified local and macro labels can't be written back out as they are
+            .filter(|symbol| {
+                Tokenizer::is_valid_label(symbol.symbol()) && !symbol.symbol().starts_with('.')
+            })
+            .for_each(|symbol| {
+                let label = labels
+                    .entry(symbol.address())
+                    .or_insert_with(|| symbol.symbol().clone());
+
+                // Only one label can be given to an address, so be consistent about which
+                if symbol.symbol() < label {
+                    *label = symbol.symbol().clone();
+                }
+            });
+
+        let mut disassembler = Self { segments, labels };
+
+        // Labels for anything outside of the segments have nowhere to be written
+        let loaded = disassembler
+            .labels
+            .keys()
+            .filter(|address| disassembler.is_loaded(**address))
+            .copied()
+            .collect::<BTreeSet<_>>();
+        disassembler
+            .labels
+            .retain(|address, _| loaded.contains(address));
+
+        disassembler
+    }
+
+    /// Whether the address has been loaded from the object
+    fn is_loaded(&self, address: u16) -> bool {
+        self.segments.iter().any(|(origin, words)| {
+            address.wrapping_sub(*origin) < words.len() as u16 && !words.is_empty()
+        })
+    }
+
+    /// Work out which words are instructions, by following each path through the code from
+    /// the start of each segment. Anything never reached is treated as data.
+    fn find_code(&self) -> BTreeSet<u16> {
+        let mut code = BTreeSet::new();
+        let mut pending = self
+            .segments
+            .iter()
+            .map(|(origin, _)| *origin)
+            .collect::<Vec<_>>();
+
+        while let Some(address) = pending.pop() {
+            if !self.is_loaded(address) || !code.insert(address) {
+                continue;
+            }
+
+            if let Some(decoded) = self.decode(address, self.word(address)) {
+                if decoded.falls_through {
+                    pending.push(address.wrapping_add(1));
+                }
+
+                pending.extend(decoded.jumps_to);
+            } else {
+                code.remove(&address);
+            }
+        }
+
+        code
+    }
+
+    fn word(&self, address: u16) -> u16 {
+        self.segments
+            .iter()
+            .find_map(|(origin, words)| words.get(address.wrapping_sub(*origin) as usize))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Give a label to each address referred to by an instruction, that doesn't already
+    /// have one from the symbol table
+    fn synthesise_labels(&mut self, code: &BTreeSet<u16>) {
+        let targets = code
+            .iter()
+            .filter_map(|address| {
+                let word = self.word(*address);
+                match word >> 12 {
+                    0x0 | 0x2 | 0x3 | 0x4 | 0xA | 0xB | 0xE => self.target(*address, word),
+                    _ => None,
+                }
+            })
+            .filter(|target| self.is_loaded(*target))
+            .collect::<Vec<_>>();
+
+        targets.into_iter().for_each(|target| {
+            let _ = self
+                .labels
+                .entry(target)
+                .or_insert_with(|| format!("L{:04X}", target));
+        });
+    }
+
+    /// The address a PC-relative instruction refers to
+    fn target(&self, address: u16, word: u16) -> Option<u16> {
+        let bits = match word >> 12 {
+            0x4 if word & 0x0800 != 0 => 11,
+            0x0 | 0x2 | 0x3 | 0xA | 0xB | 0xE => 9,
+            _ => return None,
+        };
+
+        Some(
+            address
+                .wrapping_add(1)
+                .wrapping_add(sign_extend(word, bits)),
+        )
+    }
+
+    /// The operand for a PC-relative instruction, which is the label of its target if it
+    /// has one, and its offset otherwise
+    fn operand(&self, address: u16, word: u16, bits: u32) -> String {
+        let target = address
+            .wrapping_add(1)
+            .wrapping_add(sign_extend(word, bits));
+
+        match self.labels.get(&target) {
+            Some(label) => label.clone(),
+            None => format!("#{}", sign_extend(word, bits) as i16),
+        }
+    }
+
+    /// Decode a word as an instruction, if it's one that assembles back to the same word
+    fn decode(&self, address: u16, word: u16) -> Option<Decoded> {
+        let destination = word >> 9 & 0x7;
+        let source = word >> 6 & 0x7;
+        let instruction = |text: String| Decoded {
+            text,
+            falls_through: true,
+            jumps_to: None,
+        };
+
+        Some(match word >> 12 {
+            opcode @ (0x1 | 0x5) => {
+                let name = if opcode == 0x1 { "ADD" } else { "AND" };
+
+                if word & 0x20 != 0 {
+                    instruction(format!(
+                        "{} R{}, R{}, #{}",
+                        name,
+                        destination,
+                        source,
+                        sign_extend(word, 5) as i16
+                    ))
+                } else if word & 0x18 == 0 {
+                    instruction(format!(
+                        "{} R{}, R{}, R{}",
+                        name,
+                        destination,
+                        source,
+                        word & 0x7
+                    ))
+                } else {
+                    return None;
+                }
+            }
+            0x0 => {
+                if destination == 0 {
+                    return None;
+                }
+
+                let condition = ["n", "z", "p"]
+                    .iter()
+                    .enumerate()
+                    .filter(|(bit, _)| word & (0x800 >> bit) != 0)
+                    .map(|(_, flag)| *flag)
+                    .collect::<String>();
+
+                Decoded {
+                    text: format!("BR{} {}", condition, self.operand(address, word, 9)),
+                    falls_through: destination != 0x7,
+                    jumps_to: self.target(address, word),
+                }
+            }
+            0xC => match (destination, word & 0x3F) {
+                (0, 0) if source == 7 => Decoded {
+                    text: String::from("RET"),
+                    falls_through: false,
+                    jumps_to: None,
+                },
+                (0, 0) => Decoded {
+                    text: format!("JMP R{}", source),
+                    falls_through: false,
+                    jumps_to: None,
+                },
+                (0, 1) => Decoded {
+                    text: format!("JMPT R{}", source),
+                    falls_through: false,
+                    jumps_to: None,
+                },
+                _ => return None,
+            },
+            0x4 => {
+                if word & 0x800 != 0 {
+                    Decoded {
+                        text: format!("JSR {}", self.operand(address, word, 11)),
+                        falls_through: true,
+                        jumps_to: self.target(address, word),
+                    }
+                } else if word & 0x63F == 0 {
+                    instruction(format!("JSRR R{}", source))
+                } else {
+                    return None;
+                }
+            }
+            opcode @ (0x2 | 0x3 | 0xA | 0xB | 0xE) => {
+                let name = match opcode {
+                    0x2 => "LD",
+                    0x3 => "ST",
+                    0xA => "LDI",
+                    0xB => "STI",
+                    _ => "LEA",
+                };
+
+                instruction(format!(
+                    "{} R{}, {}",
+                    name,
+                    destination,
+                    self.operand(address, word, 9)
+                ))
+            }
+            opcode @ (0x6 | 0x7) => instruction(format!(
+                "{} R{}, R{}, #{}",
+                if opcode == 0x6 { "LDR" } else { "STR" },
+                destination,
+                source,
+                sign_extend(word, 6) as i16
+            )),
+            0x9 if word & 0x3F == 0x3F => instruction(format!("NOT R{}, R{}", destination, source)),
+            0x8 if word == 0x8000 => Decoded {
+                text: String::from("RTI"),
+                falls_through: false,
+                jumps_to: None,
+            },
+            0xF if word & 0x0F00 == 0 => {
+                let vector = word & 0xFF;
+
+                Decoded {
+                    text: match vector {
+                        0x20..=0x25 => TRAPS[usize::from(vector - 0x20)].to_string(),
+                        _ => format!("TRAP x{:02X}", vector),
+                    },
+                    falls_through: vector != 0x25,
+                    jumps_to: None,
+                }
+            }
+            _ => return None,
+        })
+    }
+
+    /// The string starting at `address`, if the words from there on are printable
+    /// characters ending with a zero (and none of them but the first has a label)
+    fn string(&self, address: u16, code: &BTreeSet<u16>) -> Option<(String, u16)> {
+        let mut string = String::new();
+        let mut current = address;
+
+        loop {
+            if !self.is_loaded(current)
+                || code.contains(&current)
+                || (current != address && self.labels.contains_key(&current))
+            {
+                return None;
+            }
+
+            match self.word(current) {
+                0 if !string.is_empty() => return Some((string, current - address + 1)),
+                0x0A => string.push_str("\\n"),
+                0x09 => string.push_str("\\t"),
+                0x22 => string.push_str("\\\""),
+                0x5C => string.push_str("\\\\"),
+                character @ 0x20..=0x7E => string.push(char::from(character as u8)),
+                _ => return None,
+            }
+
+            current = current.wrapping_add(1);
+        }
+    }
+
+    /// Write out source for the segments, which assembles back to the same words
+    #[must_use]
+    pub fn disassemble(mut self) -> String {
+        let code = self.find_code();
+        self.synthesise_labels(&code);
+
+        let mut output = String::new();
+
+        self.segments.iter().for_each(|(origin, words)| {
+            let _ = writeln!(output, ".ORIG x{:04X}", origin);
+
+            let mut offset = 0;
+            while offset < words.len() {
+                let address = origin.wrapping_add(offset as u16);
+                let label = self.labels.get(&address).map_or("", String::as_str);
+
+                let (text, length) = match self.decode(address, words[offset]) {
+                    Some(decoded) if code.contains(&address) => (decoded.text, 1),
+                    _ => match self.string(address, &code) {
+                        Some((string, length)) => (format!(".STRINGZ \"{}\"", string), length),
+                        None => (format!(".FILL x{:04X}", words[offset]), 1),
+                    },
+                };
+
+                let _ = writeln!(output, "{: <20} {}", label, text);
+                offset += usize::from(length);
+            }
+        });
+
+        output.push_str(".END\n");
+        output
+    }
+}
+
+/// Sign extend the low `bits` bits of a word
+fn sign_extend(word: u16, bits: u32) -> u16 {
+    let shift = 16 - bits;
+    ((word << shift) as i16 >> shift) as u16
+}
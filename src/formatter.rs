@@ -0,0 +1,272 @@
+use std::collections::HashSet;
+
+use crate::{
+    lexer::tokenizer::{Span, Tokenizer},
+    session::Session,
+    token::Token,
+};
+
+/// The case to write mnemonics, directives or registers in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    Upper,
+    Lower,
+    /// However they were written
+    Keep,
+}
+
+/// How to write immediates, e.g. `#10` or `x000A`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Immediates {
+    Decimal,
+    Hexadecimal,
+    /// However they were written
+    Keep,
+}
+
+/// Lays out source consistently: labels at the start of the line, mnemonics and
+/// directives in one column and comments in another.
+///
+/// Formatting only moves tokens around and changes how they're written, so the
+/// program assembles to exactly the same thing. Any line the tokenizer isn't happy
+/// with is left alone.
+pub struct Formatter {
+    instruction_column: usize,
+    comment_column: usize,
+    mnemonics: Case,
+    registers: Case,
+    immediates: Immediates,
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Self {
+            instruction_column: 12,
+            comment_column: 40,
+            mnemonics: Case::Upper,
+            registers: Case::Upper,
+            immediates: Immediates::Keep,
+        }
+    }
+}
+
+impl Formatter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The column (from 0) mnemonics and directives start in
+    #[must_use]
+    pub fn instruction_column(mut self, column: usize) -> Self {
+        self.instruction_column = column;
+        self
+    }
+
+    /// The column (from 0) comments after an instruction start in
+    #[must_use]
+    pub fn comment_column(mut self, column: usize) -> Self {
+        self.comment_column = column;
+        self
+    }
+
+    #[must_use]
+    pub fn mnemonics(mut self, case: Case) -> Self {
+        self.mnemonics = case;
+        self
+    }
+
+    #[must_use]
+    pub fn registers(mut self, case: Case) -> Self {
+        self.registers = case;
+        self
+    }
+
+    #[must_use]
+    pub fn immediates(mut self, immediates: Immediates) -> Self {
+        self.immediates = immediates;
+        self
+    }
+
+    /// Format the source of a file
+    #[must_use]
+    pub fn format(&self, source: &str) -> String {
+        let file = "format.asm";
+        let mut session = Session::new();
+        session.add_file(file.to_string());
+
+        let lines = source
+            .lines()
+            .enumerate()
+            .map(|(number, line)| {
+                let diagnostics = session.diagnostics().len();
+                let mut tokenizer = Tokenizer::new(&mut session, file, line, number as u64 + 1);
+                let tokens = tokenizer.spans();
+                let comment = tokenizer.comment().map(str::to_string);
+
+                // Anything the tokenizer complained about or skipped over might not survive
+                // being moved around
+                let understood = session.diagnostics().len() == diagnostics
+                    && Self::is_covered(line, &tokens, comment.as_deref());
+
+                (line, understood.then_some((tokens, comment)))
+            })
+            .collect::<Vec<_>>();
+
+        // Macro invocations look like labels, so find the macros to tell them apart
+        let macros = lines
+            .iter()
+            .filter_map(|(_, tokens)| match tokens.as_ref()?.0.as_slice() {
+                [(directive, _), (Token::Label(name), _), ..]
+                    if directive.token().eq_ignore_ascii_case(".MACRO") =>
+                {
+                    Some(name.token().to_ascii_uppercase())
+                }
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+
+        lines
+            .into_iter()
+            .map(|(line, tokens)| match tokens {
+                Some((tokens, comment)) => {
+                    self.format_line(line, &tokens, comment.as_deref(), &macros)
+                }
+                None => line.trim_end().to_string(),
+            })
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    /// Whether the tokens (and comment) account for everything on the line, other than
+    /// the whitespace, commas and colons between them
+    fn is_covered(line: &str, tokens: &[(Token, Span)], comment: Option<&str>) -> bool {
+        let code = line.chars().count()
+            - comment.map_or(0, |comment| {
+                comment.chars().count() + (line.chars().count() - line.trim_end().chars().count())
+            });
+
+        line.chars().take(code).enumerate().all(|(index, ch)| {
+            let column = index as u64 + 1;
+            ch.is_whitespace()
+                || ch == ','
+                || ch == ':'
+                || tokens
+                    .iter()
+                    .any(|(_, span)| span.start <= column && column < span.end)
+        })
+    }
+
+    fn format_line(
+        &self,
+        line: &str,
+        tokens: &[(Token, Span)],
+        comment: Option<&str>,
+        macros: &HashSet<String>,
+    ) -> String {
+        let text = |span: &Span| -> String {
+            line.chars()
+                .skip(span.start as usize - 1)
+                .take((span.end - span.start) as usize)
+                .collect()
+        };
+
+        let (label, rest) = match tokens {
+            [(Token::Label(name), span), rest @ ..]
+                if !macros.contains(&name.token().to_ascii_uppercase()) =>
+            {
+                (text(span), rest)
+            }
+            _ => (String::new(), tokens),
+        };
+
+        let mut formatted = label;
+
+        if let [(first, span), operands @ ..] = rest {
+            let mut instruction = self.keyword(first, &text(span));
+
+            if !operands.is_empty() {
+                instruction.push(' ');
+                instruction.push_str(
+                    &operands
+                        .iter()
+                        .map(|(token, span)| self.operand(token, &text(span)))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                );
+            }
+
+            Self::pad(&mut formatted, self.instruction_column);
+            formatted.push_str(&instruction);
+        }
+
+        if let Some(comment) = comment {
+            if formatted.is_empty() {
+                // Comments on a line of their own either stay at the start of the line, or
+                // line up with the instructions
+                let at_start = line.starts_with(comment);
+                if !at_start {
+                    Self::pad(&mut formatted, self.instruction_column);
+                }
+            } else {
+                Self::pad(&mut formatted, self.comment_column);
+            }
+
+            formatted.push_str(comment);
+        }
+
+        formatted
+    }
+
+    /// Pad the line with spaces up to the column, or with a single space if it's
+    /// already past it
+    fn pad(line: &mut String, column: usize) {
+        let length = line.chars().count();
+
+        if length == 0 {
+            line.push_str(&" ".repeat(column));
+        } else if length < column {
+            line.push_str(&" ".repeat(column - length));
+        } else {
+            line.push(' ');
+        }
+    }
+
+    /// Write the mnemonic or directive (or macro) starting an instruction
+    fn keyword(&self, token: &Token, text: &str) -> String {
+        match token {
+            Token::Label(_)
+            | Token::Register(_)
+            | Token::Immediate(_)
+            | Token::Expression(_)
+            | Token::Character(_)
+            | Token::String(_) => self.operand(token, text),
+            // The condition codes read better in lower case either way
+            Token::Br(_) => match self.mnemonics {
+                Case::Upper => String::from("BR") + &text[2..].to_ascii_lowercase(),
+                Case::Lower => text.to_ascii_lowercase(),
+                Case::Keep => text.to_string(),
+            },
+            _ => Self::case(text, self.mnemonics),
+        }
+    }
+
+    fn operand(&self, token: &Token, text: &str) -> String {
+        match (token, self.immediates) {
+            (Token::Register(_), _) => Self::case(text, self.registers),
+            (Token::Immediate(immediate), Immediates::Decimal) => format!("#{}", immediate.value),
+            (Token::Immediate(immediate), Immediates::Hexadecimal) => {
+                format!("x{:04X}", immediate.value as u16)
+            }
+            _ => text.to_string(),
+        }
+    }
+
+    fn case(text: &str, case: Case) -> String {
+        match case {
+            Case::Upper => text.to_ascii_uppercase(),
+            Case::Lower => text.to_ascii_lowercase(),
+            Case::Keep => text.to_string(),
+        }
+    }
+}
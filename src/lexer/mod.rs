@@ -1,17 +1,32 @@
-use std::iter::Iterator;
-
-use crate::{lexer::tokenizer::Tokenizer, notifier, token::Token};
+use crate::{
+    lexer::{preprocessor::Preprocessor, tokenizer::Tokenizer},
+    session::Session,
+    token::Token,
+};
 
+pub mod preprocessor;
 pub mod tokenizer;
 
 /// Lex a file given its content
 #[must_use]
-pub fn lex(file: &str, content: &str) -> Option<Vec<Token>> {
-    let tokens = content
-        .lines()
-        .enumerate()
-        .flat_map(|(line_number, line)| Tokenizer::new(file, &line, line_number as u64 + 1))
-        .collect();
+pub fn lex(session: &mut Session, file: &str, content: &str) -> Option<Vec<Token>> {
+    session.add_file(file.to_string());
+
+    let mut tokens = Vec::new();
+    let mut preprocessor = Preprocessor::default();
+
+    for (line_number, line) in content.lines().enumerate() {
+        if preprocessor.is_skipping() && !Preprocessor::is_conditional(line) {
+            // Lines that aren't being assembled mustn't raise any diagnostics
+            session.add_line(file, line.to_string());
+            continue;
+        }
+
+        let line = Tokenizer::new(session, file, line, line_number as u64 + 1).collect();
+        tokens.extend(preprocessor.process(session, line));
+    }
+
+    preprocessor.finish(session);
 
-    (notifier::error_count() == 0).then(|| tokens)
+    (session.error_count() == 0).then(|| tokens)
 }
@@ -0,0 +1,590 @@
+use std::collections::VecDeque;
+
+use crate::{
+    err,
+    notifier::{DiagType, Diagnostic, Highlight, Source},
+    session::Session,
+    token::{
+        tokens::expression::{Error, Expression, Node},
+        traits::Requirements,
+        Token,
+    },
+};
+
+/// A macro defined in the source with `.MACRO NAME PARAMETERS...` and closed with `.ENDM`
+#[derive(Clone)]
+pub struct Macro {
+    name: Token,
+    parameters: Vec<String>,
+    body: Vec<Vec<Token>>,
+}
+
+/// Where a macro was expanded from, so that diagnostics raised inside the expansion
+/// can point back at the call site
+pub(crate) struct Expansion {
+    name: String,
+    /// Where it was expanded, which may be in another expansion
+    source: Source,
+    column: u64,
+    line: u64,
+    width: usize,
+}
+
+impl Expansion {
+    #[must_use]
+    pub(crate) fn name(&self) -> &String {
+        &self.name
+    }
+
+    #[must_use]
+    pub(crate) fn source(&self) -> &Source {
+        &self.source
+    }
+
+    #[must_use]
+    pub(crate) fn column(&self) -> u64 {
+        self.column
+    }
+
+    #[must_use]
+    pub(crate) fn line(&self) -> u64 {
+        self.line
+    }
+
+    #[must_use]
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+}
+
+/// An `.IF`, `.IFDEF` or `.IFNDEF` that hasn't been closed by an `.ENDIF` yet
+struct Condition {
+    directive: Token,
+    /// Whether the lines in the current branch are being assembled
+    active: bool,
+    /// Whether any branch has been assembled so far
+    taken: bool,
+    /// Whether the lines around the condition are being assembled
+    enclosing: bool,
+    seen_else: bool,
+}
+
+/// Macros can't be invoked in their own bodies, but they can still invoke each
+/// other, so stop somewhere before running out of stack
+const MAXIMUM_DEPTH: usize = 64;
+
+fn is_directive(token: &Token, directive: &str) -> bool {
+    match token {
+        Token::Label(label) => label.token().eq_ignore_ascii_case(directive),
+        _ => false,
+    }
+}
+
+/// Handles everything that happens to a line after it's been tokenized, but before
+/// it's parsed, i.e. conditional assembly, macro definitions and their expansions
+#[derive(Default)]
+pub struct Preprocessor {
+    definition: Option<Macro>,
+    conditions: Vec<Condition>,
+}
+
+impl Preprocessor {
+    /// Whether the lines being read are in a branch of a condition that isn't being
+    /// assembled, and so don't need to be tokenized (unless they're conditions themselves)
+    #[must_use]
+    pub fn is_skipping(&self) -> bool {
+        self.conditions
+            .last()
+            .is_some_and(|condition| !condition.active)
+    }
+
+    /// Whether the line given starts with one of the conditional assembly directives
+    #[must_use]
+    pub fn is_conditional(line: &str) -> bool {
+        let directive = line
+            .trim_start()
+            .split(|c: char| c.is_whitespace() || c == ';')
+            .next()
+            .unwrap_or("")
+            .to_ascii_uppercase();
+
+        [".IF", ".IFDEF", ".IFNDEF", ".ELSE", ".ENDIF"].contains(&directive.as_str())
+    }
+
+    /// Take a line of tokens, handing back whatever should be parsed in its place
+    pub fn process(&mut self, session: &mut Session, line: Vec<Token>) -> Vec<Token> {
+        match line.first() {
+            Some(Token::If(_)) | Some(Token::Ifdef(_)) | Some(Token::Ifndef(_)) => {
+                self.open_condition(session, line);
+                return Vec::new();
+            }
+            Some(Token::Else(_)) => {
+                self.switch_condition(session, &line[0]);
+                return Vec::new();
+            }
+            Some(Token::Endif(_)) => {
+                self.close_condition(session, &line[0]);
+                return Vec::new();
+            }
+            _ if self.is_skipping() => return Vec::new(),
+            _ => {}
+        }
+
+        match line.first() {
+            Some(token) if is_directive(token, ".MACRO") => {
+                self.define(session, line);
+                Vec::new()
+            }
+            Some(token) if is_directive(token, ".ENDM") => {
+                self.finish_definition(session, token);
+                Vec::new()
+            }
+            _ => {
+                if let Some(ref mut definition) = self.definition {
+                    if !line.is_empty() {
+                        definition.body.push(line);
+                    }
+                    Vec::new()
+                } else {
+                    Self::expand(session, line, &mut Vec::new())
+                }
+            }
+        }
+    }
+
+    /// Complain about any condition or macro that was never closed by the end of the file
+    pub fn finish(self, session: &mut Session) {
+        self.conditions.iter().for_each(|condition| {
+            let directive = &condition.directive;
+            err!(
+                session,
+                "unclosed-condition",
+                Highlight,
+                directive.file().to_string(),
+                directive.column(),
+                directive.line(),
+                directive.token().len(),
+                format!("{} is never closed with .ENDIF", directive.token())
+            );
+        });
+
+        if let Some(definition) = self.definition {
+            let name = &definition.name;
+            err!(
+                session,
+                "unclosed-macro",
+                Highlight,
+                name.file().to_string(),
+                name.column(),
+                name.line(),
+                name.token().len(),
+                format!("Macro {} is never closed with .ENDM", name.token())
+            );
+        }
+    }
+
+    fn open_condition(&mut self, session: &mut Session, line: Vec<Token>) {
+        let mut line = line.into_iter();
+        let mut directive = line.next().unwrap();
+        let enclosing = !self.is_skipping();
+
+        // Nothing in a branch that isn't being assembled is checked, including conditions
+        let value = enclosing && {
+            let _ = directive.consume(session, line.collect::<VecDeque<_>>());
+
+            match directive {
+                Token::If(ref token) => match token.operands().first() {
+                    Some(Token::Immediate(immediate)) => immediate.value != 0,
+                    Some(Token::Expression(expression)) => {
+                        match expression
+                            .node
+                            .evaluate(&|symbol| session.defined(symbol).map(i32::from))
+                        {
+                            Ok(value) => value != 0,
+                            Err(error) => {
+                                err!(
+                                    session,
+                                    "invalid-condition",
+                                    Highlight,
+                                    expression.file().to_string(),
+                                    expression.column(),
+                                    expression.line(),
+                                    expression.token().len(),
+                                    match error {
+                                        Error::Undefined(symbol) => {
+                                            format!("{} has not been defined", symbol)
+                                        }
+                                        Error::DivisionByZero => format!(
+                                            "Expression '{}' divides by zero",
+                                            expression.token()
+                                        ),
+                                    }
+                                );
+                                false
+                            }
+                        }
+                    }
+                    Some(Token::Label(label)) => {
+                        if let Some(value) = session.defined(label.token()) {
+                            value != 0
+                        } else {
+                            err!(
+                                session,
+                                "invalid-condition",
+                                Highlight,
+                                label.file().to_string(),
+                                label.column(),
+                                label.line(),
+                                label.token().len(),
+                                format!("{} has not been defined", label.token())
+                            );
+                            false
+                        }
+                    }
+                    _ => false,
+                },
+                Token::Ifdef(ref token) => match token.operands().first() {
+                    Some(Token::Label(label)) => session.defined(label.token()).is_some(),
+                    _ => false,
+                },
+                Token::Ifndef(ref token) => match token.operands().first() {
+                    Some(Token::Label(label)) => session.defined(label.token()).is_none(),
+                    _ => false,
+                },
+                _ => unreachable!(),
+            }
+        };
+
+        self.conditions.push(Condition {
+            directive,
+            active: value,
+            taken: value,
+            enclosing,
+            seen_else: false,
+        });
+    }
+
+    fn switch_condition(&mut self, session: &mut Session, directive: &Token) {
+        match self.conditions.last_mut() {
+            Some(condition) if !condition.seen_else => {
+                condition.active = condition.enclosing && !condition.taken;
+                condition.taken = true;
+                condition.seen_else = true;
+            }
+            Some(condition) => {
+                if condition.enclosing {
+                    err!(
+                        session,
+                        "duplicate-else",
+                        Highlight,
+                        directive.file().to_string(),
+                        directive.column(),
+                        directive.line(),
+                        directive.token().len(),
+                        format!("{} already has an .ELSE", condition.directive.token())
+                    );
+                }
+            }
+            None => {
+                err!(
+                    session,
+                    "unmatched-else",
+                    Highlight,
+                    directive.file().to_string(),
+                    directive.column(),
+                    directive.line(),
+                    directive.token().len(),
+                    String::from("Found .ELSE without a matching .IF")
+                );
+            }
+        }
+    }
+
+    fn close_condition(&mut self, session: &mut Session, directive: &Token) {
+        if self.conditions.pop().is_none() {
+            err!(
+                session,
+                "unmatched-endif",
+                Highlight,
+                directive.file().to_string(),
+                directive.column(),
+                directive.line(),
+                directive.token().len(),
+                String::from("Found .ENDIF without a matching .IF")
+            );
+        }
+    }
+
+    fn define(&mut self, session: &mut Session, line: Vec<Token>) {
+        let mut line = line.into_iter();
+        let directive = line.next().unwrap();
+
+        if self.definition.is_some() {
+            err!(
+                session,
+                "nested-macro",
+                Highlight,
+                directive.file().to_string(),
+                directive.column(),
+                directive.line(),
+                directive.token().len(),
+                String::from("Macros can't be defined inside of another macro")
+            );
+            return;
+        }
+
+        let name = match line.next() {
+            Some(name @ Token::Label(_)) => name,
+            found => {
+                let (column, line, width) = found.as_ref().map_or(
+                    (
+                        directive.column(),
+                        directive.line(),
+                        directive.token().len(),
+                    ),
+                    |found| (found.column(), found.line(), found.token().len()),
+                );
+                err!(
+                    session,
+                    "invalid-macro",
+                    Highlight,
+                    directive.file().to_string(),
+                    column,
+                    line,
+                    width,
+                    String::from("Expected to find the name of the macro")
+                );
+                return;
+            }
+        };
+
+        let mut parameters = Vec::new();
+        for parameter in line {
+            if let Token::Label(ref label) = parameter {
+                parameters.push(label.token().to_string());
+            } else {
+                err!(
+                    session,
+                    "invalid-macro",
+                    Highlight,
+                    parameter.file().to_string(),
+                    parameter.column(),
+                    parameter.line(),
+                    parameter.token().len(),
+                    format!(
+                        "Expected to find a parameter name, but found '{}'",
+                        parameter.token()
+                    )
+                );
+            }
+        }
+
+        self.definition = Some(Macro {
+            name,
+            parameters,
+            body: Vec::new(),
+        });
+    }
+
+    fn finish_definition(&mut self, session: &mut Session, directive: &Token) {
+        let definition = match self.definition.take() {
+            Some(definition) => definition,
+            None => {
+                err!(
+                    session,
+                    "unmatched-endm",
+                    Highlight,
+                    directive.file().to_string(),
+                    directive.column(),
+                    directive.line(),
+                    directive.token().len(),
+                    String::from("Found .ENDM without a matching .MACRO")
+                );
+                return;
+            }
+        };
+
+        let name = definition.name.token();
+
+        if let Some(previous) = session.macros().get(&name) {
+            let previous = previous.name.clone();
+            err!(
+                session,
+                "duplicate-macro",
+                Highlight,
+                definition.name.file().to_string(),
+                definition.name.column(),
+                definition.name.line(),
+                name.len(),
+                format!("Macro {} has already been defined", name)
+            );
+            session.add_diagnostic(Diagnostic::Highlight(Highlight::new(
+                DiagType::Note,
+                previous.file().to_string(),
+                previous.column(),
+                previous.line(),
+                name.len(),
+                String::from("Previously defined here"),
+            )));
+        } else {
+            session.macros_mut().insert(name, definition);
+        }
+    }
+
+    /// Replace an invocation of a macro on this line (if there is one) with its body,
+    /// expanding any macros invoked by the body along the way
+    fn expand(session: &mut Session, mut line: Vec<Token>, stack: &mut Vec<String>) -> Vec<Token> {
+        let is_macro = |token: &Token| match token {
+            Token::Label(label) => session.macros().contains_key(label.token()),
+            _ => false,
+        };
+
+        // Invocations can be labelled, just like any instruction
+        let index = match line.as_slice() {
+            [first, ..] if is_macro(first) => 0,
+            [Token::Label(_), second, ..] if is_macro(second) => 1,
+            _ => return line,
+        };
+
+        let arguments = line.split_off(index + 1);
+        let call = line.pop().unwrap();
+        let name = call.token();
+
+        let at = (call.source(), call.column(), call.line(), name.len());
+
+        if stack.contains(&name) || stack.len() >= MAXIMUM_DEPTH {
+            err!(
+                session,
+                "recursive-macro",
+                Highlight,
+                at.0,
+                at.1,
+                at.2,
+                at.3,
+                format!("Macro {} can't be expanded inside of itself", name)
+            );
+            return line;
+        }
+
+        let definition = session.macros()[&name].clone();
+
+        if arguments.len() != definition.parameters.len() {
+            err!(
+                session,
+                "macro-arguments",
+                Highlight,
+                at.0,
+                at.1,
+                at.2,
+                at.3,
+                format!(
+                    "Macro {} expects {} argument{}, but {} {} found",
+                    name,
+                    definition.parameters.len(),
+                    if definition.parameters.len() == 1 {
+                        ""
+                    } else {
+                        "s"
+                    },
+                    arguments.len(),
+                    if arguments.len() == 1 { "was" } else { "were" }
+                )
+            );
+            return line;
+        }
+
+        // Any label defined in the body gets a name of its own each time the macro is
+        // expanded, so that the macro can be used more than once
+        let locals = definition
+            .body
+            .iter()
+            .filter_map(|tokens| match tokens.first() {
+                Some(Token::Label(label))
+                    if !definition.parameters.contains(label.token())
+                        && !session.macros().contains_key(label.token()) =>
+                {
+                    Some(label.token().to_string())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let expansion = session.add_expansion(Expansion {
+            name: name.clone(),
+            source: at.0,
+            column: at.1,
+            line: at.2,
+            width: at.3,
+        });
+
+        stack.push(name.clone());
+
+        definition.body.iter().for_each(|tokens| {
+            let tokens = tokens
+                .iter()
+                .map(|token| match token {
+                    Token::Label(label) => {
+                        if let Some(index) = definition
+                            .parameters
+                            .iter()
+                            .position(|parameter| parameter == label.token())
+                        {
+                            arguments[index].clone()
+                        } else if locals.contains(label.token()) {
+                            let mut label = label.clone();
+                            label.rename(format!("{}.{}.{}", name, expansion, label.token()));
+                            label.set_expansion(Some(expansion));
+                            Token::Label(label)
+                        } else {
+                            let mut token = token.clone();
+                            token.set_expansion(Some(expansion));
+                            token
+                        }
+                    }
+                    Token::Expression(expression) => {
+                        let node = expression.node.replace_symbols(&|symbol| {
+                            if let Some(index) = definition
+                                .parameters
+                                .iter()
+                                .position(|parameter| parameter == symbol)
+                            {
+                                match &arguments[index] {
+                                    Token::Immediate(immediate) => {
+                                        Some(Node::Value(i32::from(immediate.value)))
+                                    }
+                                    Token::Expression(expression) => Some(expression.node.clone()),
+                                    argument => Some(Node::Symbol(argument.token())),
+                                }
+                            } else if locals.iter().any(|local| local == symbol) {
+                                Some(Node::Symbol(format!("{}.{}.{}", name, expansion, symbol)))
+                            } else {
+                                None
+                            }
+                        });
+
+                        let mut expression = Expression::new(
+                            expression.token().to_string(),
+                            expression.file().clone(),
+                            expression.column(),
+                            expression.line(),
+                            node,
+                        );
+                        expression.set_expansion(Some(expansion));
+                        Token::Expression(expression)
+                    }
+                    token => {
+                        let mut token = token.clone();
+                        token.set_expansion(Some(expansion));
+                        token
+                    }
+                })
+                .collect();
+
+            line.extend(Self::expand(session, tokens, stack));
+        });
+
+        stack.pop();
+
+        line
+    }
+}
@@ -1,17 +1,62 @@
 use std::{iter::Peekable, str::Chars};
 
 use crate::{
-    assembler::add_line,
     err,
-    notifier::{self, DiagType, Diagnostic, Highlight, Pointer},
+    lint::Lint,
+    notifier::{DiagType, Diagnostic, Highlight, Pointer},
+    session::Session,
     token::{
         tokens::{
-            add::Add, and::And, blkw::Blkw, br::Br, character::Character, end::End, fill::Fill,
-            getc::Getc, halt::Halt, immediate::Immediate, include::Include, jmp::Jmp, jmpt::Jmpt,
-            jsr::Jsr, jsrr::Jsrr, label::Label, ld::Ld, ldi::Ldi, ldr::Ldr, lea::Lea,
-            lshift::Lshift, neg::Neg, not::Not, orig::Orig, out::Out, puts::Puts, putsp::Putsp,
-            r#in::In, register::Register, ret::Ret, rti::Rti, set::Set, st::St, sti::Sti, str::Str,
-            string, stringz::Stringz, sub::Sub, trap::Trap,
+            add::Add,
+            and::And,
+            blkw::Blkw,
+            br::Br,
+            character::Character,
+            define::Define,
+            end::End,
+            endif::Endif,
+            equ::Equ,
+            expression::{Expression, Node, Operator},
+            external::External,
+            fill::Fill,
+            getc::Getc,
+            global::Global,
+            halt::Halt,
+            ifdef::Ifdef,
+            ifndef::Ifndef,
+            immediate::Immediate,
+            incbin::Incbin,
+            include::Include,
+            jmp::Jmp,
+            jmpt::Jmpt,
+            jsr::Jsr,
+            jsrr::Jsrr,
+            label::Label,
+            ld::Ld,
+            ldi::Ldi,
+            ldr::Ldr,
+            lea::Lea,
+            lshift::Lshift,
+            neg::Neg,
+            not::Not,
+            orig::Orig,
+            out::Out,
+            puts::Puts,
+            putsp::Putsp,
+            r#else::Else,
+            r#if::If,
+            r#in::In,
+            register::Register,
+            ret::Ret,
+            rti::Rti,
+            set::Set,
+            st::St,
+            sti::Sti,
+            str::Str,
+            string,
+            stringz::Stringz,
+            sub::Sub,
+            trap::Trap,
         },
         Token,
     },
@@ -24,23 +69,130 @@ macro_rules! token {
     };
 }
 
+/// The mnemonics the tokenizer knows, written the usual way
+pub const MNEMONICS: [&str; 31] = [
+    "ADD", "AND", "BR", "BRn", "BRz", "BRp", "BRnz", "BRnp", "BRzp", "BRnzp", "GETC", "HALT", "IN",
+    "JMP", "JMPT", "JSR", "JSRR", "LD", "LDI", "LDR", "LEA", "NOT", "OUT", "PUTS", "PUTSP", "RET",
+    "RTI", "ST", "STI", "STR", "TRAP",
+];
+
+/// The directives the tokenizer knows
+pub const DIRECTIVES: [&str; 22] = [
+    ".BLKW",
+    ".DEFINE",
+    ".ELSE",
+    ".END",
+    ".ENDIF",
+    ".ENDM",
+    ".EQU",
+    ".EXTERNAL",
+    ".FILL",
+    ".GLOBAL",
+    ".IF",
+    ".IFDEF",
+    ".IFNDEF",
+    ".INCBIN",
+    ".INCLUDE",
+    ".LSHIFT",
+    ".MACRO",
+    ".NEG",
+    ".ORIG",
+    ".SET",
+    ".STRINGZ",
+    ".SUB",
+];
+
+/// Where a token is in the source, as the columns (from 1) it starts at and ends before
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: u64,
+    pub start: u64,
+    pub end: u64,
+}
+
 pub struct Tokenizer<'a> {
+    source: &'a str,
     line: Peekable<Chars<'a>>,
     column: u64,
     line_number: u64,
     file: &'a str,
+    session: &'a mut Session,
+    comment: Option<&'a str>,
 }
 
 impl<'a> Tokenizer<'a> {
     #[must_use]
-    pub fn new(file: &'a str, line: &'a str, line_number: u64) -> Tokenizer<'a> {
-        add_line(file, line.to_string());
+    pub fn new(
+        session: &'a mut Session,
+        file: &'a str,
+        line: &'a str,
+        line_number: u64,
+    ) -> Tokenizer<'a> {
+        session.add_line(file, line.to_string());
         Self {
+            source: line,
             line: line.chars().peekable(),
             column: 1,
             line_number,
             file,
+            session,
+            comment: None,
+        }
+    }
+
+    /// Tokenize the rest of the line, along with where each token is, e.g. for an editor
+    /// that needs to know what's under the cursor
+    #[must_use]
+    pub fn spans(&mut self) -> Vec<(Token, Span)> {
+        let mut spans = Vec::new();
+
+        while let Some(token) = self.next_token() {
+            if let Token::Eol = token {
+                break;
+            }
+
+            // Anything after the token that was read along with it isn't part of it
+            let start = token.column();
+            let end = start
+                + self
+                    .source
+                    .chars()
+                    .skip(start as usize - 1)
+                    .take((self.column - start) as usize)
+                    .collect::<String>()
+                    .trim_end_matches(|ch: char| ch.is_whitespace() || ch == ',' || ch == ':')
+                    .chars()
+                    .count() as u64;
+
+            spans.push((
+                token,
+                Span {
+                    line: self.line_number,
+                    start,
+                    end,
+                },
+            ));
         }
+
+        spans
+    }
+
+    /// The comment ending the line (including the `;` or `//`), once the tokens before
+    /// it have been read
+    #[must_use]
+    pub fn comment(&self) -> Option<&'a str> {
+        self.comment
+    }
+
+    /// Remember the comment starting at `column`, as it's not a token of its own
+    fn read_comment(&mut self, column: u64) -> Option<Token> {
+        self.comment = self
+            .source
+            .char_indices()
+            .nth(column as usize - 1)
+            .map(|(index, _)| self.source[index..].trim_end());
+
+        Some(Token::Eol)
     }
 
     #[inline]
@@ -151,6 +303,8 @@ impl<'a> Tokenizer<'a> {
                     '\\' => token.push('\\'),
                     _ => {
                         warn!(
+                            self.session,
+                            Lint::UnknownEscape,
                             Highlight,
                             self.file.to_string(),
                             self.column - 2,
@@ -162,6 +316,10 @@ impl<'a> Tokenizer<'a> {
                         token.push(ch);
                     }
                 }
+
+                // The escaped character can't start another escape, e.g. in "\\n"
+                previous = '\0';
+                continue;
             } else if ch == '"' {
                 terminated = true;
                 break;
@@ -176,11 +334,13 @@ impl<'a> Tokenizer<'a> {
             Some(Token::String(string::String::new(
                 token,
                 self.file.to_string(),
-                self.line_number,
                 token_start,
+                self.line_number,
             )))
         } else {
             err!(
+                self.session,
+                "unterminated-string",
                 Highlight,
                 self.file.to_string(),
                 token_start,
@@ -213,6 +373,8 @@ impl<'a> Tokenizer<'a> {
                     '0' => character.push('\0'),
                     _ => {
                         warn!(
+                            self.session,
+                            Lint::UnknownEscape,
                             Highlight,
                             self.file.to_string(),
                             self.column - 2,
@@ -223,6 +385,9 @@ impl<'a> Tokenizer<'a> {
                         character.push(ch);
                     }
                 };
+
+                previous_character = '\0';
+                continue;
             } else if ch == '\'' {
                 terminated = true;
                 break;
@@ -244,6 +409,8 @@ impl<'a> Tokenizer<'a> {
                 ))
             } else {
                 err!(
+                    self.session,
+                    "invalid-character",
                     Highlight,
                     self.file.to_string(),
                     token_start,
@@ -255,6 +422,8 @@ impl<'a> Tokenizer<'a> {
             }
         } else {
             err!(
+                self.session,
+                "unterminated-character",
                 Highlight,
                 self.file.to_string(),
                 token_start,
@@ -335,6 +504,7 @@ impl<'a> Tokenizer<'a> {
             None
         } else if Self::is_valid_decimal(&token) {
             Some(Token::Immediate(Immediate::from_decimal(
+                self.session,
                 token,
                 self.file.to_string(),
                 column,
@@ -342,6 +512,7 @@ impl<'a> Tokenizer<'a> {
             )))
         } else if Self::is_valid_hexadecimal(&token) {
             Some(Token::Immediate(Immediate::from_hexadecimal(
+                self.session,
                 token,
                 self.file.to_string(),
                 column,
@@ -349,6 +520,7 @@ impl<'a> Tokenizer<'a> {
             )))
         } else if Self::is_valid_binary(&token) {
             Some(Token::Immediate(Immediate::from_binary(
+                self.session,
                 token,
                 self.file.to_string(),
                 column,
@@ -365,7 +537,8 @@ impl<'a> Tokenizer<'a> {
         let mut word = String::with_capacity(20);
 
         while let Some(&ch) = self.peek() {
-            if Self::is_terminator_character(ch) {
+            // A '-' can only start an immediate, anywhere else it's a subtraction
+            if Self::is_terminator_character(ch) || (ch == '-' && !word.is_empty() && word != "#") {
                 break;
             }
 
@@ -383,6 +556,22 @@ impl<'a> Tokenizer<'a> {
             ".BLKW" => Some(token!(Blkw, token, self.file.to_string(), column, line)),
             ".FILL" => Some(token!(Fill, token, self.file.to_string(), column, line)),
             ".INCLUDE" => Some(token!(Include, token, self.file.to_string(), column, line)),
+            ".INCBIN" => Some(Token::Incbin(Incbin::new(
+                token,
+                self.file.to_string(),
+                column,
+                line,
+                Vec::new(),
+            ))),
+            ".EQU" => Some(token!(Equ, token, self.file.to_string(), column, line)),
+            ".DEFINE" => Some(token!(Define, token, self.file.to_string(), column, line)),
+            ".GLOBAL" => Some(token!(Global, token, self.file.to_string(), column, line)),
+            ".EXTERNAL" => Some(token!(External, token, self.file.to_string(), column, line)),
+            ".IF" => Some(token!(If, token, self.file.to_string(), column, line)),
+            ".IFDEF" => Some(token!(Ifdef, token, self.file.to_string(), column, line)),
+            ".IFNDEF" => Some(token!(Ifndef, token, self.file.to_string(), column, line)),
+            ".ELSE" => Some(token!(Else, token, self.file.to_string(), column, line)),
+            ".ENDIF" => Some(token!(Endif, token, self.file.to_string(), column, line)),
             ".SET" => Some(token!(Set, token, self.file.to_string(), column, line)),
             ".LSHIFT" => Some(token!(Lshift, token, self.file.to_string(), column, line)),
             ".NEG" => Some(token!(Neg, token, self.file.to_string(), column, line)),
@@ -410,6 +599,218 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
+    /// The operator coming up next in the line, if there is one
+    fn peek_operator(&mut self) -> Option<Operator> {
+        self.skip_whitespace();
+
+        let mut ahead = self.line.clone();
+        match (ahead.next(), ahead.next()) {
+            (Some('+'), _) => Some(Operator::Add),
+            (Some('-'), _) => Some(Operator::Subtract),
+            (Some('*'), _) => Some(Operator::Multiply),
+            (Some('/'), Some('/')) => None, // A comment
+            (Some('/'), _) => Some(Operator::Divide),
+            (Some('<'), Some('<')) => Some(Operator::ShiftLeft),
+            (Some('>'), Some('>')) => Some(Operator::ShiftRight),
+            (Some('&'), _) => Some(Operator::And),
+            (Some('|'), _) => Some(Operator::Or),
+            (Some('^'), _) => Some(Operator::Xor),
+            _ => None,
+        }
+    }
+
+    /// Carry on with an expression if the operand given is followed by an operator
+    fn maybe_expression(&mut self, token: Option<Token>, token_start: u64) -> Option<Token> {
+        match token {
+            Some(Token::Immediate(_)) | Some(Token::Label(_)) | Some(Token::Character(_))
+                if self.peek_operator().is_some() =>
+            {
+                self.tokenize_expression(token_start, token)
+            }
+            token => token,
+        }
+    }
+
+    /// Read an expression, e.g. `#OFFSET+1` or `(END-START)*2`, starting with the operand
+    /// given (if it's already been read). Expressions that don't refer to any symbols are
+    /// worked out straight away, so they end up as a plain immediate.
+    fn tokenize_expression(&mut self, token_start: u64, first: Option<Token>) -> Option<Token> {
+        let node = match first {
+            Some(Token::Immediate(immediate)) => Ok(Node::Value(i32::from(immediate.value))),
+            Some(Token::Character(character)) => {
+                Ok(Node::Value(character.token().chars().next().unwrap() as i32))
+            }
+            Some(Token::Label(label)) => Ok(Node::Symbol(label.token().to_string())),
+            _ => self.parse_unary(),
+        }
+        .and_then(|node| self.parse_binary(node, 0));
+
+        let token = self
+            .source
+            .chars()
+            .skip(token_start as usize - 1)
+            .take((self.column - token_start) as usize)
+            .collect::<String>()
+            .trim_end()
+            .to_string();
+
+        match node {
+            Ok(node) if node.has_symbols() => Some(Token::Expression(Expression::new(
+                token,
+                self.file.to_string(),
+                token_start,
+                self.line_number,
+                node,
+            ))),
+            Ok(node) => match node.evaluate(&|_| None) {
+                Ok(value) if (-0x8000..=0xFFFF).contains(&value) => {
+                    Some(Token::Immediate(Immediate::new(
+                        token,
+                        self.file.to_string(),
+                        token_start,
+                        self.line_number,
+                        value as i16,
+                    )))
+                }
+                result => {
+                    err!(
+                        self.session,
+                        "invalid-expression",
+                        Highlight,
+                        self.file.to_string(),
+                        token_start,
+                        self.line_number,
+                        token.len(),
+                        if result.is_ok() {
+                            format!("Expression '{}' can't be represented in 16 bits", token)
+                        } else {
+                            format!("Expression '{}' divides by zero", token)
+                        }
+                    );
+                    self.next_token()
+                }
+            },
+            Err(message) => {
+                err!(
+                    self.session,
+                    "invalid-expression",
+                    Highlight,
+                    self.file.to_string(),
+                    token_start,
+                    self.line_number,
+                    token.len().max(1),
+                    message
+                );
+                self.next_token()
+            }
+        }
+    }
+
+    /// Read the rest of an expression after `left`, for as long as the operators
+    /// bind at least as tightly as `precedence`
+    fn parse_binary(&mut self, mut left: Node, precedence: u8) -> Result<Node, String> {
+        loop {
+            let operator = match self.peek_operator() {
+                Some(operator) if operator.precedence() >= precedence => operator,
+                _ => return Ok(left),
+            };
+
+            self.next();
+            if let Operator::ShiftLeft | Operator::ShiftRight = operator {
+                self.next();
+            }
+
+            let mut right = self.parse_unary()?;
+
+            loop {
+                match self.peek_operator() {
+                    Some(next) if next.precedence() > operator.precedence() => {
+                        right = self.parse_binary(right, operator.precedence() + 1)?;
+                    }
+                    _ => break,
+                }
+            }
+
+            left = Node::Binary(operator, Box::new(left), Box::new(right));
+        }
+    }
+
+    /// Read a single operand of an expression, along with any unary operators before it
+    fn parse_unary(&mut self) -> Result<Node, String> {
+        self.skip_whitespace();
+
+        let token_start = self.column;
+        let mut ahead = self.line.clone();
+
+        match (ahead.next(), ahead.next()) {
+            (Some('('), _) => {
+                self.next();
+
+                let node = self.parse_unary()?;
+                let node = self.parse_binary(node, 0)?;
+
+                self.skip_whitespace();
+                if let Some(')') = self.next() {
+                    Ok(node)
+                } else {
+                    Err(String::from(
+                        "Expected a ')' to close the '(' in this expression",
+                    ))
+                }
+            }
+            (Some('~'), _) => {
+                self.next();
+                Ok(Node::Unary(Operator::Not, Box::new(self.parse_unary()?)))
+            }
+            (Some('-'), Some(ch)) if !ch.is_ascii_digit() => {
+                self.next();
+                Ok(Node::Unary(Operator::Negate, Box::new(self.parse_unary()?)))
+            }
+            (Some('\''), _) => match self.tokenize_character_literal() {
+                Some(character) => {
+                    Ok(Node::Value(character.token().chars().next().unwrap() as i32))
+                }
+                None => Err(String::from("Invalid character literal")),
+            },
+            (Some(ch), _) if Self::is_token_character(ch) => {
+                let token = self.read_word();
+
+                if token.starts_with('#') {
+                    if Self::is_valid_decimal(&token) {
+                        let immediate = Immediate::from_decimal(
+                            self.session,
+                            token,
+                            self.file.to_string(),
+                            token_start,
+                            self.line_number,
+                        );
+                        Ok(Node::Value(i32::from(immediate.value)))
+                    } else if let Some(symbol) = token
+                        .strip_prefix('#')
+                        .filter(|symbol| Self::is_valid_label(symbol))
+                    {
+                        Ok(Node::Symbol(symbol.to_string()))
+                    } else {
+                        Err(format!("Invalid token '{}'", token))
+                    }
+                } else {
+                    match self.tokenize_immediate_literal(
+                        token.clone(),
+                        token_start,
+                        self.line_number,
+                    ) {
+                        Some(Token::Immediate(immediate)) => {
+                            Ok(Node::Value(i32::from(immediate.value)))
+                        }
+                        Some(Token::Label(label)) => Ok(Node::Symbol(label.token().to_string())),
+                        _ => Err(format!("Invalid token '{}'", token)),
+                    }
+                }
+            }
+            _ => Err(String::from("Expected to find a value in this expression")),
+        }
+    }
+
     fn next_token(&mut self) -> Option<Token> {
         self.skip_whitespace();
 
@@ -420,9 +821,11 @@ impl<'a> Tokenizer<'a> {
                 '/' => {
                     self.next(); // Skip this character
                     if let Some('/') = self.next() {
-                        Some(Token::Eol) // Line comment
+                        self.read_comment(token_start) // Line comment
                     } else {
                         warn!(
+                            self.session,
+                            Lint::SingleSlash,
                             Pointer,
                             self.file.to_string(),
                             token_start,
@@ -431,50 +834,40 @@ impl<'a> Tokenizer<'a> {
                                 "Expected another '/' here. Treating it as a comment anyways"
                             )
                         );
-                        Some(Token::Eol)
+                        self.read_comment(token_start)
                     }
                 }
-                ';' => Some(Token::Eol), // Line comment
+                ';' => self.read_comment(token_start), // Line comment
                 ':' | ',' => {
                     self.next();
                     self.next_token()
                 }
                 '"' => self.tokenize_string_literal(),
-                '\'' => self.tokenize_character_literal(),
-                '#' | '-' => {
-                    let token = self.read_word();
-                    if Self::is_valid_decimal(&token) {
-                        Some(Token::Immediate(Immediate::from_decimal(
-                            token,
-                            self.file.to_string(),
-                            token_start,
-                            self.line_number,
-                        )))
-                    } else {
-                        err!(
-                            Highlight,
-                            self.file.to_string(),
-                            token_start,
-                            self.line_number,
-                            token.len(),
-                            format!("Invalid token '{}'", token)
-                        );
-                        self.next_token()
-                    }
+                '#' | '-' | '(' | '~' => self.tokenize_expression(token_start, None),
+                '\'' => {
+                    let character = self.tokenize_character_literal();
+                    self.maybe_expression(character, token_start)
                 }
                 '.' => {
                     let token = self.read_word();
-                    self.tokenize_directive(token, token_start, self.line_number)
+                    // A local label can start an expression, e.g. `.END-.START`
+                    let token = self.tokenize_directive(token, token_start, self.line_number);
+                    self.maybe_expression(token, token_start)
                 }
                 ch => {
                     if ch.is_digit(10) {
                         let token = self.read_word();
-                        self.tokenize_immediate_literal(token, token_start, self.line_number)
+                        let token =
+                            self.tokenize_immediate_literal(token, token_start, self.line_number);
+                        self.maybe_expression(token, token_start)
                     } else if Self::is_token_character(ch) {
                         let token = self.read_word();
-                        self.tokenize_literal(token, token_start, self.line_number)
+                        let token = self.tokenize_literal(token, token_start, self.line_number);
+                        self.maybe_expression(token, token_start)
                     } else {
                         err!(
+                            self.session,
+                            "invalid-character",
                             Pointer,
                             self.file.to_string(),
                             token_start,
@@ -3,14 +3,18 @@
 #![allow(clippy::cast_sign_loss)]
 #![allow(clippy::cast_possible_wrap)]
 
-#[macro_use]
-extern crate lazy_static;
-
 pub mod assembler;
+pub mod disassembler;
+pub mod formatter;
 pub mod lexer;
+pub mod linker;
+pub mod lint;
 pub mod macros;
 pub mod notifier;
 pub mod parser;
+pub mod session;
+pub mod simulator;
+pub mod suggest;
 pub mod types;
 pub mod writer;
 #[macro_use]
@@ -0,0 +1,450 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, Error, ErrorKind, Write},
+};
+
+use crate::{
+    token::Symbol,
+    types::{Listing, Program, Segment, SymbolTable},
+};
+
+/// The first line of every relocatable object
+const HEADER: &str = "LC3REL 1";
+
+/// How a reference to an external symbol is to be patched into a word
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocationKind {
+    /// A PC-relative offset in the low `n` bits of an instruction
+    Offset(u32),
+    /// The whole word is the address of the symbol (e.g. `.FILL LABEL`)
+    Word,
+}
+
+/// A reference to a symbol that couldn't be resolved when the object was assembled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    address: u16,
+    symbol: String,
+    kind: RelocationKind,
+}
+
+impl Relocation {
+    #[must_use]
+    pub fn new(address: u16, symbol: String, kind: RelocationKind) -> Self {
+        Self {
+            address,
+            symbol,
+            kind,
+        }
+    }
+
+    #[must_use]
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+
+    #[must_use]
+    pub fn symbol(&self) -> &String {
+        &self.symbol
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> RelocationKind {
+        self.kind
+    }
+}
+
+/// The symbols a program shares with (or needs from) others
+#[derive(Debug, Default)]
+pub struct Linkage {
+    globals: Vec<String>,
+    externals: Vec<String>,
+    relocations: Vec<Relocation>,
+}
+
+impl Linkage {
+    pub fn add_global(&mut self, symbol: String) {
+        if !self.globals.contains(&symbol) {
+            self.globals.push(symbol);
+        }
+    }
+
+    pub fn add_external(&mut self, symbol: String) {
+        if !self.externals.contains(&symbol) {
+            self.externals.push(symbol);
+        }
+    }
+
+    pub fn relocate(&mut self, relocation: Relocation) {
+        self.relocations.push(relocation);
+    }
+
+    #[must_use]
+    pub fn is_external(&self, symbol: &str) -> bool {
+        self.externals.iter().any(|external| external == symbol)
+    }
+
+    #[must_use]
+    pub fn globals(&self) -> &Vec<String> {
+        &self.globals
+    }
+
+    #[must_use]
+    pub fn externals(&self) -> &Vec<String> {
+        &self.externals
+    }
+
+    #[must_use]
+    pub fn relocations(&self) -> &Vec<Relocation> {
+        &self.relocations
+    }
+
+    /// Whether the program can't be loaded until it's been linked
+    #[must_use]
+    pub fn is_relocatable(&self) -> bool {
+        !self.relocations.is_empty()
+    }
+}
+
+/// An assembled program that may still refer to symbols defined elsewhere
+pub struct Object {
+    name: String,
+    program: Program,
+    linkage: Linkage,
+}
+
+impl Object {
+    #[must_use]
+    pub fn new(name: String, program: Program, linkage: Linkage) -> Self {
+        Self {
+            name,
+            program,
+            linkage,
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    #[must_use]
+    pub fn linkage(&self) -> &Linkage {
+        &self.linkage
+    }
+
+    /// Write the object out in the relocatable format read by `Object::read`
+    ///
+    /// # Errors
+    ///   Fails if there is an error writing to the writer
+    pub fn write<W: Write>(&self, out: &mut W) -> Result<(), Error> {
+        let (symbols, segments) = &self.program;
+
+        writeln!(out, "{}", HEADER)?;
+
+        let mut symbols = symbols.values().collect::<Vec<_>>();
+        symbols.sort_by_key(|symbol| (symbol.address(), symbol.symbol()));
+
+        symbols.into_iter().try_for_each(|symbol| {
+            writeln!(
+                out,
+                "SYMBOL {:04X} {}{}{}",
+                symbol.address(),
+                symbol.symbol(),
+                if symbol.is_constant() {
+                    " CONSTANT"
+                } else {
+                    ""
+                },
+                if self.linkage.globals.contains(symbol.symbol()) {
+                    " GLOBAL"
+                } else {
+                    ""
+                }
+            )
+        })?;
+
+        self.linkage
+            .externals
+            .iter()
+            .try_for_each(|external| writeln!(out, "EXTERNAL {}", external))?;
+
+        segments.iter().try_for_each(|segment| {
+            writeln!(out, "SEGMENT {:04X}", segment.origin())?;
+
+            if let Some((_, header)) = segment.header() {
+                writeln!(out, "HEADER {}", header)?;
+            }
+
+            segment
+                .listings()
+                .iter()
+                .try_for_each(|(word, listing)| writeln!(out, "WORD {:04X} {}", word, listing))
+        })?;
+
+        self.linkage.relocations.iter().try_for_each(|relocation| {
+            writeln!(
+                out,
+                "RELOCATE {:04X} {} {}",
+                relocation.address,
+                match relocation.kind {
+                    RelocationKind::Offset(bits) => format!("OFFSET{}", bits),
+                    RelocationKind::Word => String::from("WORD"),
+                },
+                relocation.symbol
+            )
+        })
+    }
+
+    /// Read an object written by `Object::write`
+    ///
+    /// # Errors
+    ///   Fails if the object can't be read, or isn't a relocatable object
+    pub fn read<R: BufRead>(name: String, input: R) -> Result<Self, Error> {
+        let invalid = |line: usize, reason: &str| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}:{}: {}", name, line + 1, reason),
+            )
+        };
+
+        let mut symbols = SymbolTable::new();
+        let mut segments: Vec<Segment> = Vec::new();
+        let mut linkage = Linkage::default();
+
+        for (number, line) in input.lines().enumerate() {
+            let line = line?;
+
+            if number == 0 {
+                if line != HEADER {
+                    return Err(invalid(number, "Not a relocatable LC-3 object"));
+                }
+                continue;
+            }
+
+            let mut fields = line.splitn(2, ' ');
+            let (kind, rest) = (fields.next().unwrap_or(""), fields.next().unwrap_or(""));
+
+            match kind {
+                "SYMBOL" => {
+                    let mut fields = rest.split(' ');
+                    let address = fields.next().and_then(hex);
+                    let symbol = fields.next();
+
+                    match (address, symbol) {
+                        (Some(address), Some(symbol)) => {
+                            let flags = fields.collect::<Vec<_>>();
+
+                            if flags.contains(&"GLOBAL") {
+                                linkage.add_global(symbol.to_string());
+                            }
+                            symbols.insert(
+                                symbol.to_string(),
+                                if flags.contains(&"CONSTANT") {
+                                    Symbol::constant(address as i16, symbol.to_string())
+                                } else {
+                                    Symbol::new(address, symbol.to_string())
+                                },
+                            );
+                        }
+                        _ => return Err(invalid(number, "Malformed symbol")),
+                    }
+                }
+                "EXTERNAL" => linkage.add_external(rest.to_string()),
+                "SEGMENT" => match hex(rest) {
+                    Some(origin) => segments.push(Segment::new(origin, None)),
+                    None => return Err(invalid(number, "Malformed segment")),
+                },
+                "HEADER" | "WORD" => {
+                    let segment = segments
+                        .last_mut()
+                        .ok_or_else(|| invalid(number, "Found a word outside of a segment"))?;
+
+                    if kind == "HEADER" {
+                        segment.set_header((segment.origin(), rest.to_string()));
+                    } else {
+                        let mut fields = rest.splitn(2, ' ');
+                        let word = fields
+                            .next()
+                            .and_then(hex)
+                            .ok_or_else(|| invalid(number, "Malformed word"))?;
+                        segment.extend(vec![(word, fields.next().unwrap_or("").to_string())]);
+                    }
+                }
+                "RELOCATE" => {
+                    let fields = rest.split(' ').collect::<Vec<_>>();
+                    let kind = match fields.get(1) {
+                        Some(&"WORD") => Some(RelocationKind::Word),
+                        Some(kind) if kind.starts_with("OFFSET") => {
+                            kind[6..].parse().ok().map(RelocationKind::Offset)
+                        }
+                        _ => None,
+                    };
+
+                    match (
+                        fields.first().and_then(|address| hex(address)),
+                        kind,
+                        fields.get(2),
+                    ) {
+                        (Some(address), Some(kind), Some(symbol)) => {
+                            linkage.relocate(Relocation::new(address, (*symbol).to_string(), kind))
+                        }
+                        _ => return Err(invalid(number, "Malformed relocation")),
+                    }
+                }
+                "" => {}
+                _ => return Err(invalid(number, "Unknown record")),
+            }
+        }
+
+        Ok(Self::new(name, (symbols, segments), linkage))
+    }
+}
+
+fn hex(value: &str) -> Option<u16> {
+    u16::from_str_radix(value, 16).ok()
+}
+
+/// Put a new word into a listing, keeping its rendered form in step
+fn patch(listing: &mut Listing, word: u16) {
+    listing.0 = word;
+
+    // Listings start with "(ADDR) WORD BINARY", so the word starts after the address
+    let rendered = format!("{:04X} {:016b}", word, word);
+    if listing.1.len() >= 7 + rendered.len() && listing.1.is_char_boundary(7 + rendered.len()) {
+        listing.1.replace_range(7..7 + rendered.len(), &rendered);
+    }
+}
+
+/// Link objects together into a single program, resolving their references to
+/// each other's global symbols.
+///
+/// Nothing is moved: every segment stays at the address its `.ORIG` gave it, so
+/// objects have to be assembled at addresses that don't overlap.
+///
+/// # Errors
+///   Every problem found along the way, e.g. undefined or duplicated symbols,
+///   offsets that are out of range, or segments that overlap
+pub fn link(objects: Vec<Object>) -> Result<Program, Vec<String>> {
+    let mut errors = Vec::new();
+    let mut globals: HashMap<String, (Symbol, String)> = HashMap::new();
+
+    objects.iter().for_each(|object| {
+        object
+            .linkage
+            .globals
+            .iter()
+            .for_each(|global| match object.program.0.get(global) {
+                Some(symbol) => {
+                    if let Some((_, other)) = globals.get(global) {
+                        errors.push(format!(
+                            "{}: Global symbol {} is also defined in {}",
+                            object.name, global, other
+                        ));
+                    } else {
+                        globals.insert(global.clone(), (symbol.clone(), object.name.clone()));
+                    }
+                }
+                None => errors.push(format!(
+                    "{}: Global symbol {} is never defined",
+                    object.name, global
+                )),
+            })
+    });
+
+    let mut symbols = SymbolTable::new();
+    let mut segments: Vec<Segment> = Vec::new();
+
+    objects.into_iter().for_each(|object| {
+        let Object {
+            name,
+            program: (object_symbols, mut object_segments),
+            linkage,
+        } = object;
+
+        linkage.relocations.iter().for_each(|relocation| {
+            let target = match globals.get(&relocation.symbol) {
+                Some((symbol, _)) => symbol.address(),
+                None => {
+                    errors.push(format!(
+                        "{}: Undefined reference to {} at x{:04X}",
+                        name, relocation.symbol, relocation.address
+                    ));
+                    return;
+                }
+            };
+
+            let listing = object_segments.iter_mut().find_map(|segment| {
+                let index = relocation.address.wrapping_sub(segment.origin()) as usize;
+                segment.listings_mut().get_mut(index)
+            });
+
+            let listing = match listing {
+                Some(listing) => listing,
+                None => {
+                    errors.push(format!(
+                        "{}: Relocation for {} at x{:04X} is outside of every segment",
+                        name, relocation.symbol, relocation.address
+                    ));
+                    return;
+                }
+            };
+
+            let word = match relocation.kind {
+                RelocationKind::Word => target,
+                RelocationKind::Offset(bits) => {
+                    let offset = i32::from(target as i16) - i32::from(relocation.address as i16) - 1;
+                    let offset = (offset + 0x8000).rem_euclid(0x10000) - 0x8000;
+                    let (minimum, maximum) = (-(1 << (bits - 1)), (1 << (bits - 1)) - 1);
+
+                    if offset < minimum || offset > maximum {
+                        errors.push(format!(
+                            "{}: {} is #{} away from x{:04X}, but PCoffset{} can only reach #{} to #{}",
+                            name, relocation.symbol, offset, relocation.address, bits, minimum, maximum
+                        ));
+                    }
+
+                    listing.0 & !((1 << bits) - 1) | (offset as u16 & ((1 << bits) - 1))
+                }
+            };
+
+            patch(listing, word);
+        });
+
+        object_symbols.into_iter().for_each(|(symbol, value)| {
+            // Local symbols can clash between objects, but they're only of use for
+            // debugging, so the first one wins
+            let _ = symbols.entry(symbol).or_insert(value);
+        });
+
+        object_segments.into_iter().for_each(|segment| {
+            if let Some(other) = segments.iter().find(|other| segment.overlaps(other)) {
+                errors.push(format!(
+                    "{}: Segment x{:04X}-x{:04X} overlaps the segment at x{:04X}-x{:04X}",
+                    name,
+                    segment.origin(),
+                    segment.end(),
+                    other.origin(),
+                    other.end()
+                ));
+            }
+            segments.push(segment);
+        });
+    });
+
+    // Global symbols always win over any local symbols of the same name
+    globals.into_iter().for_each(|(global, (symbol, _))| {
+        symbols.insert(global, symbol);
+    });
+
+    if errors.is_empty() {
+        Ok((symbols, segments))
+    } else {
+        Err(errors)
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::HashSet;
+
+use crate::{
+    notifier::{DiagType, Diagnostic, Highlight},
+    session::Session,
+    token::{tokens::expression::Node, traits::Requirements, Token},
+    types::SymbolTable,
+    warn,
+};
+
+/// Something that assembles fine, but probably isn't what was meant. Every warning
+/// has one, so that each can be turned off or made into an error on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A second label for an address that already has one
+    MultipleSymbols,
+    /// An escape sequence in a string or character that doesn't mean anything, which is
+    /// kept as it was written
+    UnknownEscape,
+    /// A comment started with a single `/` rather than `//`
+    SingleSlash,
+    /// `.INCBIN` of a file with an odd number of bytes, read as words
+    IncbinPadding,
+    /// `.LSHIFT` by `#0`, which doesn't assemble to anything
+    LshiftZero,
+    /// `JSRR R7`, which jumps through the register it saves the return address in
+    JsrrR7,
+    /// `BR` or `JSR` with an offset of `#-1`, which jumps straight back to itself
+    JumpToSelf,
+    /// A label that nothing refers to, other than one starting a segment (which names
+    /// where the program starts)
+    UnusedLabel,
+    /// `RET` in code that never set R7, so there's nothing sensible to return to
+    RetWithoutR7,
+    /// A program without an `.END`
+    MissingEnd,
+    /// Anything after an `.END` other than another segment
+    AfterEnd,
+}
+
+impl Lint {
+    pub const ALL: [Self; 11] = [
+        Self::MultipleSymbols,
+        Self::UnknownEscape,
+        Self::SingleSlash,
+        Self::IncbinPadding,
+        Self::LshiftZero,
+        Self::JsrrR7,
+        Self::JumpToSelf,
+        Self::UnusedLabel,
+        Self::RetWithoutR7,
+        Self::MissingEnd,
+        Self::AfterEnd,
+    ];
+
+    /// The name the lint goes by, e.g. to turn it on or off, which won't change
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::MultipleSymbols => "multiple-symbols",
+            Self::UnknownEscape => "unknown-escape",
+            Self::SingleSlash => "single-slash",
+            Self::IncbinPadding => "incbin-padding",
+            Self::LshiftZero => "lshift-zero",
+            Self::JsrrR7 => "jsrr-r7",
+            Self::JumpToSelf => "jump-to-self",
+            Self::UnusedLabel => "unused-label",
+            Self::RetWithoutR7 => "ret-without-r7",
+            Self::MissingEnd => "missing-end",
+            Self::AfterEnd => "after-end",
+        }
+    }
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|lint| lint.name() == name)
+    }
+}
+
+/// What happens to the warnings of a lint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// They aren't raised at all
+    Allow,
+    Warn,
+    /// They're raised as errors instead, so the program won't assemble
+    Error,
+}
+
+/// Look over the parsed program for anything that assembles fine but probably isn't
+/// what was meant, warning about each one
+pub fn lint(session: &mut Session, tokens: &[Token], symbols: &SymbolTable) {
+    let addresses = addresses(tokens);
+
+    lshift_zero(session, tokens);
+    jsrr_r7(session, tokens);
+    jump_to_self(session, tokens, symbols, &addresses);
+    unused_labels(session, tokens, symbols);
+    ret_without_r7(session, tokens);
+    end(session, tokens);
+}
+
+fn report(session: &mut Session, lint: Lint, token: &Token, message: String) {
+    warn!(
+        session,
+        lint,
+        Highlight,
+        token.source(),
+        token.column(),
+        token.line(),
+        token.width(),
+        message
+    );
+}
+
+/// The address of each token, worked out the same way the parser does
+fn addresses(tokens: &[Token]) -> Vec<u16> {
+    let mut address = 0_u16;
+
+    tokens
+        .iter()
+        .map(|token| {
+            if let Token::Orig(_) = token {
+                address = token.memory_requirement();
+            }
+
+            let current = address;
+            if !matches!(token, Token::Orig(_)) {
+                address = address.wrapping_add(token.memory_requirement());
+            }
+
+            current
+        })
+        .collect()
+}
+
+fn lshift_zero(session: &mut Session, tokens: &[Token]) {
+    tokens.iter().for_each(|token| {
+        if let Token::Lshift(lshift) = token {
+            if let Some(Token::Immediate(immediate)) = lshift.operands().last() {
+                if immediate.value == 0 {
+                    report(
+                        session,
+                        Lint::LshiftZero,
+                        token,
+                        format!("{} by #0 doesn't shift anything", token.token()),
+                    );
+                }
+            }
+        }
+    });
+}
+
+fn jsrr_r7(session: &mut Session, tokens: &[Token]) {
+    tokens.iter().for_each(|token| {
+        if let Token::Jsrr(jsrr) = token {
+            if let Some(Token::Register(register)) = jsrr.operands().first() {
+                if register.register == 7 {
+                    report(
+                        session,
+                        Lint::JsrrR7,
+                        token,
+                        format!(
+                            "{} R7 jumps through the register the return address is saved in, \
+                             which not every machine handles the same way",
+                            token.token()
+                        ),
+                    );
+                }
+            }
+        }
+    });
+}
+
+fn jump_to_self(session: &mut Session, tokens: &[Token], symbols: &SymbolTable, addresses: &[u16]) {
+    tokens.iter().zip(addresses).for_each(|(token, address)| {
+        let operand = match token {
+            Token::Br(br) => br.operands().first(),
+            Token::Jsr(jsr) => jsr.operands().first(),
+            _ => return,
+        };
+
+        let is_self = match operand {
+            Some(Token::Immediate(immediate)) => immediate.value == -1,
+            Some(Token::Label(label)) => symbols
+                .get(label.token())
+                .is_some_and(|symbol| !symbol.is_constant() && symbol.address() == *address),
+            _ => false,
+        };
+
+        if is_self {
+            report(
+                session,
+                Lint::JumpToSelf,
+                token,
+                format!(
+                    "{} jumps back to itself (an offset of #-1), so it never gets any further",
+                    token.token()
+                ),
+            );
+        }
+    });
+}
+
+/// Every symbol the token refers to, including those inside expressions
+fn uses(token: &Token, used: &mut HashSet<String>) {
+    match token {
+        Token::Label(label) => {
+            used.insert(label.token().clone());
+        }
+        Token::Expression(expression) => symbols_in(&expression.node, used),
+        token => token
+            .operands()
+            .into_iter()
+            .flatten()
+            .for_each(|operand| uses(operand, used)),
+    }
+}
+
+fn symbols_in(node: &Node, used: &mut HashSet<String>) {
+    match node {
+        Node::Value(_) => {}
+        Node::Symbol(symbol) => {
+            used.insert(symbol.clone());
+        }
+        Node::Unary(_, operand) => symbols_in(operand, used),
+        Node::Binary(_, left, right) => {
+            symbols_in(left, used);
+            symbols_in(right, used);
+        }
+    }
+}
+
+fn unused_labels(session: &mut Session, tokens: &[Token], symbols: &SymbolTable) {
+    let mut used = HashSet::new();
+    tokens
+        .iter()
+        .filter(|token| !matches!(token, Token::Label(_)))
+        .for_each(|token| uses(token, &mut used));
+
+    let mut previous: Option<&Token> = None;
+    tokens.iter().for_each(|token| {
+        if let Token::Label(label) = token {
+            // Labels inside a macro are only used by some of its expansions
+            let expanded = label.source().expansion().is_some();
+            let starts_segment = matches!(previous, Some(Token::Orig(_)));
+
+            if !expanded
+                && !starts_segment
+                && !used.contains(label.token())
+                && symbols
+                    .get(label.token())
+                    .is_some_and(|symbol| !symbol.is_constant())
+            {
+                report(
+                    session,
+                    Lint::UnusedLabel,
+                    token,
+                    format!("Label {} is never used", label.token()),
+                );
+            }
+        }
+
+        previous = Some(token);
+    });
+}
+
+/// Whether the instruction leaves something in R7, i.e. a return address, or a value
+/// written there directly
+fn sets_r7(token: &Token) -> bool {
+    match token {
+        Token::Jsr(_)
+        | Token::Jsrr(_)
+        | Token::Trap(_)
+        | Token::Getc(_)
+        | Token::Halt(_)
+        | Token::In(_)
+        | Token::Out(_)
+        | Token::Puts(_)
+        | Token::Putsp(_) => true,
+        Token::Add(_)
+        | Token::And(_)
+        | Token::Not(_)
+        | Token::Ld(_)
+        | Token::Ldi(_)
+        | Token::Ldr(_)
+        | Token::Lea(_)
+        | Token::Set(_)
+        | Token::Neg(_)
+        | Token::Sub(_)
+        | Token::Lshift(_) => matches!(
+            token.operands().and_then(|operands| operands.first()),
+            Some(Token::Register(register)) if register.register == 7
+        ),
+        _ => false,
+    }
+}
+
+/// Each segment starts off running the program itself, until it reaches a label that
+/// something could call (or otherwise take the address of), which is where a subroutine
+/// might start. A `RET` before anything sets R7 in the program itself has nowhere to go.
+fn ret_without_r7(session: &mut Session, tokens: &[Token]) {
+    let mut entries = HashSet::new();
+    tokens.iter().for_each(|token| match token {
+        Token::Jsr(_) | Token::Lea(_) | Token::Ld(_) | Token::Fill(_) | Token::Global(_) => {
+            uses(token, &mut entries);
+        }
+        _ => {}
+    });
+
+    // Whether R7 has been set yet, while still in the program rather than a subroutine
+    let mut set = None;
+
+    tokens.iter().for_each(|token| match token {
+        Token::Orig(_) => set = Some(false),
+        Token::Label(label) if entries.contains(label.token()) => set = None,
+        Token::Ret(_) if set == Some(false) => report(
+            session,
+            Lint::RetWithoutR7,
+            token,
+            format!(
+                "{} returns to the address in R7, but nothing before it has set R7",
+                token.token()
+            ),
+        ),
+        token if set.is_some() && sets_r7(token) => set = Some(true),
+        _ => {}
+    });
+}
+
+fn end(session: &mut Session, tokens: &[Token]) {
+    let mut ended = false;
+
+    tokens.iter().for_each(|token| match token {
+        Token::End(_) => ended = true,
+        Token::Orig(_) => ended = false,
+        token if ended => {
+            report(
+                session,
+                Lint::AfterEnd,
+                token,
+                format!("{} comes after .END, but is still assembled", token.token()),
+            );
+            // Once is enough
+            ended = false;
+        }
+        _ => {}
+    });
+
+    if !tokens.iter().any(|token| matches!(token, Token::End(_))) {
+        if let Some(token) = tokens.last() {
+            report(
+                session,
+                Lint::MissingEnd,
+                token,
+                String::from("The program doesn't finish with .END"),
+            );
+        }
+    }
+}
@@ -1,48 +1,48 @@
 #[macro_export]
 macro_rules! err {
-    ( $ty:ident, $file:expr, $column:expr, $line:expr, $width:expr, $message:expr ) => {
-        notifier::add_diagnostic(Diagnostic::$ty($ty::new(
-            DiagType::Error,
-            $file,
-            $column,
-            $line,
-            $width,
-            $message,
-        )));
+    ( $session:expr, $code:expr, $ty:ident, $file:expr, $column:expr, $line:expr, $width:expr, $message:expr ) => {
+        $session.add_diagnostic(
+            Diagnostic::$ty($ty::new(
+                DiagType::Error,
+                $file,
+                $column,
+                $line,
+                $width,
+                $message,
+            ))
+            .with_code($code),
+        );
     };
 
-    ( $ty:ident, $file:expr, $column:expr, $line:expr, $message:expr ) => {
-        notifier::add_diagnostic(Diagnostic::$ty($ty::new(
-            DiagType::Error,
-            $file,
-            $column,
-            $line,
-            $message,
-        )));
+    ( $session:expr, $code:expr, $ty:ident, $file:expr, $column:expr, $line:expr, $message:expr ) => {
+        $session.add_diagnostic(
+            Diagnostic::$ty($ty::new(DiagType::Error, $file, $column, $line, $message))
+                .with_code($code),
+        );
     };
 }
 
 #[macro_export]
 macro_rules! warn {
-    ( $ty:ident, $file:expr, $column:expr, $line:expr, $width:expr, $message:expr ) => {
-        notifier::add_diagnostic(Diagnostic::$ty($ty::new(
-            DiagType::Warning,
-            $file,
-            $column,
-            $line,
-            $width,
-            $message,
-        )));
+    ( $session:expr, $lint:expr, $ty:ident, $file:expr, $column:expr, $line:expr, $width:expr, $message:expr ) => {
+        $session.add_diagnostic(
+            Diagnostic::$ty($ty::new(
+                DiagType::Warning,
+                $file,
+                $column,
+                $line,
+                $width,
+                $message,
+            ))
+            .with_lint($lint),
+        );
     };
 
-    ( $ty:ident, $file:expr, $column:expr, $line:expr, $message:expr ) => {
-        notifier::add_diagnostic(Diagnostic::$ty($ty::new(
-            DiagType::Warning,
-            $file,
-            $column,
-            $line,
-            $message,
-        )));
+    ( $session:expr, $lint:expr, $ty:ident, $file:expr, $column:expr, $line:expr, $message:expr ) => {
+        $session.add_diagnostic(
+            Diagnostic::$ty($ty::new(DiagType::Warning, $file, $column, $line, $message))
+                .with_lint($lint),
+        );
     };
 }
 
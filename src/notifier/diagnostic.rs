@@ -1,17 +1,19 @@
 extern crate colored;
+extern crate serde_json;
 use self::colored::Colorize;
+use self::serde_json::{json, Value};
 
-use assembler::get_line;
+use crate::{lint::Lint, session::FileController};
 
 pub trait Colour {
-    fn coloured(&self) -> String;
+    fn coloured(&self, files: &FileController) -> String;
 }
 
 pub trait NoColour {
-    fn uncoloured(&self) -> String;
+    fn uncoloured(&self, files: &FileController) -> String;
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DiagType {
     Note,
     Warning,
@@ -28,8 +30,9 @@ impl ToString for DiagType {
     }
 }
 
-impl Colour for DiagType {
-    fn coloured(&self) -> String {
+impl DiagType {
+    #[must_use]
+    pub fn coloured(&self) -> String {
         (match self {
             DiagType::Note => self.to_string().bright_white(),
             DiagType::Warning => self.to_string().yellow(),
@@ -39,170 +42,257 @@ impl Colour for DiagType {
     }
 }
 
+/// The message, followed by the name of the lint it's for (if it's for one), which is
+/// what's needed to turn it off
+fn tagged(context: &str, lint: Option<Lint>) -> String {
+    match lint {
+        Some(lint) => format!("{} [{}]", context, lint.name()),
+        None => context.to_string(),
+    }
+}
+
+/// The file something is in, along with the macro expansion it came out of (if it
+/// did), so that a diagnostic raised there can be traced back to where the macro was used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    file: String,
+    expansion: Option<usize>,
+}
+
+impl Source {
+    #[must_use]
+    pub(crate) fn new(file: String, expansion: Option<usize>) -> Self {
+        Self { file, expansion }
+    }
+
+    #[must_use]
+    pub fn file(&self) -> &String {
+        &self.file
+    }
+
+    #[must_use]
+    pub fn expansion(&self) -> Option<usize> {
+        self.expansion
+    }
+}
+
+impl From<String> for Source {
+    fn from(file: String) -> Self {
+        Self::new(file, None)
+    }
+}
+
 pub trait Type {
     fn diagnostic_type(&self) -> &DiagType;
 }
 
+#[derive(Debug, Clone)]
 pub struct Note {
     diagnostic_type: DiagType,
+    file: String,
+    expansion: Option<usize>,
     column: u64,
     line: u64,
     context: String,
+    lint: Option<Lint>,
+    code: Option<&'static str>,
 }
 
 impl Note {
     #[must_use]
-    pub fn new(diagnostic_type: DiagType, column: u64, line: u64, context: String) -> Self {
+    pub fn new(
+        diagnostic_type: DiagType,
+        file: impl Into<Source>,
+        column: u64,
+        line: u64,
+        context: String,
+    ) -> Self {
+        let Source { file, expansion } = file.into();
+
         Self {
             diagnostic_type,
+            file,
+            expansion,
             column,
             line,
             context,
+            lint: None,
+            code: None,
         }
     }
 }
 
+/// Where a note is, leaving out the line and column of one about the file as a whole
+fn position(file: &str, line: u64, column: u64) -> String {
+    if line == 0 {
+        file.to_string()
+    } else {
+        format!("{}:{}:{}", file, line, column)
+    }
+}
+
 impl Colour for Note {
-    fn coloured(&self) -> String {
+    fn coloured(&self, _files: &FileController) -> String {
         format!(
-            "{}:{}:{}: {}",
+            "{}: {}: {}",
+            position(&self.file, self.line, self.column),
             self.diagnostic_type.coloured(),
-            self.line,
-            self.column,
-            self.context
+            tagged(&self.context, self.lint)
         )
     }
 }
 
 impl NoColour for Note {
-    fn uncoloured(&self) -> String {
+    fn uncoloured(&self, _files: &FileController) -> String {
         format!(
-            "{}:{}:{}: {}",
+            "{}: {}: {}",
+            position(&self.file, self.line, self.column),
             self.diagnostic_type.to_string(),
-            self.line,
-            self.column,
-            self.context
+            tagged(&self.context, self.lint)
         )
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct Pointer {
     diagnostic_type: DiagType,
     file: String,
+    expansion: Option<usize>,
     column: u64,
     line: u64,
     context: String,
+    lint: Option<Lint>,
+    code: Option<&'static str>,
 }
 
 impl Pointer {
     #[must_use]
     pub fn new(
         diagnostic_type: DiagType,
-        file: String,
+        file: impl Into<Source>,
         column: u64,
         line: u64,
         context: String,
     ) -> Self {
+        let Source { file, expansion } = file.into();
+
         Self {
             diagnostic_type,
             file,
+            expansion,
             column,
             line,
             context,
+            lint: None,
+            code: None,
         }
     }
 }
 
 impl Colour for Pointer {
-    fn coloured(&self) -> String {
+    fn coloured(&self, files: &FileController) -> String {
         format!(
             "{}:{}:{}: {}: {}\n{}\n{}",
             self.diagnostic_type.coloured(),
             self.file,
             self.line,
             self.column,
-            self.context,
-            get_line(&self.file, self.line),
+            tagged(&self.context, self.lint),
+            files.get_line(&self.file, self.line),
             " ".repeat(self.column as usize - 1) + "^"
         )
     }
 }
 
 impl NoColour for Pointer {
-    fn uncoloured(&self) -> String {
+    fn uncoloured(&self, files: &FileController) -> String {
         format!(
             "{}:{}:{}: {}: {}\n{}\n{}",
             self.file,
             self.line,
             self.column,
             self.diagnostic_type.to_string(),
-            self.context,
-            get_line(&self.file, self.line),
+            tagged(&self.context, self.lint),
+            files.get_line(&self.file, self.line),
             " ".repeat(self.column as usize - 1) + "^"
         )
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct Highlight {
     diagnostic_type: DiagType,
     file: String,
+    expansion: Option<usize>,
     column: u64,
     line: u64,
     width: usize,
     context: String,
+    lint: Option<Lint>,
+    code: Option<&'static str>,
 }
 
 impl Highlight {
     #[must_use]
     pub fn new(
         diagnostic_type: DiagType,
-        file: String,
+        file: impl Into<Source>,
         column: u64,
         line: u64,
         width: usize,
         context: String,
     ) -> Self {
+        let Source { file, expansion } = file.into();
+
         Self {
             diagnostic_type,
             file,
+            expansion,
             column,
             line,
             width,
             context,
+            lint: None,
+            code: None,
         }
     }
 }
 
 impl Colour for Highlight {
-    fn coloured(&self) -> String {
+    fn coloured(&self, files: &FileController) -> String {
         format!(
             "{}:{}:{}: {}: {}\n{}\n{}",
             self.file,
             self.line,
             self.column,
             self.diagnostic_type.coloured(),
-            self.context,
-            get_line(&self.file, self.line),
+            tagged(&self.context, self.lint),
+            files.get_line(&self.file, self.line),
             " ".repeat(self.column as usize - 1) + &"~".repeat(self.width)
         )
     }
 }
 
 impl NoColour for Highlight {
-    fn uncoloured(&self) -> String {
+    fn uncoloured(&self, files: &FileController) -> String {
         format!(
             "{}:{}:{}: {}: {}\n{}\n{}",
             self.file,
             self.line,
             self.column,
             self.diagnostic_type.to_string(),
-            self.context,
-            get_line(&self.file, self.line),
+            tagged(&self.context, self.lint),
+            files.get_line(&self.file, self.line),
             " ".repeat(self.column as usize - 1) + &"~".repeat(self.width)
         )
     }
 }
 
+/// A single message produced while assembling a program.
+///
+/// Each kind carries the same information (where it was found, how severe it
+/// is, and what went wrong), they just differ in how they get rendered.
+#[derive(Debug, Clone)]
 pub enum Diagnostic {
     Note(Note),
     Pointer(Pointer),
@@ -219,22 +309,155 @@ impl Type for Diagnostic {
     }
 }
 
+impl Diagnostic {
+    #[must_use]
+    pub fn severity(&self) -> DiagType {
+        *self.diagnostic_type()
+    }
+
+    pub(crate) fn set_severity(&mut self, severity: DiagType) {
+        match self {
+            Self::Note(ref mut n) => n.diagnostic_type = severity,
+            Self::Pointer(ref mut p) => p.diagnostic_type = severity,
+            Self::Highlight(ref mut h) => h.diagnostic_type = severity,
+        }
+    }
+
+    /// The lint the diagnostic was raised for, which every warning has
+    #[must_use]
+    pub fn lint(&self) -> Option<Lint> {
+        match self {
+            Self::Note(ref n) => n.lint,
+            Self::Pointer(ref p) => p.lint,
+            Self::Highlight(ref h) => h.lint,
+        }
+    }
+
+    #[must_use]
+    pub fn with_lint(mut self, lint: Lint) -> Self {
+        match self {
+            Self::Note(ref mut n) => n.lint = Some(lint),
+            Self::Pointer(ref mut p) => p.lint = Some(lint),
+            Self::Highlight(ref mut h) => h.lint = Some(lint),
+        }
+
+        self.with_code(lint.name())
+    }
+
+    /// What kind of diagnostic it is, which won't change between versions: the name of
+    /// the lint for a warning, or what went wrong for an error
+    #[must_use]
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            Self::Note(ref n) => n.code,
+            Self::Pointer(ref p) => p.code,
+            Self::Highlight(ref h) => h.code,
+        }
+    }
+
+    #[must_use]
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        match self {
+            Self::Note(ref mut n) => n.code = Some(code),
+            Self::Pointer(ref mut p) => p.code = Some(code),
+            Self::Highlight(ref mut h) => h.code = Some(code),
+        }
+
+        self
+    }
+
+    #[must_use]
+    pub fn file(&self) -> &str {
+        match self {
+            Self::Note(ref n) => &n.file,
+            Self::Pointer(ref p) => &p.file,
+            Self::Highlight(ref h) => &h.file,
+        }
+    }
+
+    /// The macro expansion the diagnostic was raised in, if it was
+    #[must_use]
+    pub(crate) fn expansion(&self) -> Option<usize> {
+        match self {
+            Self::Note(ref n) => n.expansion,
+            Self::Pointer(ref p) => p.expansion,
+            Self::Highlight(ref h) => h.expansion,
+        }
+    }
+
+    #[must_use]
+    pub fn line(&self) -> u64 {
+        match self {
+            Self::Note(ref n) => n.line,
+            Self::Pointer(ref p) => p.line,
+            Self::Highlight(ref h) => h.line,
+        }
+    }
+
+    #[must_use]
+    pub fn column(&self) -> u64 {
+        match self {
+            Self::Note(ref n) => n.column,
+            Self::Pointer(ref p) => p.column,
+            Self::Highlight(ref h) => h.column,
+        }
+    }
+
+    /// How many characters the diagnostic covers, starting from its column
+    #[must_use]
+    pub fn width(&self) -> usize {
+        match self {
+            Self::Note(_) => 0,
+            Self::Pointer(_) => 1,
+            Self::Highlight(ref h) => h.width,
+        }
+    }
+
+    #[must_use]
+    pub fn message(&self) -> &str {
+        match self {
+            Self::Note(ref n) => &n.context,
+            Self::Pointer(ref p) => &p.context,
+            Self::Highlight(ref h) => &h.context,
+        }
+    }
+}
+
+impl Diagnostic {
+    /// The diagnostic as a JSON object, with an empty list for any notes to be attached
+    /// to it
+    #[must_use]
+    pub fn to_json(&self) -> Value {
+        json!({
+            "severity": self.severity().to_string().to_lowercase(),
+            "file": self.file(),
+            "line": self.line(),
+            "column": self.column(),
+            "width": self.width(),
+            "message": self.message(),
+            "code": self.code(),
+            "lint": self.lint().map(Lint::name),
+            "notes": [],
+        })
+    }
+}
+
 impl Colour for Diagnostic {
-    fn coloured(&self) -> String {
+    fn coloured(&self, files: &FileController) -> String {
         match self {
-            Self::Note(ref n) => n.coloured(),
-            Self::Pointer(ref p) => p.coloured(),
-            Self::Highlight(ref h) => h.coloured(),
+            Self::Note(ref n) => n.coloured(files),
+            Self::Pointer(ref p) => p.coloured(files),
+            Self::Highlight(ref h) => h.coloured(files),
         }
     }
 }
 
 impl NoColour for Diagnostic {
-    fn uncoloured(&self) -> String {
+    fn uncoloured(&self, files: &FileController) -> String {
         match self {
-            Self::Note(ref n) => n.uncoloured(),
-            Self::Pointer(ref p) => p.uncoloured(),
-            Self::Highlight(ref h) => h.uncoloured(),
+            Self::Note(ref n) => n.uncoloured(files),
+            Self::Pointer(ref p) => p.uncoloured(files),
+            Self::Highlight(ref h) => h.uncoloured(files),
         }
     }
 }
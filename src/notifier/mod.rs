@@ -1,13 +1,33 @@
 use std::collections::HashMap;
-use std::sync::Mutex;
+
+extern crate serde_json;
+use self::serde_json::Value;
 
 pub use self::diagnostic::{Colour, NoColour};
-pub use self::diagnostic::{DiagType, Diagnostic, Highlight, Note, Pointer, Type};
+pub use self::diagnostic::{DiagType, Diagnostic, Highlight, Note, Pointer, Source, Type};
+pub use self::sarif::Sarif;
+
+use crate::session::FileController;
 
 pub mod diagnostic;
+pub mod sarif;
+
+/// Something that wants to hear about each diagnostic as it's raised, e.g. to show it in
+/// an editor. The files are there to render the diagnostic with its source, if wanted.
+///
+/// Any closure taking a `&Diagnostic` is one, so a closure can be registered in place of
+/// a type of its own.
+pub trait Notify {
+    fn notify(&mut self, diagnostic: &Diagnostic, files: &FileController);
+}
 
-trait Notify {
-    fn notify(&mut self, diagnostic: &Diagnostic);
+impl<F> Notify for F
+where
+    F: FnMut(&Diagnostic),
+{
+    fn notify(&mut self, diagnostic: &Diagnostic, _files: &FileController) {
+        self(diagnostic);
+    }
 }
 
 pub enum Stdout {
@@ -19,121 +39,147 @@ pub enum Stdout {
 pub enum Notifier {
     Standard(Stdout),
     Stringify(Vec<String>),
+    Json(Vec<Value>),
+    Custom(Box<dyn Notify + Send>),
 }
 
 /// `Standard` will simply push the diagnostic to stdout, with
 /// optional colouring.
 ///
 /// `Stringifiy` will simply collect each into a vector for later
+///
+/// `Json` will collect each as a JSON object, with the notes that follow a
+/// diagnostic attached to it rather than given objects of their own
+///
+/// `Custom` will hand each over to be dealt with however it likes
 impl Notify for Notifier {
-    fn notify(&mut self, diagnostic: &Diagnostic) {
+    fn notify(&mut self, diagnostic: &Diagnostic, files: &FileController) {
         match *self {
             Self::Standard(ref stdout) => match stdout {
-                Stdout::NoColour => println!("{}", diagnostic.uncoloured()),
-                Stdout::Colour => println!("{}", diagnostic.coloured()),
+                Stdout::NoColour => println!("{}", diagnostic.uncoloured(files)),
+                Stdout::Colour => println!("{}", diagnostic.coloured(files)),
                 Stdout::Quiet => {}
             },
-            Self::Stringify(ref mut strings) => strings.push(diagnostic.uncoloured()),
+            Self::Stringify(ref mut strings) => strings.push(diagnostic.uncoloured(files)),
+            Self::Json(ref mut objects) => attach(objects, diagnostic),
+            Self::Custom(ref mut notify) => notify.notify(diagnostic, files),
         }
     }
 }
 
 impl Notifier {
+    /// A notifier that passes each diagnostic on to the closure (or `Notify`) given
+    #[must_use]
+    pub fn custom<N: Notify + Send + 'static>(notify: N) -> Self {
+        Self::Custom(Box::new(notify))
+    }
+
     #[must_use]
     pub fn inner(&self) -> Vec<String> {
         match self {
             Self::Stringify(i) => i.clone(),
-            _ => Vec::new(),
+            Self::Json(i) => i.iter().map(Value::to_string).collect(),
+            Self::Standard(_) | Self::Custom(_) => Vec::new(),
         }
     }
 
     pub fn reset(&mut self) {
-        if let Self::Stringify(i) = self {
-            i.clear();
+        match self {
+            Self::Stringify(i) => i.clear(),
+            Self::Json(i) => i.clear(),
+            Self::Standard(_) | Self::Custom(_) => {}
         }
     }
 }
 
-#[derive(Default)]
-pub struct NotificationController {
-    notifiers: HashMap<String, Notifier>,
-    diagnostics: Vec<Diagnostic>,
-}
-
-#[inline]
-pub fn register(name: String, notifier: Notifier) {
-    let mut guard = NOTIFICATION_CONTROLLER.lock().unwrap();
-    guard.register(name, notifier);
-}
-
-#[inline]
-pub fn add_diagnostic(diagnostic: Diagnostic) {
-    let mut guard = NOTIFICATION_CONTROLLER.lock().unwrap();
-    guard.push(diagnostic);
-}
-
-#[inline]
+/// Turn the diagnostics into JSON objects, one for each that isn't a note, with the notes
+/// that follow it attached
 #[must_use]
-pub fn error_count() -> u64 {
-    let guard = NOTIFICATION_CONTROLLER.lock().unwrap();
-    guard
-        .diagnostics()
+pub fn to_json(diagnostics: &[Diagnostic]) -> Vec<Value> {
+    let mut objects = Vec::new();
+    diagnostics
         .iter()
-        .filter(|diag| diag.diagnostic_type() == &DiagType::Error)
-        .count() as u64
+        .for_each(|diagnostic| attach(&mut objects, diagnostic));
+    objects
 }
 
-#[inline]
-pub fn clear(notifier: Option<&str>) {
-    let mut guard = NOTIFICATION_CONTROLLER.lock().unwrap();
-    guard.diagnostics.clear();
-
-    if let Some(n) = notifier {
-        if let Some(no) = guard.notifiers.get_mut(n) {
-            no.reset();
+fn attach(objects: &mut Vec<Value>, diagnostic: &Diagnostic) {
+    match objects.last_mut() {
+        Some(last) if diagnostic.severity() == DiagType::Note => {
+            if let Some(notes) = last["notes"].as_array_mut() {
+                notes.push(diagnostic.to_json());
+            }
         }
+        _ => objects.push(diagnostic.to_json()),
     }
 }
 
-#[inline]
-pub fn notifications() -> Vec<String> {
-    let guard = NOTIFICATION_CONTROLLER.lock().unwrap();
-    guard
-        .notifiers
-        .values()
-        .find_map(|notifier| match notifier {
-            Notifier::Stringify(_) => Some(notifier.inner()),
-            _ => None,
-        })
-        .unwrap_or_else(Vec::new)
+#[derive(Default)]
+pub struct NotificationController {
+    notifiers: HashMap<String, Notifier>,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl NotificationController {
-    fn push(&mut self, diagnostic: Diagnostic) {
+    pub(crate) fn push(&mut self, diagnostic: Diagnostic, files: &FileController) {
         self.diagnostics.push(diagnostic);
-        self.notify();
+        self.notify(files);
     }
 
     #[inline]
-    fn diagnostics(&self) -> &Vec<Diagnostic> {
+    pub(crate) fn diagnostics(&self) -> &Vec<Diagnostic> {
         &self.diagnostics
     }
 
     #[inline]
-    fn register(&mut self, name: String, notification: Notifier) {
+    pub(crate) fn register(&mut self, name: String, notification: Notifier) {
         self.notifiers.insert(name, notification);
     }
 
-    fn notify(&mut self) {
+    #[inline]
+    #[must_use]
+    pub(crate) fn error_count(&self) -> u64 {
+        self.diagnostics
+            .iter()
+            .filter(|diag| diag.diagnostic_type() == &DiagType::Error)
+            .count() as u64
+    }
+
+    /// Remove every diagnostic collected so far, handing them back to the caller
+    #[inline]
+    #[must_use]
+    pub(crate) fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        self.diagnostics.drain(..).collect()
+    }
+
+    #[inline]
+    pub(crate) fn clear(&mut self, notifier: Option<&str>) {
+        self.diagnostics.clear();
+
+        if let Some(n) = notifier {
+            if let Some(no) = self.notifiers.get_mut(n) {
+                no.reset();
+            }
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub(crate) fn notifications(&self) -> Vec<String> {
+        self.notifiers
+            .values()
+            .find_map(|notifier| match notifier {
+                Notifier::Stringify(_) | Notifier::Json(_) => Some(notifier.inner()),
+                _ => None,
+            })
+            .unwrap_or_else(Vec::new)
+    }
+
+    fn notify(&mut self, files: &FileController) {
         if let Some(diagnostic) = self.diagnostics.last() {
             self.notifiers
                 .values_mut()
-                .for_each(|notifier| notifier.notify(diagnostic))
+                .for_each(|notifier| notifier.notify(diagnostic, files))
         }
     }
 }
-
-lazy_static! {
-    pub static ref NOTIFICATION_CONTROLLER: Mutex<NotificationController> =
-        Mutex::new(NotificationController::default());
-}
@@ -0,0 +1,143 @@
+extern crate serde_json;
+use self::serde_json::{json, Value};
+
+use super::{DiagType, Diagnostic};
+
+/// Collects the diagnostics of an assembly run (of however many files) into a SARIF 2.1
+/// log, which code scanning tools can show alongside the source
+///
+/// Each diagnostic is a result, with its code as the rule it broke. The notes that follow
+/// a diagnostic become its related locations, rather than results of their own. A file
+/// that failed without a diagnostic to say why (e.g. the assembler panicked on it) is
+/// noted in the invocation instead, which then isn't counted as successful.
+#[derive(Default)]
+pub struct Sarif {
+    rules: Vec<&'static str>,
+    results: Vec<Value>,
+    notifications: Vec<Value>,
+}
+
+impl Sarif {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, diagnostics: &[Diagnostic]) {
+        diagnostics.iter().for_each(|diagnostic| {
+            if diagnostic.severity() == DiagType::Note {
+                if let Some(related) = self
+                    .results
+                    .last_mut()
+                    .and_then(|result| result["relatedLocations"].as_array_mut())
+                {
+                    let mut location = location(diagnostic);
+                    location["id"] = json!(related.len());
+                    location["message"] = json!({ "text": diagnostic.message() });
+                    related.push(location);
+                    return;
+                }
+            }
+
+            let mut result = json!({
+                "level": level(diagnostic.severity()),
+                "message": { "text": diagnostic.message() },
+                "locations": [location(diagnostic)],
+                "relatedLocations": [],
+            });
+
+            if let Some(code) = diagnostic.code() {
+                let index = self
+                    .rules
+                    .iter()
+                    .position(|rule| *rule == code)
+                    .unwrap_or_else(|| {
+                        self.rules.push(code);
+                        self.rules.len() - 1
+                    });
+
+                result["ruleId"] = json!(code);
+                result["ruleIndex"] = json!(index);
+            }
+
+            self.results.push(result);
+        });
+    }
+
+    /// Record that the file failed for a reason none of its diagnostics give
+    pub fn fail(&mut self, file: &str, reason: &str) {
+        self.notifications.push(json!({
+            "level": "error",
+            "message": { "text": reason },
+            "locations": [{
+                "physicalLocation": {
+                    "artifactLocation": { "uri": file },
+                }
+            }],
+        }));
+    }
+
+    /// The SARIF log, with a single run of the assembler
+    #[must_use]
+    pub fn to_json(&self) -> Value {
+        json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "lc3as",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": self
+                            .rules
+                            .iter()
+                            .map(|rule| json!({ "id": rule }))
+                            .collect::<Vec<_>>(),
+                    }
+                },
+                "invocations": [{
+                    "executionSuccessful": self.notifications.is_empty(),
+                    "toolExecutionNotifications": self.notifications,
+                }],
+                "results": self.results,
+            }],
+        })
+    }
+}
+
+fn level(severity: DiagType) -> &'static str {
+    match severity {
+        DiagType::Error => "error",
+        DiagType::Warning => "warning",
+        DiagType::Note => "note",
+    }
+}
+
+/// Where the diagnostic is, with the region ending just after the last character it
+/// covers (as SARIF's end columns are exclusive). One about the file as a whole (at line
+/// 0, e.g. when it can't be read) has no region at all.
+fn location(diagnostic: &Diagnostic) -> Value {
+    if diagnostic.line() == 0 {
+        return json!({
+            "physicalLocation": {
+                "artifactLocation": { "uri": diagnostic.file() },
+            }
+        });
+    }
+
+    let mut region = json!({
+        "startLine": diagnostic.line(),
+        "startColumn": diagnostic.column(),
+    });
+
+    if diagnostic.width() > 0 {
+        region["endColumn"] = json!(diagnostic.column() + diagnostic.width() as u64);
+    }
+
+    json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": diagnostic.file() },
+            "region": region,
+        }
+    })
+}
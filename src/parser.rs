@@ -1,42 +1,112 @@
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
+};
 
 use crate::{
     assembler::Assembler,
     err,
-    notifier::{self, DiagType, Diagnostic, Highlight},
-    token::{traits::Requirements, Symbol, Token},
+    lint::Lint,
+    notifier::{DiagType, Diagnostic, Highlight, Source},
+    session::Session,
+    suggest,
+    token::{
+        tokens::{
+            expression::{self, Expression, Node},
+            immediate::Immediate,
+            string::String as StringToken,
+        },
+        traits::Requirements,
+        Symbol, Token,
+    },
     types::SymbolTable,
     warn,
 };
 
 #[must_use]
-pub fn parse(mut tokens: Vec<Token>) -> Option<(Vec<Token>, SymbolTable)> {
+pub fn parse(session: &mut Session, mut tokens: Vec<Token>) -> Option<(Vec<Token>, SymbolTable)> {
     let mut address = 0;
     let mut parsed_tokens: VecDeque<Token> = tokens.drain(..).collect();
 
     let mut symbols: SymbolTable = HashMap::new();
+    let mut constants = constant_names(&parsed_tokens);
+    let mut declarations = Vec::new();
+    let mut constant = None;
+    let mut scope: Option<String> = None;
+    let mut line = None;
+    let mut misspelling = None;
+    // Whether the segment has used memory right up to xFFFF, where the address wraps to 0
+    let mut filled = false;
 
     while let Some(mut token) = parsed_tokens.pop_front() {
-        parsed_tokens = token.consume(parsed_tokens);
+        // Each expansion of a macro has lines of its own, even though they're all in the body
+        if line != Some((token.source(), token.line())) {
+            line = Some((token.source(), token.line()));
+
+            // Held back until something goes wrong on the line, so it follows that
+            misspelling =
+                misspelled(&token, &parsed_tokens).map(|note| (note, session.diagnostics().len()));
+
+            if let Some(global) = global_scope(&token, parsed_tokens.front()) {
+                scope = Some(global);
+            }
+
+            qualify_local_labels(session, &mut token, &mut parsed_tokens, scope.as_deref());
+        }
+
+        substitute_constants(&token, &mut parsed_tokens, &symbols, &constants);
+
+        parsed_tokens = token.consume(session, parsed_tokens);
+
+        if let Token::Orig(_)
+        | Token::Blkw(_)
+        | Token::Set(_)
+        | Token::Lshift(_)
+        | Token::Equ(_)
+        | Token::Define(_)
+        | Token::Incbin(_) = token
+        {
+            // Their sizes (or addresses) are needed now, so they can't wait for the second pass
+            expression::resolve(session, &mut token, &symbols, address as i16, true);
+        }
+
+        if let Token::Incbin(ref mut tok) = token {
+            if let Some(Token::String(file)) = tok.operands().first() {
+                if let Some(path) = find_include(session, file) {
+                    tok.load(session, &path);
+                }
+            }
+        }
 
         match &token {
+            Token::Label(_) if matches!(parsed_tokens.front(), Some(Token::Equ(_))) => {
+                // The label names the constant that follows, rather than an address
+                constant = Some(token.clone());
+            }
             Token::Label(ref tok) => {
                 if symbols.contains_key(tok.token()) {
                     err!(
+                        session,
+                        "duplicate-symbol",
                         Highlight,
-                        (*tok.file()).clone(),
+                        tok.source(),
                         tok.column(),
                         tok.line(),
-                        tok.token().len(),
+                        tok.width(),
                         format!("Duplicate symbol found {}", tok.token())
                     );
-                } else if symbols.values().any(|symbol| symbol.address() == address) {
+                } else if symbols
+                    .values()
+                    .any(|symbol| !symbol.is_constant() && symbol.address() == address)
+                {
                     warn!(
+                        session,
+                        Lint::MultipleSymbols,
                         Highlight,
-                        (*tok.file()).clone(),
+                        tok.source(),
                         tok.column(),
                         tok.line(),
-                        tok.token().len(),
+                        tok.width(),
                         format!("Multiple symbols found for address {:#X}", address)
                     );
                 } else {
@@ -46,39 +116,452 @@ pub fn parse(mut tokens: Vec<Token>) -> Option<(Vec<Token>, SymbolTable)> {
                     );
                 }
             }
-            Token::Include(ref token) => {
-                if let Token::String(string) = token.operands().first().unwrap() {
-                    let file = string
-                        .file()
-                        .chars()
-                        .take(string.file().rfind(|c| c == '/').unwrap() + 1)
-                        .collect::<String>()
-                        + string.token();
-
-                    Assembler::from_file(file)
-                        .ok()
-                        .and_then(|assembler| {
-                            assembler.lex().map(|ast| {
-                                let length = parsed_tokens.len();
-                                parsed_tokens.extend(ast.into_iter().rev());
-                                parsed_tokens.rotate_left(length);
-                            })
-                        })
-                        .unwrap();
-                } else {
-                    unreachable!()
+            Token::Include(ref tok) => {
+                if let Some(Token::String(file)) = tok.operands().first() {
+                    include(session, file, &mut parsed_tokens);
+                    constants.extend(constant_names(&parsed_tokens));
                 }
             }
+            Token::Equ(ref tok) => match constant.take() {
+                Some(name) => define_constant(session, &mut symbols, &name, tok.value()),
+                None => err!(
+                    session,
+                    "missing-constant-name",
+                    Highlight,
+                    tok.source(),
+                    tok.column(),
+                    tok.line(),
+                    tok.token().len(),
+                    format!(
+                        "{} needs a label to name the constant, e.g. NAME .EQU #10",
+                        tok.token()
+                    )
+                ),
+            },
+            Token::Define(ref tok) => {
+                if let Some(name) = tok.name() {
+                    define_constant(session, &mut symbols, name, tok.value());
+                }
+            }
+            Token::Global(ref tok) => {
+                declare(session, &mut declarations, true, tok.operands().first());
+            }
+            Token::External(ref tok) => {
+                declare(session, &mut declarations, false, tok.operands().first());
+            }
             Token::Orig(ref tok) => {
                 address = tok.memory_requirement();
+                filled = false;
             }
             token => {
-                address += token.memory_requirement();
+                let requirement = token.memory_requirement();
+                let end = u32::from(address) + u32::from(requirement);
+
+                if end > 0x1_0000 || (filled && requirement > 0) {
+                    err!(
+                        session,
+                        "past-end-of-memory",
+                        Highlight,
+                        token.source(),
+                        token.column(),
+                        token.line(),
+                        token.width(),
+                        format!(
+                            "{} doesn't fit in memory, which ends at xFFFF",
+                            token.token()
+                        )
+                    );
+                }
+
+                filled |= end == 0x1_0000;
+                address = address.wrapping_add(requirement);
+            }
+        }
+
+        if let Some((note, raised)) = misspelling.take() {
+            if session.diagnostics().len() > raised {
+                session.add_diagnostic(note);
+            } else {
+                misspelling = Some((note, raised));
             }
         }
 
         tokens.push(token);
     }
 
-    (notifier::error_count() == 0).then(|| (tokens, symbols))
+    declarations
+        .into_iter()
+        .for_each(|(global, symbol, file, column, line)| {
+            if global && !symbols.contains_key(&symbol) {
+                err!(
+                    session,
+                    "undefined-global",
+                    Highlight,
+                    file,
+                    column,
+                    line,
+                    symbol.len(),
+                    format!("Global symbol {} is never defined", symbol)
+                );
+            } else if !global && symbols.contains_key(&symbol) {
+                err!(
+                    session,
+                    "external-defined",
+                    Highlight,
+                    file,
+                    column,
+                    line,
+                    symbol.len(),
+                    format!("External symbol {} is also defined in this file", symbol)
+                );
+            }
+        });
+
+    (session.error_count() == 0).then(|| (tokens, symbols))
+}
+
+/// Find the file named by an `.INCLUDE` or `.INCBIN`, which is either next to the file
+/// naming it or in one of the include directories
+fn find_include(session: &mut Session, file: &StringToken) -> Option<PathBuf> {
+    let includer = file.file().clone();
+
+    let mut directories = Path::new(&includer)
+        .parent()
+        .map(Path::to_path_buf)
+        .into_iter()
+        .chain(session.include_directories().iter().cloned())
+        .collect::<Vec<_>>();
+    directories.dedup();
+
+    let path = directories
+        .iter()
+        .map(|directory| directory.join(file.token()))
+        .find(|path| path.is_file());
+
+    if path.is_none() {
+        let searched = directories
+            .iter()
+            .map(|directory| match directory.to_string_lossy() {
+                ref directory if directory.is_empty() => String::from("."),
+                directory => directory.into_owned(),
+            })
+            .collect::<Vec<_>>();
+
+        err!(
+            session,
+            "include-not-found",
+            Highlight,
+            file.source(),
+            file.column(),
+            file.line(),
+            // Including the quotes around it
+            file.token().len() + 2,
+            format!(
+                "Can't find '{}' to include, having looked in {}",
+                file.token(),
+                searched.join(", ")
+            )
+        );
+    }
+
+    path
+}
+
+/// Put the tokens of the file named by an `.INCLUDE` in its place. Files are only ever
+/// included once, so headers can be included by everything that needs them.
+fn include(session: &mut Session, file: &StringToken, tokens: &mut VecDeque<Token>) {
+    let includer = file.file().clone();
+    let complain = |session: &mut Session, message: String| {
+        err!(
+            session,
+            "invalid-include",
+            Highlight,
+            file.source(),
+            file.column(),
+            file.line(),
+            file.token().len() + 2,
+            message
+        );
+    };
+
+    let path = match find_include(session, file) {
+        Some(path) => path,
+        None => return,
+    };
+
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+    let chain = session.include_chain(&includer);
+
+    if chain.iter().any(|included| {
+        Path::new(included)
+            .canonicalize()
+            .is_ok_and(|included| included == canonical)
+    }) {
+        complain(
+            session,
+            format!(
+                "Including '{}' would never end, as it's already being included: {} -> {}",
+                file.token(),
+                chain.join(" -> "),
+                path.display()
+            ),
+        );
+        return;
+    }
+
+    if !session.add_include(&path, includer) {
+        return;
+    }
+
+    match Assembler::from_file(path.to_string_lossy().into_owned()) {
+        Ok(assembler) => {
+            if let Some(included) = assembler.lex(session) {
+                included
+                    .into_iter()
+                    .rev()
+                    .for_each(|token| tokens.push_front(token));
+            }
+        }
+        Err(error) => complain(
+            session,
+            format!("Can't read '{}' to include it: {}", path.display(), error),
+        ),
+    }
+}
+
+/// A note for a label starting a line that's followed by operands rather than an
+/// instruction, if it looks like a misspelled mnemonic (e.g. `ADDD R1, R2, R3`). The
+/// word after a label is checked as well, for the likes of `MSG .STRINZ "hi"`.
+fn misspelled(token: &Token, rest: &VecDeque<Token>) -> Option<Diagnostic> {
+    misspelled_keyword(token, rest.front()).or_else(|| match rest.front() {
+        Some(next @ Token::Label(_))
+            if matches!(token, Token::Label(_))
+                && next.line() == token.line()
+                && next.source() == token.source() =>
+        {
+            misspelled_keyword(next, rest.get(1))
+        }
+        _ => None,
+    })
+}
+
+/// A note for the label if it's followed by operands on its line and looks like a
+/// misspelled mnemonic or directive
+fn misspelled_keyword(token: &Token, next: Option<&Token>) -> Option<Diagnostic> {
+    let label = match token {
+        Token::Label(label) => label,
+        _ => return None,
+    };
+
+    match next {
+        Some(
+            next @ (Token::Register(_)
+            | Token::Immediate(_)
+            | Token::Expression(_)
+            | Token::Character(_)
+            | Token::String(_)
+            | Token::Label(_)),
+        ) if next.line() == label.line() && next.source() == label.source() => {}
+        _ => return None,
+    }
+
+    suggest::keyword(label.token()).map(|keyword| {
+        Diagnostic::Highlight(Highlight::new(
+            DiagType::Note,
+            label.source(),
+            label.column(),
+            label.line(),
+            label.width(),
+            format!("'{}' looks like a misspelled '{}'", label.token(), keyword),
+        ))
+    })
+}
+
+/// The names of the constants the tokens define, so that one used before it's defined
+/// can be told apart from a label
+fn constant_names(tokens: &VecDeque<Token>) -> HashSet<String> {
+    tokens
+        .iter()
+        .zip(tokens.iter().skip(1))
+        .filter_map(|pair| match pair {
+            (Token::Label(name), Token::Equ(_)) | (Token::Define(_), Token::Label(name)) => {
+                Some(name.token().to_string())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Replace any constants used as operands of the token with their values, so that
+/// they can be used anywhere an immediate can. PC-relative instructions take them as
+/// an offset, the same as an immediate, rather than as an address like a label.
+///
+/// A constant that's only defined further on becomes an expression instead, which is
+/// worked out in the second pass once every constant is known.
+fn substitute_constants(
+    token: &Token,
+    tokens: &mut VecDeque<Token>,
+    symbols: &SymbolTable,
+    constants: &HashSet<String>,
+) {
+    let skip = match token {
+        Token::Label(_) | Token::Global(_) | Token::External(_) => return,
+        // The first operand is the name of the constant being defined
+        Token::Define(_) => 1,
+        _ => 0,
+    };
+
+    tokens
+        .iter_mut()
+        .take_while(|operand| operand.line() == token.line() && operand.source() == token.source())
+        .skip(skip)
+        .for_each(|operand| {
+            if let Token::Label(label) = operand {
+                let expansion = label.source().expansion();
+                let replacement = match symbols.get(label.token()) {
+                    Some(symbol) if symbol.is_constant() => Token::Immediate(Immediate::new(
+                        label.token().to_string(),
+                        (*label.file()).clone(),
+                        label.column(),
+                        label.line(),
+                        symbol.address() as i16,
+                    )),
+                    None if constants.contains(label.token()) => {
+                        Token::Expression(Expression::new(
+                            label.token().to_string(),
+                            (*label.file()).clone(),
+                            label.column(),
+                            label.line(),
+                            Node::Symbol(label.token().to_string()),
+                        ))
+                    }
+                    _ => return,
+                };
+
+                *operand = replacement;
+                operand.set_expansion(expansion);
+            }
+        });
+}
+
+/// The global label that the local labels after it belong to, if the token is one that
+/// starts a line (and doesn't name a constant)
+#[must_use]
+pub fn global_scope(token: &Token, next: Option<&Token>) -> Option<String> {
+    match token {
+        Token::Label(label) if label.is_global() && !matches!(next, Some(Token::Equ(_))) => {
+            Some(label.token().to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Prefix the local labels on the token's line (e.g. `.LOOP`), including those in
+/// expressions, with the global label they belong to, so that they can be told apart
+/// from those of other subroutines
+pub fn qualify_local_labels(
+    session: &mut Session,
+    token: &mut Token,
+    tokens: &mut VecDeque<Token>,
+    scope: Option<&str>,
+) {
+    let (source, line) = (token.source(), token.line());
+    let is_local = |symbol: &str| symbol.starts_with('.');
+
+    std::iter::once(token)
+        .chain(
+            tokens
+                .iter_mut()
+                .take_while(|operand| operand.line() == line && operand.source() == source),
+        )
+        .for_each(|operand| match operand {
+            Token::Label(label) if label.is_local() => match scope {
+                Some(scope) => label.qualify(scope),
+                None => err!(
+                    session,
+                    "local-label-scope",
+                    Highlight,
+                    label.source(),
+                    label.column(),
+                    label.line(),
+                    label.width(),
+                    format!(
+                        "Local label {} must come after a global label for it to belong to",
+                        label.token()
+                    )
+                ),
+            },
+            Token::Expression(expression) if expression.node.refers_to(&is_local) => match scope {
+                Some(scope) => {
+                    expression.node = expression.node.replace_symbols(&|symbol| {
+                        is_local(symbol).then(|| Node::Symbol(format!("{}{}", scope, symbol)))
+                    });
+                }
+                None => err!(
+                    session,
+                    "local-label-scope",
+                    Highlight,
+                    expression.source(),
+                    expression.column(),
+                    expression.line(),
+                    expression.width(),
+                    format!(
+                        "Local labels in {} must come after a global label for them to belong to",
+                        expression.token()
+                    )
+                ),
+            },
+            _ => {}
+        });
+}
+
+/// Add a constant named by the label to the symbol table, unless the name is taken
+fn define_constant(
+    session: &mut Session,
+    symbols: &mut SymbolTable,
+    name: &Token,
+    value: Option<i16>,
+) {
+    let value = match value {
+        Some(value) => value,
+        None => return,
+    };
+
+    let symbol = name.token();
+
+    if symbols.contains_key(&symbol) {
+        err!(
+            session,
+            "duplicate-symbol",
+            Highlight,
+            name.source(),
+            name.column(),
+            name.line(),
+            symbol.len(),
+            format!("Duplicate symbol found {}", symbol)
+        );
+    } else {
+        symbols.insert(symbol.clone(), Symbol::constant(value, symbol));
+    }
+}
+
+/// Record a `.GLOBAL` or `.EXTERNAL` symbol, remembering where it was declared
+/// so that it can be checked against the symbols once they're all known
+fn declare(
+    session: &mut Session,
+    declarations: &mut Vec<(bool, String, Source, u64, u64)>,
+    global: bool,
+    operand: Option<&Token>,
+) {
+    if let Some(Token::Label(label)) = operand {
+        let symbol = label.token().to_string();
+
+        if global {
+            session.linkage_mut().add_global(symbol.clone());
+        } else {
+            session.linkage_mut().add_external(symbol.clone());
+        }
+
+        declarations.push((global, symbol, label.source(), label.column(), label.line()));
+    }
 }
@@ -0,0 +1,323 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    lexer::preprocessor::{Expansion, Macro},
+    linker::Linkage,
+    lint::{Level, Lint},
+    notifier::{DiagType, Diagnostic, Highlight, NotificationController, Notifier},
+};
+
+#[derive(Default)]
+pub struct FileController {
+    files: HashMap<String, Vec<String>>,
+}
+
+impl FileController {
+    fn add_file(&mut self, file: String) {
+        self.files.insert(file, Vec::new());
+    }
+
+    pub fn add_line(&mut self, file: &str, line: String) {
+        self.files.get_mut(file).unwrap().push(line);
+    }
+
+    #[must_use]
+    pub fn get_line(&self, file: &str, line: u64) -> String {
+        self.files.get(file).unwrap()[(line - 1) as usize].clone()
+    }
+
+    /// The line, if it's been read yet
+    fn find_line(&self, file: &str, line: u64) -> Option<&str> {
+        self.files
+            .get(file)?
+            .get(line.checked_sub(1)? as usize)
+            .map(String::as_str)
+    }
+
+    /// Whether a `; lc3as: allow(name, ...)` comment names the lint, either on the line
+    /// itself or on a line of its own just before it
+    fn allows(&self, file: &str, line: u64, lint: Lint) -> bool {
+        let allowed = |text: &str| {
+            text.split_once("lc3as: allow(")
+                .and_then(|(_, names)| names.split_once(')'))
+                .is_some_and(|(names, _)| names.split(',').any(|name| name.trim() == lint.name()))
+        };
+        let comment = |text: &str| -> Option<usize> { text.find(';').or_else(|| text.find("//")) };
+
+        let here = self
+            .find_line(file, line)
+            .and_then(|text| comment(text).map(|start| &text[start..]))
+            .is_some_and(allowed);
+        let before = self
+            .find_line(file, line.wrapping_sub(1))
+            .filter(|text| comment(text) == Some(text.len() - text.trim_start().len()))
+            .is_some_and(allowed);
+
+        here || before
+    }
+
+    pub fn remove(&mut self, file: &str) {
+        self.files.remove(file);
+    }
+}
+
+/// Everything that used to be shared between every assembly in the process:
+/// the source lines of each file (for rendering diagnostics), the diagnostics
+/// raised so far, the notifiers that want to hear about them, and the symbols
+/// the program shares with others.
+///
+/// Each program gets its own session, so several programs can be assembled at
+/// once (e.g. on different threads) without stepping on each other.
+#[derive(Default)]
+pub struct Session {
+    files: FileController,
+    notifications: NotificationController,
+    linkage: Linkage,
+    macros: HashMap<String, Macro>,
+    expansions: Vec<Expansion>,
+    defines: HashMap<String, i16>,
+    include_directories: Vec<PathBuf>,
+    includers: HashMap<String, String>,
+    included: HashSet<PathBuf>,
+    sources: HashMap<u16, (String, u64)>,
+    lints: HashMap<Lint, Level>,
+    warnings_as_errors: bool,
+    // Whether the last warning was dropped, so the notes following it should be too
+    suppressed: bool,
+}
+
+impl Session {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a notifier that will be told about every diagnostic raised
+    /// in this session
+    pub fn register(&mut self, name: String, notifier: Notifier) {
+        self.notifications.register(name, notifier);
+    }
+
+    /// Raise a diagnostic. Anything raised inside a macro expansion (which is in the
+    /// macro body) is followed by a note pointing at each call site that led there.
+    ///
+    /// Warnings for lints that have been allowed (here, or in the source) are dropped,
+    /// along with any notes that follow them, and those for lints that have been made
+    /// errors are raised as errors.
+    pub fn add_diagnostic(&mut self, mut diagnostic: Diagnostic) {
+        let mut expansion = diagnostic.expansion();
+
+        if diagnostic.severity() == DiagType::Note {
+            if self.suppressed {
+                return;
+            }
+        } else {
+            self.suppressed = false;
+        }
+
+        if let Some(lint) = diagnostic.lint() {
+            match self.lint_level(lint) {
+                Level::Allow => {
+                    self.suppressed = true;
+                    return;
+                }
+                _ if self
+                    .files
+                    .allows(diagnostic.file(), diagnostic.line(), lint) =>
+                {
+                    self.suppressed = true;
+                    return;
+                }
+                Level::Error => diagnostic.set_severity(DiagType::Error),
+                Level::Warn => {}
+            }
+        }
+
+        self.notifications.push(diagnostic, &self.files);
+
+        while let Some(index) = expansion {
+            let call = &self.expansions[index];
+
+            let note = Diagnostic::Highlight(Highlight::new(
+                DiagType::Note,
+                call.source().file().clone(),
+                call.column(),
+                call.line(),
+                call.width(),
+                format!("In expansion of macro {}", call.name()),
+            ));
+
+            self.notifications.push(note, &self.files);
+            expansion = call.source().expansion();
+        }
+    }
+
+    #[must_use]
+    pub fn diagnostics(&self) -> &Vec<Diagnostic> {
+        self.notifications.diagnostics()
+    }
+
+    #[must_use]
+    pub fn error_count(&self) -> u64 {
+        self.notifications.error_count()
+    }
+
+    /// Remove every diagnostic collected so far, handing them back to the caller
+    #[must_use]
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        self.notifications.take_diagnostics()
+    }
+
+    /// Clear the diagnostics, along with anything collected by the notifier given
+    pub fn clear(&mut self, notifier: Option<&str>) {
+        self.notifications.clear(notifier);
+    }
+
+    /// The rendered diagnostics collected by a `Stringify` (or `Json`) notifier, if one was
+    /// registered
+    #[must_use]
+    pub fn notifications(&self) -> Vec<String> {
+        self.notifications.notifications()
+    }
+
+    #[must_use]
+    pub fn files(&self) -> &FileController {
+        &self.files
+    }
+
+    pub fn add_file(&mut self, file: String) {
+        self.files.add_file(file);
+    }
+
+    pub fn add_line(&mut self, file: &str, line: String) {
+        self.files.add_line(file, line);
+    }
+
+    #[must_use]
+    pub fn linkage(&self) -> &Linkage {
+        &self.linkage
+    }
+
+    pub fn linkage_mut(&mut self) -> &mut Linkage {
+        &mut self.linkage
+    }
+
+    /// Remove the linkage collected so far, handing it back to the caller
+    #[must_use]
+    pub fn take_linkage(&mut self) -> Linkage {
+        std::mem::take(&mut self.linkage)
+    }
+
+    #[must_use]
+    pub fn macros(&self) -> &HashMap<String, Macro> {
+        &self.macros
+    }
+
+    pub fn macros_mut(&mut self) -> &mut HashMap<String, Macro> {
+        &mut self.macros
+    }
+
+    /// Remember where a macro was expanded, handing back an index for it to mark the
+    /// tokens that came out of the expansion with
+    pub(crate) fn add_expansion(&mut self, expansion: Expansion) -> usize {
+        self.expansions.push(expansion);
+        self.expansions.len() - 1
+    }
+
+    /// Turn a lint on or off (they're all on to begin with)
+    pub fn set_lint(&mut self, lint: Lint, enabled: bool) {
+        self.set_lint_level(lint, if enabled { Level::Warn } else { Level::Allow });
+    }
+
+    #[must_use]
+    pub fn is_lint_enabled(&self, lint: Lint) -> bool {
+        self.lint_level(lint) != Level::Allow
+    }
+
+    pub fn set_lint_level(&mut self, lint: Lint, level: Level) {
+        self.lints.insert(lint, level);
+    }
+
+    /// What happens to warnings for the lint, which is set on its own, or by making
+    /// every warning an error
+    #[must_use]
+    pub fn lint_level(&self, lint: Lint) -> Level {
+        match self.lints.get(&lint) {
+            Some(level) => *level,
+            None if self.warnings_as_errors => Level::Error,
+            None => Level::Warn,
+        }
+    }
+
+    /// Raise every warning (of a lint without a level of its own) as an error instead
+    pub fn set_warnings_as_errors(&mut self, errors: bool) {
+        self.warnings_as_errors = errors;
+    }
+
+    /// Set a symbol for conditional assembly (e.g. `-D NAME=value` on the command line)
+    pub fn define(&mut self, name: String, value: i16) {
+        self.defines.insert(name, value);
+    }
+
+    #[must_use]
+    pub fn defined(&self, name: &str) -> Option<i16> {
+        self.defines.get(name).copied()
+    }
+
+    /// Add a directory to search for `.INCLUDE`d files (e.g. `-I DIR` on the command line),
+    /// after the directory of the file doing the including
+    pub fn add_include_directory(&mut self, directory: PathBuf) {
+        self.include_directories.push(directory);
+    }
+
+    #[must_use]
+    pub fn include_directories(&self) -> &[PathBuf] {
+        &self.include_directories
+    }
+
+    /// Remember that `file` was included by `includer`, marking it so it isn't included
+    /// again. Hands back whether this is the first time it's been included.
+    pub(crate) fn add_include(&mut self, file: &Path, includer: String) -> bool {
+        let canonical = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+
+        if self.included.insert(canonical) {
+            self.includers
+                .insert(file.to_string_lossy().into_owned(), includer);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The files that were included to get to `file`, starting from the file being
+    /// assembled and ending with `file` itself
+    #[must_use]
+    pub(crate) fn include_chain(&self, file: &str) -> Vec<String> {
+        let mut chain = vec![file.to_string()];
+
+        while let Some(includer) = self.includers.get(chain.last().unwrap()) {
+            if chain.contains(includer) {
+                break;
+            }
+            chain.push(includer.clone());
+        }
+
+        chain.reverse();
+        chain
+    }
+
+    /// Remember which line of which file the word at `address` was assembled from
+    pub(crate) fn add_source(&mut self, address: u16, file: &str, line: u64) {
+        self.sources.insert(address, (file.to_string(), line));
+    }
+
+    /// The file and line the word at `address` was assembled from, e.g. so a debugger
+    /// can show the source of the instruction it's stopped at
+    #[must_use]
+    pub fn source(&self, address: u16) -> Option<(&String, u64)> {
+        self.sources.get(&address).map(|(file, line)| (file, *line))
+    }
+}
@@ -0,0 +1,537 @@
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+use crate::{types::Program, writer::read_object};
+
+/// The number of words of memory, i.e. everything a 16 bit address can reach
+pub const MEMORY_SIZE: usize = 0x10000;
+
+/// Where `PC` starts if nothing that's loaded says otherwise
+pub const DEFAULT_ORIGIN: u16 = 0x3000;
+
+// Device registers
+pub const KBSR: u16 = 0xFE00;
+pub const KBDR: u16 = 0xFE02;
+pub const DSR: u16 = 0xFE04;
+pub const DDR: u16 = 0xFE06;
+pub const MCR: u16 = 0xFFFE;
+
+/// The privilege bit of the PSR, set when running in user mode
+const USER_MODE: u16 = 0x8000;
+
+/// Where the supervisor stack starts, before anything has been pushed to it
+const SUPERVISOR_STACK: u16 = 0x3000;
+
+/// The table of service routines for exceptions and interrupts
+const INTERRUPT_VECTOR_TABLE: u16 = 0x0100;
+const PRIVILEGE_MODE_VIOLATION: u16 = 0x00;
+const ILLEGAL_OPCODE: u16 = 0x01;
+
+/// Why the simulator couldn't carry on
+#[derive(Debug)]
+pub enum Error {
+    /// An instruction that needs supervisor mode (e.g. `RTI`) was run in user mode, and
+    /// there's no service routine for it
+    PrivilegeViolation(u16),
+    /// The reserved opcode was run, and there's no service routine for it
+    IllegalOpcode(u16),
+    /// A `TRAP` with no service routine, other than the standard ones
+    UnknownTrap(u8, u16),
+    /// `GETC` or `IN` needed a character, but the input has run out
+    EndOfInput(u16),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::PrivilegeViolation(address) => write!(
+                f,
+                "Privilege mode violation at x{:04X}, as it can only be run in supervisor mode",
+                address
+            ),
+            Self::IllegalOpcode(address) => write!(f, "Illegal opcode at x{:04X}", address),
+            Self::UnknownTrap(vector, address) => write!(
+                f,
+                "TRAP x{:02X} at x{:04X} has no service routine",
+                vector, address
+            ),
+            Self::EndOfInput(address) => {
+                write!(f, "Ran out of input for the TRAP at x{:04X}", address)
+            }
+            Self::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Runs LC-3 programs, reading characters from `input` and writing them to `output`
+/// (for the TRAP routines and the keyboard and display device registers)
+pub struct Simulator<I: Read, O: Write> {
+    memory: Vec<u16>,
+    registers: [u16; 8],
+    pc: u16,
+    psr: u16,
+    saved_ssp: u16,
+    saved_usp: u16,
+    halted: bool,
+    input: I,
+    output: O,
+    /// A character read from the input to check if there is one, that's yet to be used
+    pending: Option<u8>,
+}
+
+impl<I: Read, O: Write> Simulator<I, O> {
+    /// Create a simulator with empty memory, ready to run from `x3000` in user mode
+    #[must_use]
+    pub fn new(input: I, output: O) -> Self {
+        let mut memory = vec![0; MEMORY_SIZE];
+        // The clock is running
+        memory[MCR as usize] = 0x8000;
+
+        Self {
+            memory,
+            registers: [0; 8],
+            pc: DEFAULT_ORIGIN,
+            psr: USER_MODE | 0x2,
+            saved_ssp: SUPERVISOR_STACK,
+            saved_usp: 0,
+            halted: false,
+            input,
+            output,
+            pending: None,
+        }
+    }
+
+    /// Load each segment of an assembled program into memory, starting the program
+    /// at the origin of its first segment
+    pub fn load_program(&mut self, program: &Program) {
+        let (_, segments) = program;
+
+        segments.iter().for_each(|segment| {
+            segment.words().enumerate().for_each(|(offset, word)| {
+                self.write(segment.origin().wrapping_add(offset as u16), word);
+            });
+        });
+
+        if let Some(segment) = segments.first() {
+            self.pc = segment.origin();
+        }
+    }
+
+    /// Load an object file, either a plain one (an origin followed by its words) or a
+    /// container of several segments, as written by `Writer`
+    ///
+    /// # Errors
+    ///   If the object can't be read
+    pub fn load_object<R: Read>(&mut self, object: R) -> Result<(), io::Error> {
+        let segments = read_object(object)?;
+
+        segments.iter().for_each(|(origin, segment)| {
+            segment.iter().enumerate().for_each(|(offset, word)| {
+                self.write(origin.wrapping_add(offset as u16), *word);
+            });
+        });
+
+        if let Some((origin, _)) = segments.first() {
+            self.pc = *origin;
+        }
+
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn register(&self, register: usize) -> u16 {
+        self.registers[register]
+    }
+
+    pub fn set_register(&mut self, register: usize, value: u16) {
+        self.registers[register] = value;
+    }
+
+    #[must_use]
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    #[must_use]
+    pub fn psr(&self) -> u16 {
+        self.psr
+    }
+
+    pub fn set_psr(&mut self, psr: u16) {
+        self.psr = psr;
+    }
+
+    /// Whether the program is running in supervisor mode
+    #[must_use]
+    pub fn is_supervisor(&self) -> bool {
+        self.psr & USER_MODE == 0
+    }
+
+    /// The condition codes, as `n`, `z` and `p` (e.g. `"z"`)
+    #[must_use]
+    pub fn condition(&self) -> &'static str {
+        match self.psr & 0x7 {
+            0x4 => "n",
+            0x2 => "z",
+            _ => "p",
+        }
+    }
+
+    #[must_use]
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Let a halted program carry on (e.g. after loading another one)
+    pub fn resume(&mut self) {
+        self.halted = false;
+        self.memory[MCR as usize] |= 0x8000;
+    }
+
+    /// Read a word of memory without any of the side effects a program reading it would
+    /// have (i.e. reading `KBDR` doesn't use up a character)
+    #[must_use]
+    pub fn memory(&self, address: u16) -> u16 {
+        self.memory[address as usize]
+    }
+
+    /// Write a word of memory without any of the side effects a program writing it would
+    /// have (i.e. writing `DDR` doesn't output anything)
+    pub fn write(&mut self, address: u16, value: u16) {
+        self.memory[address as usize] = value;
+    }
+
+    #[must_use]
+    pub fn output(&self) -> &O {
+        &self.output
+    }
+
+    #[must_use]
+    pub fn into_output(self) -> O {
+        self.output
+    }
+
+    /// Run until the program halts, or `limit` instructions have been run, handing back
+    /// how many were run
+    ///
+    /// # Errors
+    ///   If an instruction couldn't be run, see `Simulator::step`
+    pub fn run(&mut self, limit: u64) -> Result<u64, Error> {
+        let mut steps = 0;
+
+        while !self.halted && steps < limit {
+            self.step()?;
+            steps += 1;
+        }
+
+        Ok(steps)
+    }
+
+    /// Run a single instruction
+    ///
+    /// # Errors
+    ///   If the instruction is privileged or illegal (and there's no service routine for
+    ///   it), it's a TRAP without a service routine, a TRAP ran out of input, or the
+    ///   output can't be written to
+    pub fn step(&mut self) -> Result<(), Error> {
+        if self.halted {
+            return Ok(());
+        }
+
+        let address = self.pc;
+        let instruction = self.load(address)?;
+        self.pc = self.pc.wrapping_add(1);
+
+        let destination = usize::from(instruction >> 9 & 0x7);
+        let source = usize::from(instruction >> 6 & 0x7);
+        let offset = |bits: u32| sign_extend(instruction, bits);
+
+        match instruction >> 12 {
+            // ADD and AND
+            opcode @ (0x1 | 0x5) => {
+                let operand = if instruction & 0x20 == 0 {
+                    self.registers[usize::from(instruction & 0x7)]
+                } else {
+                    offset(5)
+                };
+
+                self.registers[destination] = if opcode == 0x1 {
+                    self.registers[source].wrapping_add(operand)
+                } else {
+                    self.registers[source] & operand
+                };
+                self.set_condition(self.registers[destination]);
+            }
+            // BR
+            0x0 => {
+                if instruction >> 9 & self.psr & 0x7 != 0 {
+                    self.pc = self.pc.wrapping_add(offset(9));
+                }
+            }
+            // JMP, RET and JMPT
+            0xC => {
+                let target = self.registers[source];
+
+                if instruction & 0x1 != 0 {
+                    if !self.is_supervisor() {
+                        return self.exception(PRIVILEGE_MODE_VIOLATION, address);
+                    }
+
+                    // Drop to user mode, switching to its stack
+                    self.saved_ssp = self.registers[6];
+                    self.registers[6] = self.saved_usp;
+                    self.psr |= USER_MODE;
+                }
+
+                self.pc = target;
+            }
+            // JSR and JSRR
+            0x4 => {
+                let target = if instruction & 0x800 == 0 {
+                    self.registers[source]
+                } else {
+                    self.pc.wrapping_add(offset(11))
+                };
+
+                self.registers[7] = self.pc;
+                self.pc = target;
+            }
+            // LD
+            0x2 => {
+                self.registers[destination] = self.load(self.pc.wrapping_add(offset(9)))?;
+                self.set_condition(self.registers[destination]);
+            }
+            // LDI
+            0xA => {
+                let pointer = self.load(self.pc.wrapping_add(offset(9)))?;
+                self.registers[destination] = self.load(pointer)?;
+                self.set_condition(self.registers[destination]);
+            }
+            // LDR
+            0x6 => {
+                self.registers[destination] =
+                    self.load(self.registers[source].wrapping_add(offset(6)))?;
+                self.set_condition(self.registers[destination]);
+            }
+            // LEA (which doesn't set the condition codes)
+            0xE => {
+                self.registers[destination] = self.pc.wrapping_add(offset(9));
+            }
+            // NOT
+            0x9 => {
+                self.registers[destination] = !self.registers[source];
+                self.set_condition(self.registers[destination]);
+            }
+            // RTI
+            0x8 => {
+                if !self.is_supervisor() {
+                    return self.exception(PRIVILEGE_MODE_VIOLATION, address);
+                }
+
+                self.pc = self.pop()?;
+                self.psr = self.pop()?;
+
+                if !self.is_supervisor() {
+                    self.saved_ssp = self.registers[6];
+                    self.registers[6] = self.saved_usp;
+                }
+            }
+            // ST
+            0x3 => self.store(self.pc.wrapping_add(offset(9)), self.registers[destination])?,
+            // STI
+            0xB => {
+                let pointer = self.load(self.pc.wrapping_add(offset(9)))?;
+                self.store(pointer, self.registers[destination])?;
+            }
+            // STR
+            0x7 => self.store(
+                self.registers[source].wrapping_add(offset(6)),
+                self.registers[destination],
+            )?,
+            // TRAP
+            0xF => self.trap((instruction & 0xFF) as u8, address)?,
+            // The reserved opcode
+            _ => return self.exception(ILLEGAL_OPCODE, address),
+        }
+
+        Ok(())
+    }
+
+    fn set_condition(&mut self, value: u16) {
+        let condition = match value as i16 {
+            value if value < 0 => 0x4,
+            0 => 0x2,
+            _ => 0x1,
+        };
+
+        self.psr = self.psr & !0x7 | condition;
+    }
+
+    /// Read a word as a program would, going to the keyboard for `KBSR` and `KBDR`
+    fn load(&mut self, address: u16) -> Result<u16, Error> {
+        Ok(match address {
+            KBSR => {
+                if self.peek()?.is_some() {
+                    0x8000
+                } else {
+                    0
+                }
+            }
+            KBDR => self.getc()?.map_or(0, u16::from),
+            DSR => 0x8000,
+            _ => self.memory[address as usize],
+        })
+    }
+
+    /// Write a word as a program would, going to the display for `DDR`
+    fn store(&mut self, address: u16, value: u16) -> Result<(), Error> {
+        match address {
+            DDR => self.putc(value)?,
+            MCR if value & 0x8000 == 0 => self.halted = true,
+            _ => {}
+        }
+
+        self.memory[address as usize] = value;
+        Ok(())
+    }
+
+    fn push(&mut self, value: u16) -> Result<(), Error> {
+        self.registers[6] = self.registers[6].wrapping_sub(1);
+        self.store(self.registers[6], value)
+    }
+
+    fn pop(&mut self) -> Result<u16, Error> {
+        let value = self.load(self.registers[6])?;
+        self.registers[6] = self.registers[6].wrapping_add(1);
+        Ok(value)
+    }
+
+    /// Go to the service routine for an exception, in supervisor mode, or give up if
+    /// nothing has been loaded to deal with it
+    fn exception(&mut self, vector: u16, address: u16) -> Result<(), Error> {
+        let routine = self.memory[(INTERRUPT_VECTOR_TABLE + vector) as usize];
+
+        if routine == 0 {
+            return Err(if vector == PRIVILEGE_MODE_VIOLATION {
+                Error::PrivilegeViolation(address)
+            } else {
+                Error::IllegalOpcode(address)
+            });
+        }
+
+        let psr = self.psr;
+
+        if !self.is_supervisor() {
+            self.saved_usp = self.registers[6];
+            self.registers[6] = self.saved_ssp;
+            self.psr &= !USER_MODE;
+        }
+
+        self.push(psr)?;
+        self.push(self.pc)?;
+        self.pc = routine;
+
+        Ok(())
+    }
+
+    /// Run a TRAP, using its service routine if one has been loaded, and otherwise
+    /// doing what the standard routines would
+    fn trap(&mut self, vector: u8, address: u16) -> Result<(), Error> {
+        self.registers[7] = self.pc;
+
+        let routine = self.memory[usize::from(vector)];
+        if routine != 0 {
+            self.pc = routine;
+            return Ok(());
+        }
+
+        match vector {
+            // GETC
+            0x20 => self.registers[0] = self.read_character(address)?,
+            // OUT
+            0x21 => self.putc(self.registers[0])?,
+            // PUTS
+            0x22 => {
+                let mut address = self.registers[0];
+                while self.memory[address as usize] != 0 {
+                    self.putc(self.memory[address as usize])?;
+                    address = address.wrapping_add(1);
+                }
+            }
+            // IN
+            0x23 => {
+                self.output.write_all(b"Input a character> ")?;
+                self.registers[0] = self.read_character(address)?;
+                self.putc(self.registers[0])?;
+            }
+            // PUTSP
+            0x24 => {
+                let mut address = self.registers[0];
+                while self.memory[address as usize] != 0 {
+                    let word = self.memory[address as usize];
+                    self.putc(word & 0xFF)?;
+                    if word >> 8 != 0 {
+                        self.putc(word >> 8)?;
+                    }
+                    address = address.wrapping_add(1);
+                }
+            }
+            // HALT
+            0x25 => {
+                self.halted = true;
+                self.memory[MCR as usize] &= !0x8000;
+            }
+            _ => return Err(Error::UnknownTrap(vector, address)),
+        }
+
+        self.output.flush()?;
+        Ok(())
+    }
+
+    fn read_character(&mut self, address: u16) -> Result<u16, Error> {
+        self.getc()?
+            .map(u16::from)
+            .ok_or(Error::EndOfInput(address))
+    }
+
+    /// Look at the next character of input, without using it up
+    fn peek(&mut self) -> Result<Option<u8>, Error> {
+        if self.pending.is_none() {
+            let mut byte = [0];
+            if self.input.read(&mut byte)? == 1 {
+                self.pending = Some(byte[0]);
+            }
+        }
+
+        Ok(self.pending)
+    }
+
+    fn getc(&mut self) -> Result<Option<u8>, Error> {
+        self.peek()?;
+        Ok(self.pending.take())
+    }
+
+    fn putc(&mut self, character: u16) -> Result<(), Error> {
+        self.output.write_all(&[(character & 0xFF) as u8])?;
+        Ok(())
+    }
+}
+
+/// Sign extend the low `bits` bits of an instruction
+fn sign_extend(instruction: u16, bits: u32) -> u16 {
+    let shift = 16 - bits;
+    ((instruction << shift) as i16 >> shift) as u16
+}
@@ -0,0 +1,81 @@
+use crate::{
+    lexer::tokenizer::{DIRECTIVES, MNEMONICS},
+    types::SymbolTable,
+};
+
+/// The candidate closest to the word (ignoring case), if any is close enough to be what
+/// was meant. Longer words are allowed more mistakes, and ties go to the candidate that
+/// sorts first, so the same suggestion is always made.
+#[must_use]
+pub fn closest<'a, I>(word: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let allowed = (word.chars().count() / 3).max(1);
+    let upper = word.to_ascii_uppercase();
+
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != word)
+        .map(|candidate| (distance(&upper, &candidate.to_ascii_uppercase()), candidate))
+        .filter(|(distance, _)| *distance <= allowed)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// The label in the symbol table that a label which couldn't be found was probably
+/// meant to be. Local labels are given as they'd be written, e.g. `.LOOP`.
+#[must_use]
+pub fn label<'a>(label: &str, symbols: &'a SymbolTable) -> Option<&'a str> {
+    let suggestion = closest(
+        label,
+        symbols
+            .values()
+            .filter(|symbol| !symbol.is_constant())
+            .map(|symbol| symbol.symbol().as_str()),
+    )?;
+
+    // Both belong to the same global label, so the local part is all that differs
+    Some(match (label.find('.'), suggestion.find('.')) {
+        (Some(at), Some(other)) if label[..at] == suggestion[..other] => &suggestion[other..],
+        _ => suggestion,
+    })
+}
+
+/// The mnemonic or directive that the word was probably meant to be
+#[must_use]
+pub fn keyword(word: &str) -> Option<&'static str> {
+    closest(word, MNEMONICS.iter().chain(DIRECTIVES.iter()).copied())
+}
+
+/// How many characters have to be inserted, removed, replaced or swapped with the one
+/// next to them to turn one word into the other
+fn distance(from: &str, to: &str) -> usize {
+    let from = from.chars().collect::<Vec<_>>();
+    let to = to.chars().collect::<Vec<_>>();
+
+    // Only the last two rows are needed to work out the next
+    let mut before = Vec::new();
+    let mut previous = (0..=to.len()).collect::<Vec<_>>();
+
+    for i in 1..=from.len() {
+        let mut current = vec![i; to.len() + 1];
+
+        for j in 1..=to.len() {
+            let cost = usize::from(from[i - 1] != to[j - 1]);
+
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+
+            if i > 1 && j > 1 && from[i - 1] == to[j - 2] && from[i - 2] == to[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+
+        before = previous;
+        previous = current;
+    }
+
+    previous[to.len()]
+}
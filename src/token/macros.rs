@@ -16,6 +16,15 @@ macro_rules! token_of {
     }
 }
 
+macro_rules! width_of {
+    ( $self:expr, $( $token:path ),* ) => {
+        match *$self {
+            $( $token(ref token) => token.width(), )+
+            _ => unreachable!(),
+        }
+    }
+}
+
 macro_rules! column_of {
     ( $self:expr, $( $token:path ),* ) => {
         match *$self {
@@ -43,6 +52,42 @@ macro_rules! file_of {
     }
 }
 
+macro_rules! source_of {
+    ( $self:expr, $( $token:path ),* ) => {
+        match *$self {
+            $( $token(ref token) => token.source(), )+
+            _ => unreachable!(),
+        }
+    }
+}
+
+macro_rules! set_expansion_of {
+    ( $self:expr, $expansion:expr, $( $token:path ),* ) => {
+        match *$self {
+            $( $token(ref mut token) => token.set_expansion($expansion), )+
+            _ => {}
+        }
+    }
+}
+
+macro_rules! operands_of {
+    ( $self:expr, $( $token:path ),* ) => {
+        match *$self {
+            $( $token(ref token) => Some(token.operands()), )+
+            _ => None,
+        }
+    }
+}
+
+macro_rules! operands_of_mut {
+    ( $self:expr, $( $token:path ),* ) => {
+        match *$self {
+            $( $token(ref mut token) => Some(token.operands_mut()), )+
+            _ => None,
+        }
+    }
+}
+
 macro_rules! memory_requirement_of {
     ( $self:expr, $( $token:path ),* ) => {
         match *$self {
@@ -53,15 +98,16 @@ macro_rules! memory_requirement_of {
 }
 
 macro_rules! consume {
-    ( $self:expr, $tokens:expr, $( $token_type:path ),*, 0, $( $fail_token:path ),* ) => {
+    ( $self:expr, $session:expr, $tokens:expr, $( $token_type:path ),*, 0, $( $fail_token:path ),* ) => {
         match $self {
-            $( $token_type(ref mut token) => token.consume($tokens), )+
+            $( $token_type(ref mut token) => token.consume($session, $tokens), )+
             $( $fail_token(ref token) => {
                 expected(
-                    $self.file(),
+                    $session,
+                    token.source(),
                     &["Instruction", "Directive", "Label"],
                     None,
-                    (token.column(), token.line(), token.token().len()),
+                    (token.column(), token.line(), token.width()),
                 );
                 $tokens
             }
@@ -72,9 +118,9 @@ macro_rules! consume {
 }
 
 macro_rules! assembled {
-    ( $self:expr, $program_counter:expr, $symbols:expr, $symbol:expr, $( $token_type:path ),* ) => {
+    ( $self:expr, $session:expr, $program_counter:expr, $symbols:expr, $symbol:expr, $( $token_type:path ),* ) => {
         match $self {
-            $( $token_type(token) => token.assembled($program_counter, $symbols, $symbol), )+
+            $( $token_type(token) => token.assembled($session, $program_counter, $symbols, $symbol), )+
             _ => Vec::new(),
         }
     };
@@ -1,5 +1,5 @@
 pub use self::r#type::Token;
-pub use self::symbol::Symbol;
+pub use self::symbol::{Symbol, SymbolKind};
 pub use self::tokens::traits;
 
 #[macro_use]
@@ -1,15 +1,39 @@
-#[derive(Debug)]
+/// What a symbol stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// A label, naming the address of whatever follows it
+    Address,
+    /// A name given to a value with `.EQU` or `.DEFINE`
+    Constant,
+}
+
+#[derive(Debug, Clone)]
 pub struct Symbol {
     address: u16,
     symbol: String,
+    kind: SymbolKind,
 }
 
 impl Symbol {
     #[must_use]
     pub fn new(address: u16, symbol: String) -> Self {
-        Self { address, symbol }
+        Self {
+            address,
+            symbol,
+            kind: SymbolKind::Address,
+        }
     }
 
+    #[must_use]
+    pub fn constant(value: i16, symbol: String) -> Self {
+        Self {
+            address: value as u16,
+            symbol,
+            kind: SymbolKind::Constant,
+        }
+    }
+
+    /// The address of a label, or the value of a constant (as a word)
     #[must_use]
     pub fn address(&self) -> u16 {
         self.address
@@ -19,4 +43,24 @@ impl Symbol {
     pub fn symbol(&self) -> &String {
         &self.symbol
     }
+
+    #[must_use]
+    pub fn kind(&self) -> SymbolKind {
+        self.kind
+    }
+
+    #[must_use]
+    pub fn is_constant(&self) -> bool {
+        self.kind == SymbolKind::Constant
+    }
+
+    /// The value of the symbol when it's used in an expression. Constants are signed,
+    /// so that e.g. `#MINUS_ONE + 1` is zero, whereas addresses are not.
+    #[must_use]
+    pub fn value(&self) -> i32 {
+        match self.kind {
+            SymbolKind::Address => i32::from(self.address),
+            SymbolKind::Constant => i32::from(self.address as i16),
+        }
+    }
 }
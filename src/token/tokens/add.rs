@@ -2,9 +2,10 @@ use std::collections::VecDeque;
 
 use crate::{
     listing,
+    session::Session,
     token::{
         tokens::{
-            expected, too_few_operands,
+            expected, immediate_field, too_few_operands,
             traits::{Assemble, Requirements},
         },
         Token,
@@ -17,11 +18,12 @@ token!(Add);
 impl Assemble for Add {
     fn assembled(
         mut self,
+        session: &mut Session,
         program_counter: &mut i16,
         _symbols: &SymbolTable,
         symbol: &str,
     ) -> Listings {
-        *program_counter += 1;
+        *program_counter = program_counter.wrapping_add(1);
 
         let destination_register = if let Token::Register(register) = self.operands.remove(0) {
             register.register
@@ -37,7 +39,16 @@ impl Assemble for Add {
 
         let source_two = match self.operands.last() {
             Some(Token::Register(register)) => register.register,
-            Some(Token::Immediate(imm)) => (0x20 | (imm.value & 0x1F)) as u16,
+            Some(immediate @ Token::Immediate(_)) => {
+                0x20 | immediate_field(
+                    session,
+                    immediate,
+                    5,
+                    true,
+                    "ADD's imm5 field",
+                    Some("Consider loading it into a register with .SET instead"),
+                )
+            }
             Some(_) => unreachable!(),
             None => source_one,
         };
@@ -46,7 +57,7 @@ impl Assemble for Add {
 
         vec![listing!(
             instruction,
-            *program_counter - 1,
+            program_counter.wrapping_sub(1),
             self.line,
             symbol,
             "ADD",
@@ -66,18 +77,18 @@ impl Requirements for Add {
         1
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Register);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Register);
 
         maybe_expect!(self, tokens, Register);
 
         if self.operands.len() == 2 {
-            maybe_expect!(self, tokens, Immediate, Register);
+            maybe_expect!(self, tokens, Immediate, Expression, Register);
         } else {
             maybe_expect!(self, tokens, Register);
         }
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
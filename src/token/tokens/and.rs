@@ -2,9 +2,10 @@ use std::collections::VecDeque;
 
 use crate::{
     listing,
+    session::Session,
     token::{
         tokens::{
-            expected, too_few_operands,
+            expected, immediate_field, too_few_operands,
             traits::{Assemble, Requirements},
         },
         Token,
@@ -17,11 +18,12 @@ token!(And);
 impl Assemble for And {
     fn assembled(
         mut self,
+        session: &mut Session,
         program_counter: &mut i16,
         _symbols: &SymbolTable,
         symbol: &str,
     ) -> Listings {
-        *program_counter += 1;
+        *program_counter = program_counter.wrapping_add(1);
 
         let destination_register = if let Token::Register(register) = self.operands.remove(0) {
             register.register
@@ -37,7 +39,16 @@ impl Assemble for And {
 
         let source_two = match self.operands.last() {
             Some(Token::Register(register)) => register.register,
-            Some(Token::Immediate(imm)) => (0x20 | (imm.value & 0x1F)) as u16,
+            Some(immediate @ Token::Immediate(_)) => {
+                0x20 | immediate_field(
+                    session,
+                    immediate,
+                    5,
+                    true,
+                    "AND's imm5 field",
+                    Some("Consider loading it into a register with .SET instead"),
+                )
+            }
             Some(_) => unreachable!(),
             None => source_one,
         };
@@ -46,7 +57,7 @@ impl Assemble for And {
 
         vec![listing!(
             instruction,
-            *program_counter - 1,
+            program_counter.wrapping_sub(1),
             self.line,
             symbol,
             "AND",
@@ -66,20 +77,20 @@ impl Requirements for And {
         1
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Register);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Register);
 
         maybe_expect!(self, tokens, Register);
 
         // We want to allow AND R1, R2[, #2] but not AND R2, #2
         if self.operands.len() == 2 {
             // This will mean the above maybe_expect! succeeded, and so we can accept an immediate value here
-            maybe_expect!(self, tokens, Immediate, Register);
+            maybe_expect!(self, tokens, Immediate, Expression, Register);
         } else {
             maybe_expect!(self, tokens, Register);
         }
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
@@ -1,12 +1,15 @@
 use std::collections::VecDeque;
 
 use crate::{
+    linker::{Relocation, RelocationKind},
     listing,
-    notifier::{self, DiagType, Diagnostic, Highlight},
+    notifier::{DiagType, Diagnostic, Highlight},
+    session::Session,
     token::{
         tokens::{
-            expected, too_few_operands,
+            count_out_of_range, expected, too_few_operands,
             traits::{Assemble, Requirements},
+            unlinked_operand,
         },
         Token,
     },
@@ -16,7 +19,24 @@ use crate::{
 token!(Blkw);
 
 impl Assemble for Blkw {
-    fn assembled(self, program_counter: &mut i16, symbols: &SymbolTable, symbol: &str) -> Listings {
+    fn assembled(
+        self,
+        session: &mut Session,
+        program_counter: &mut i16,
+        symbols: &SymbolTable,
+        symbol: &str,
+    ) -> Listings {
+        // A count that came from an expression is only known now
+        if count_out_of_range(session, ".BLKW", &self.operands[0], 1, i16::MAX) {
+            return Vec::new();
+        }
+
+        let count = if let Token::Immediate(immediate) = self.operands.first().unwrap() {
+            immediate.value as usize
+        } else {
+            unreachable!()
+        };
+
         let value = if self.operands.len() == 1 {
             0
         } else {
@@ -25,8 +45,18 @@ impl Assemble for Blkw {
                 Token::Label(label) => {
                     if let Some(symbol) = symbols.get(label.token()) {
                         symbol.address()
+                    } else if session.linkage().is_external(label.token()) {
+                        // Every word in the block needs the address once it's linked
+                        (0..count).for_each(|word| {
+                            session.linkage_mut().relocate(Relocation::new(
+                                program_counter.wrapping_add(word as i16) as u16,
+                                label.token().to_string(),
+                                RelocationKind::Word,
+                            ));
+                        });
+                        0
                     } else {
-                        undefined!(label);
+                        undefined!(session, label, symbols);
                         0
                     }
                 }
@@ -45,14 +75,8 @@ impl Assemble for Blkw {
             val
         )];
 
-        let count = if let Token::Immediate(immediate) = self.operands.first().unwrap() {
-            immediate.value as usize
-        } else {
-            unreachable!()
-        };
-
         (1..count).for_each(|_| {
-            *program_counter += 1;
+            *program_counter = program_counter.wrapping_add(1);
             assembled.push(listing!(
                 value,
                 *program_counter,
@@ -63,7 +87,7 @@ impl Assemble for Blkw {
             ));
         });
 
-        *program_counter += 1;
+        *program_counter = program_counter.wrapping_add(1);
 
         assembled
     }
@@ -75,18 +99,25 @@ impl Requirements for Blkw {
     }
 
     fn memory_requirement(&self) -> u16 {
-        match self.operands.first().unwrap() {
-            Token::Immediate(imm) => imm.value as u16,
-            _ => unreachable!(),
+        // Anything else has already been complained about
+        match self.operands.first() {
+            Some(Token::Immediate(imm)) if imm.value > 0 => imm.value as u16,
+            _ => 0,
         }
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Immediate);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        if unlinked_operand(session, ".BLKW", &mut tokens) {
+            return tokens;
+        }
+
+        expect!(self, session, tokens, Immediate, Expression);
+
+        count_out_of_range(session, ".BLKW", &self.operands[0], 1, i16::MAX);
 
-        maybe_expect!(self, tokens, Immediate, Character, Label);
+        maybe_expect!(self, tokens, Immediate, Expression, Character, Label);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
@@ -2,10 +2,10 @@ use std::collections::VecDeque;
 
 use crate::{
     listing,
-    notifier::{self, DiagType, Diagnostic, Highlight},
+    session::Session,
     token::{
         tokens::{
-            expected, too_few_operands,
+            expected, pc_offset, too_few_operands,
             traits::{Assemble, Requirements},
         },
         Token,
@@ -34,28 +34,29 @@ impl Br {
 }
 
 impl Assemble for Br {
-    fn assembled(self, program_counter: &mut i16, symbols: &SymbolTable, symbol: &str) -> Listings {
-        *program_counter += 1;
+    fn assembled(
+        self,
+        session: &mut Session,
+        program_counter: &mut i16,
+        symbols: &SymbolTable,
+        symbol: &str,
+    ) -> Listings {
+        *program_counter = program_counter.wrapping_add(1);
 
-        let value = match self.operands.first().unwrap() {
-            Token::Immediate(imm) => imm.value,
-            Token::Label(label) => {
-                if let Some(symbol) = symbols.get(label.token()) {
-                    symbol.address() as i16 - *program_counter
-                } else {
-                    undefined!(label);
-                    0
-                }
-            }
-            _ => unreachable!(),
-        } as u16;
+        let value = pc_offset(
+            session,
+            self.operands.first().unwrap(),
+            *program_counter,
+            symbols,
+            9,
+        );
 
         let instruction =
-            (self.n as u16) << 11 | (self.z as u16) << 10 | (self.p as u16) << 9 | value & 0x1FF;
+            (self.n as u16) << 11 | (self.z as u16) << 10 | (self.p as u16) << 9 | value;
 
         vec![listing!(
             instruction,
-            *program_counter - 1,
+            program_counter.wrapping_sub(1),
             self.line,
             symbol,
             format!(
@@ -78,10 +79,10 @@ impl Requirements for Br {
         1
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Label, Immediate);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Label, Immediate, Expression);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
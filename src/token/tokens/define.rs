@@ -0,0 +1,52 @@
+use std::collections::VecDeque;
+
+use crate::{
+    session::Session,
+    token::{
+        tokens::{expected, too_few_operands, traits::Requirements},
+        Token,
+    },
+};
+
+token!(Define);
+
+impl Define {
+    /// The name of the constant being defined
+    #[must_use]
+    pub fn name(&self) -> Option<&Token> {
+        self.operands
+            .first()
+            .filter(|name| matches!(name, Token::Label(_)))
+    }
+
+    /// The value given to the constant, once any expression has been worked out
+    #[must_use]
+    pub fn value(&self) -> Option<i16> {
+        match self.operands.get(1) {
+            Some(Token::Immediate(immediate)) => Some(immediate.value),
+            Some(Token::Character(character)) => {
+                character.token().chars().next().map(|ch| ch as i16)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Requirements for Define {
+    fn min_operands(&self) -> u64 {
+        2
+    }
+
+    fn memory_requirement(&self) -> u16 {
+        0
+    }
+
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Label);
+        expect!(self, session, tokens, Immediate, Expression, Character);
+
+        operands_check!(self, session);
+
+        tokens
+    }
+}
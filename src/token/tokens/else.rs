@@ -0,0 +1,9 @@
+use crate::token::{tokens::traits::Requirements, Token};
+
+token!(Else);
+
+impl Requirements for Else {
+    fn memory_requirement(&self) -> u16 {
+        0
+    }
+}
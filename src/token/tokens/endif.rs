@@ -0,0 +1,9 @@
+use crate::token::{tokens::traits::Requirements, Token};
+
+token!(Endif);
+
+impl Requirements for Endif {
+    fn memory_requirement(&self) -> u16 {
+        0
+    }
+}
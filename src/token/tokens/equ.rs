@@ -0,0 +1,43 @@
+use std::collections::VecDeque;
+
+use crate::{
+    session::Session,
+    token::{
+        tokens::{expected, too_few_operands, traits::Requirements},
+        Token,
+    },
+};
+
+token!(Equ);
+
+impl Equ {
+    /// The value given to the constant, once any expression has been worked out
+    #[must_use]
+    pub fn value(&self) -> Option<i16> {
+        match self.operands.first() {
+            Some(Token::Immediate(immediate)) => Some(immediate.value),
+            Some(Token::Character(character)) => {
+                character.token().chars().next().map(|ch| ch as i16)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Requirements for Equ {
+    fn min_operands(&self) -> u64 {
+        1
+    }
+
+    fn memory_requirement(&self) -> u16 {
+        0
+    }
+
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Immediate, Expression, Character);
+
+        operands_check!(self, session);
+
+        tokens
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{
+    err,
+    notifier::{DiagType, Diagnostic, Highlight},
+    session::Session,
+    token::{
+        tokens::{immediate::Immediate, unlinked, Token},
+        Symbol,
+    },
+    types::SymbolTable,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    ShiftLeft,
+    ShiftRight,
+    And,
+    Or,
+    Xor,
+    Negate,
+    Not,
+}
+
+impl Operator {
+    /// How tightly the operator binds, as in C (higher binds tighter)
+    #[must_use]
+    pub fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::Xor => 2,
+            Self::And => 3,
+            Self::ShiftLeft | Self::ShiftRight => 4,
+            Self::Add | Self::Subtract => 5,
+            Self::Multiply | Self::Divide => 6,
+            Self::Negate | Self::Not => 7,
+        }
+    }
+}
+
+/// Why an expression couldn't be evaluated
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    Undefined(String),
+    DivisionByZero,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Node {
+    Value(i32),
+    Symbol(String),
+    Unary(Operator, Box<Node>),
+    Binary(Operator, Box<Node>, Box<Node>),
+}
+
+impl Node {
+    /// Work out the value of the expression, using `lookup` to find the value of any symbols
+    ///
+    /// # Errors
+    ///   If a symbol can't be found, or the expression divides by zero
+    pub fn evaluate(&self, lookup: &dyn Fn(&str) -> Option<i32>) -> Result<i32, Error> {
+        Ok(match self {
+            Self::Value(value) => *value,
+            Self::Symbol(symbol) => {
+                lookup(symbol).ok_or_else(|| Error::Undefined(symbol.clone()))?
+            }
+            Self::Unary(operator, operand) => {
+                let operand = operand.evaluate(lookup)?;
+                match operator {
+                    Operator::Not => !operand,
+                    _ => operand.wrapping_neg(),
+                }
+            }
+            Self::Binary(operator, left, right) => {
+                let (left, right) = (left.evaluate(lookup)?, right.evaluate(lookup)?);
+                match operator {
+                    Operator::Add => left.wrapping_add(right),
+                    Operator::Subtract => left.wrapping_sub(right),
+                    Operator::Multiply => left.wrapping_mul(right),
+                    Operator::Divide => left.checked_div(right).ok_or(Error::DivisionByZero)?,
+                    Operator::ShiftLeft => left.wrapping_shl(right as u32),
+                    Operator::ShiftRight => left.wrapping_shr(right as u32),
+                    Operator::And => left & right,
+                    Operator::Or => left | right,
+                    _ => left ^ right,
+                }
+            }
+        })
+    }
+
+    /// Whether the expression refers to any symbols (and so can't be worked out until
+    /// they're known)
+    #[must_use]
+    pub fn has_symbols(&self) -> bool {
+        self.refers_to(&|_| true)
+    }
+
+    /// Whether the expression refers to any symbol that `matches`
+    #[must_use]
+    pub fn refers_to(&self, matches: &dyn Fn(&str) -> bool) -> bool {
+        match self {
+            Self::Value(_) => false,
+            Self::Symbol(symbol) => matches(symbol),
+            Self::Unary(_, operand) => operand.refers_to(matches),
+            Self::Binary(_, left, right) => left.refers_to(matches) || right.refers_to(matches),
+        }
+    }
+
+    /// Replace the symbols in the expression, e.g. with the arguments given to a macro
+    #[must_use]
+    pub fn replace_symbols(&self, replace: &dyn Fn(&str) -> Option<Node>) -> Self {
+        match self {
+            Self::Value(_) => self.clone(),
+            Self::Symbol(symbol) => replace(symbol).unwrap_or_else(|| self.clone()),
+            Self::Unary(operator, operand) => {
+                Self::Unary(*operator, Box::new(operand.replace_symbols(replace)))
+            }
+            Self::Binary(operator, left, right) => Self::Binary(
+                *operator,
+                Box::new(left.replace_symbols(replace)),
+                Box::new(right.replace_symbols(replace)),
+            ),
+        }
+    }
+}
+
+token!(Expression, node: Node);
+
+impl Expression {
+    /// Work the expression out into an immediate, complaining if it can't be
+    fn evaluated(
+        &self,
+        session: &mut Session,
+        lookup: &dyn Fn(&str) -> Option<i32>,
+        undefined: &dyn Fn(&str) -> String,
+    ) -> Option<i32> {
+        let message = match self.node.evaluate(lookup) {
+            Ok(value) if (-0x8000..=0xFFFF).contains(&value) => return Some(value),
+            Ok(value) => format!(
+                "Expression '{}' is #{}, which can't be represented in 16 bits",
+                self.token, value
+            ),
+            Err(Error::Undefined(symbol)) => undefined(&symbol),
+            Err(Error::DivisionByZero) => format!("Expression '{}' divides by zero", self.token),
+        };
+
+        err!(
+            session,
+            "invalid-expression",
+            Highlight,
+            self.source(),
+            self.column,
+            self.line,
+            self.token.len(),
+            message
+        );
+
+        None
+    }
+
+    /// Turn the expression into an immediate holding its value
+    #[must_use]
+    pub fn into_immediate(self, value: i32) -> Immediate {
+        let mut immediate =
+            Immediate::new(self.token, self.file, self.column, self.line, value as i16);
+        immediate.set_expansion(self.expansion);
+        immediate
+    }
+}
+
+/// Replace any expressions in the operands of the token with their values.
+///
+/// An expression given to a PC-relative instruction is an address if it refers to a
+/// label (e.g. `DATA+1`), so it's turned into an offset from the instruction at
+/// `program_counter`. One made of only numbers and constants (e.g. `#K-1`) is already an
+/// offset, just as an immediate would be. The first pass only knows about the symbols
+/// defined so far, so anything else is a forward reference there.
+pub fn resolve(
+    session: &mut Session,
+    token: &mut Token,
+    symbols: &SymbolTable,
+    program_counter: i16,
+    first_pass: bool,
+) {
+    let relative = matches!(
+        token,
+        Token::Br(_)
+            | Token::Jsr(_)
+            | Token::Ld(_)
+            | Token::Ldi(_)
+            | Token::Lea(_)
+            | Token::St(_)
+            | Token::Sti(_)
+    );
+    // A constant's value comes after its name, the range of an .INCBIN after its file, and
+    // the value of a .SET or .LSHIFT after its register
+    let after_first = matches!(
+        token,
+        Token::Define(_) | Token::Incbin(_) | Token::Set(_) | Token::Lshift(_)
+    );
+    let directive = token.token();
+
+    let operands = match token.operands_mut() {
+        Some(operands) => operands,
+        None => return,
+    };
+
+    let lookup = |symbol: &str| symbols.get(symbol).map(Symbol::value);
+    let externals = session.linkage().externals().clone();
+    let undefined = |symbol: &str| {
+        if externals.iter().any(|external| external == symbol) {
+            unlinked(symbol, &directive)
+        } else if first_pass {
+            format!(
+                "{} must be defined before it's used by {}, as its value is needed in the first pass",
+                symbol, directive
+            )
+        } else {
+            format!("Undefined reference to {}", symbol)
+        }
+    };
+
+    // Only the first operand (the size or address) is needed in the first pass
+    let needed = if first_pass && !after_first {
+        1
+    } else {
+        operands.len()
+    };
+
+    operands.iter_mut().take(needed).for_each(|operand| {
+        if let Token::Expression(expression) = operand {
+            let address = relative
+                && expression.node.refers_to(&|symbol| {
+                    symbols
+                        .get(symbol)
+                        .is_some_and(|symbol| !symbol.is_constant())
+                });
+
+            let value = expression
+                .evaluated(session, &lookup, &undefined)
+                .map_or(0, |value| {
+                    if address {
+                        value - (i32::from(program_counter) + 1)
+                    } else {
+                        value
+                    }
+                });
+
+            *operand = Token::Immediate(expression.clone().into_immediate(value));
+        }
+    });
+}
@@ -0,0 +1,29 @@
+use std::collections::VecDeque;
+
+use crate::{
+    session::Session,
+    token::{
+        tokens::{expected, too_few_operands, traits::Requirements},
+        Token,
+    },
+};
+
+token!(External);
+
+impl Requirements for External {
+    fn min_operands(&self) -> u64 {
+        1
+    }
+
+    fn memory_requirement(&self) -> u16 {
+        0
+    }
+
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Label);
+
+        operands_check!(self, session);
+
+        tokens
+    }
+}
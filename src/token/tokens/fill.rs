@@ -1,8 +1,10 @@
 use std::collections::VecDeque;
 
 use crate::{
+    linker::{Relocation, RelocationKind},
     listing,
-    notifier::{self, DiagType, Diagnostic, Highlight},
+    notifier::{DiagType, Diagnostic, Highlight},
+    session::Session,
     token::{
         tokens::{
             expected, too_few_operands,
@@ -16,8 +18,14 @@ use crate::{
 token!(Fill);
 
 impl Assemble for Fill {
-    fn assembled(self, program_counter: &mut i16, symbols: &SymbolTable, symbol: &str) -> Listings {
-        *program_counter += 1;
+    fn assembled(
+        self,
+        session: &mut Session,
+        program_counter: &mut i16,
+        symbols: &SymbolTable,
+        symbol: &str,
+    ) -> Listings {
+        *program_counter = program_counter.wrapping_add(1);
 
         let value = match self.operands.first().unwrap() {
             Token::Label(label) => {
@@ -26,8 +34,15 @@ impl Assemble for Fill {
                     .find(|(_, symbol)| symbol.symbol() == label.token())
                 {
                     symbol.1.address()
+                } else if session.linkage().is_external(label.token()) {
+                    session.linkage_mut().relocate(Relocation::new(
+                        program_counter.wrapping_sub(1) as u16,
+                        label.token().to_string(),
+                        RelocationKind::Word,
+                    ));
+                    0
                 } else {
-                    undefined!(label);
+                    undefined!(session, label, symbols);
                     0
                 }
             }
@@ -38,7 +53,7 @@ impl Assemble for Fill {
 
         vec![listing!(
             value,
-            *program_counter - 1,
+            program_counter.wrapping_sub(1),
             self.line,
             symbol,
             ".FILL",
@@ -52,10 +67,10 @@ impl Requirements for Fill {
         1
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Immediate, Character, Label);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Immediate, Expression, Character, Label);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
@@ -0,0 +1,29 @@
+use std::collections::VecDeque;
+
+use crate::{
+    session::Session,
+    token::{
+        tokens::{expected, too_few_operands, traits::Requirements},
+        Token,
+    },
+};
+
+token!(If);
+
+impl Requirements for If {
+    fn min_operands(&self) -> u64 {
+        1
+    }
+
+    fn memory_requirement(&self) -> u16 {
+        0
+    }
+
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Immediate, Expression, Label);
+
+        operands_check!(self, session);
+
+        tokens
+    }
+}
@@ -1,13 +1,23 @@
 use crate::{
     err,
-    notifier::{self, DiagType, Diagnostic, Highlight},
+    notifier::{DiagType, Diagnostic, Highlight},
+    session::Session,
     token::tokens::Token,
 };
 
 token!(Immediate, value: i16);
 
-fn too_large(file: String, column: u64, line: u64, length: usize, value: &str) {
+fn too_large(
+    session: &mut Session,
+    file: String,
+    column: u64,
+    line: u64,
+    length: usize,
+    value: &str,
+) {
     err!(
+        session,
+        "value-out-of-range",
         Highlight,
         file,
         column,
@@ -22,14 +32,20 @@ fn too_large(file: String, column: u64, line: u64, length: usize, value: &str) {
 
 impl Immediate {
     #[must_use]
-    pub fn from_decimal(token: String, file: String, column: u64, line: u64) -> Self {
+    pub fn from_decimal(
+        session: &mut Session,
+        token: String,
+        file: String,
+        column: u64,
+        line: u64,
+    ) -> Self {
         let value = token
             .chars()
             .skip(token.chars().position(|c| c == '#').map_or(0, |p| p + 1))
             .collect::<String>()
             .parse::<i16>()
             .unwrap_or_else(|_| {
-                too_large(file.clone(), column, line, token.len(), &token);
+                too_large(session, file.clone(), column, line, token.len(), &token);
                 0
             });
 
@@ -37,7 +53,13 @@ impl Immediate {
     }
 
     #[must_use]
-    pub fn from_hexadecimal(token: String, file: String, column: u64, line: u64) -> Self {
+    pub fn from_hexadecimal(
+        session: &mut Session,
+        token: String,
+        file: String,
+        column: u64,
+        line: u64,
+    ) -> Self {
         let value = u16::from_str_radix(
             token
                 .chars()
@@ -52,7 +74,7 @@ impl Immediate {
             16,
         )
         .unwrap_or_else(|_| {
-            too_large(file.clone(), column, line, token.len(), &token);
+            too_large(session, file.clone(), column, line, token.len(), &token);
             0
         }) as i16;
 
@@ -60,7 +82,13 @@ impl Immediate {
     }
 
     #[must_use]
-    pub fn from_binary(token: String, file: String, column: u64, line: u64) -> Self {
+    pub fn from_binary(
+        session: &mut Session,
+        token: String,
+        file: String,
+        column: u64,
+        line: u64,
+    ) -> Self {
         let value = u16::from_str_radix(
             token
                 .chars()
@@ -75,7 +103,7 @@ impl Immediate {
             2,
         )
         .unwrap_or_else(|_| {
-            too_large(file.clone(), column, line, token.len(), &token);
+            too_large(session, file.clone(), column, line, token.len(), &token);
             0
         }) as i16;
 
@@ -1,6 +1,7 @@
 use crate::token::tokens::Token;
 use crate::{
     listing,
+    session::Session,
     token::tokens::traits::{Assemble, Requirements},
     types::{Listings, SymbolTable},
 };
@@ -10,15 +11,16 @@ token!(In);
 impl Assemble for In {
     fn assembled(
         self,
+        _session: &mut Session,
         program_counter: &mut i16,
         _symbols: &SymbolTable,
         symbol: &str,
     ) -> Listings {
-        *program_counter += 1;
+        *program_counter = program_counter.wrapping_add(1);
 
         vec![listing!(
             0xF023,
-            *program_counter - 1,
+            program_counter.wrapping_sub(1),
             self.line,
             symbol,
             "IN"
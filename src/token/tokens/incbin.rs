@@ -0,0 +1,205 @@
+use std::{collections::VecDeque, fs, path::Path};
+
+use crate::{
+    err,
+    lint::Lint,
+    listing,
+    notifier::{DiagType, Diagnostic, Highlight},
+    session::Session,
+    token::{
+        tokens::{
+            expected, too_few_operands,
+            traits::{Assemble, Requirements},
+        },
+        Token,
+    },
+    types::{Listings, SymbolTable},
+    warn,
+};
+
+token!(Incbin, words: Vec<u16>);
+
+impl Incbin {
+    /// Whether each byte of the file is a word of its own (`BYTES`), rather than each
+    /// pair of bytes being a big-endian word (`WORDS`, the default)
+    fn is_bytes(&self) -> bool {
+        matches!(self.operands.last(), Some(Token::Label(mode)) if mode.token().eq_ignore_ascii_case("BYTES"))
+    }
+
+    /// The offset (in bytes) into the file and the number of words to include, if given
+    fn range(&self) -> (Option<i16>, Option<i16>) {
+        let mut numbers = self.operands.iter().skip(1).filter_map(|operand| {
+            if let Token::Immediate(immediate) = operand {
+                Some(immediate.value)
+            } else {
+                None
+            }
+        });
+
+        (numbers.next(), numbers.next())
+    }
+
+    /// Read the words to include from the file found at `path`, complaining if the
+    /// offset or count don't fit the file
+    pub(crate) fn load(&mut self, session: &mut Session, path: &Path) {
+        let complain = |session: &mut Session, message: String| {
+            err!(
+                session,
+                "invalid-incbin",
+                Highlight,
+                self.source(),
+                self.column,
+                self.line,
+                self.token.len(),
+                message
+            );
+        };
+
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                complain(
+                    session,
+                    format!("Can't read '{}' to include it: {}", path.display(), error),
+                );
+                return;
+            }
+        };
+
+        let (offset, count) = self.range();
+
+        if offset.unwrap_or(0) < 0 || count.unwrap_or(0) < 0 {
+            complain(
+                session,
+                String::from(".INCBIN's offset and count can't be negative"),
+            );
+            return;
+        }
+
+        let offset = offset.unwrap_or(0) as usize;
+        let bytes = match bytes.get(offset..) {
+            Some(bytes) => bytes,
+            None => {
+                complain(
+                    session,
+                    format!(
+                        "Offset #{} is past the end of '{}', which is only {} bytes long",
+                        offset,
+                        path.display(),
+                        bytes.len()
+                    ),
+                );
+                return;
+            }
+        };
+
+        // The count is in words unless the file is included a byte at a time, with the
+        // last word of a file that's an odd number of bytes long counting as one
+        let size = if self.is_bytes() { 1 } else { 2 };
+        let available = bytes.len().div_ceil(size);
+        let count = count.map_or(available, |count| count as usize);
+
+        if count > available {
+            complain(
+                session,
+                format!(
+                    "Can't include {} words from '{}', as it only has {} after offset #{}",
+                    count,
+                    path.display(),
+                    available,
+                    offset
+                ),
+            );
+            return;
+        }
+
+        let bytes = &bytes[..(count * size).min(bytes.len())];
+
+        self.words = if self.is_bytes() {
+            bytes.iter().map(|byte| u16::from(*byte)).collect()
+        } else {
+            // Only a range running to the end of the file can stop half way through a word
+            if bytes.len() % 2 != 0 {
+                warn!(
+                    session,
+                    Lint::IncbinPadding,
+                    Highlight,
+                    self.source(),
+                    self.column,
+                    self.line,
+                    self.token.len(),
+                    format!(
+                        "'{}' ends half way through a word, so its last byte is padded with zero",
+                        path.display()
+                    )
+                );
+            }
+
+            bytes
+                .chunks(2)
+                .map(|word| u16::from(word[0]) << 8 | u16::from(*word.get(1).unwrap_or(&0)))
+                .collect()
+        };
+    }
+}
+
+impl Assemble for Incbin {
+    fn assembled(
+        self,
+        _session: &mut Session,
+        program_counter: &mut i16,
+        _symbols: &SymbolTable,
+        symbol: &str,
+    ) -> Listings {
+        self.words
+            .iter()
+            .enumerate()
+            .map(|(index, word)| {
+                *program_counter = program_counter.wrapping_add(1);
+
+                listing!(
+                    *word,
+                    program_counter.wrapping_sub(1),
+                    self.line,
+                    if index == 0 { symbol } else { "" },
+                    ".FILL",
+                    format!("#{}", *word as i16)
+                )
+            })
+            .collect()
+    }
+}
+
+impl Requirements for Incbin {
+    fn min_operands(&self) -> u64 {
+        1
+    }
+
+    fn memory_requirement(&self) -> u16 {
+        self.words.len() as u16
+    }
+
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, String);
+
+        maybe_expect!(self, tokens, Immediate, Expression);
+
+        if self.operands.len() == 2 {
+            maybe_expect!(self, tokens, Immediate, Expression);
+        }
+
+        // The mode is optional, so make sure not to take the label of the next line
+        if let Some(Token::Label(mode)) = tokens.front() {
+            if mode.line() == self.line
+                && (mode.token().eq_ignore_ascii_case("BYTES")
+                    || mode.token().eq_ignore_ascii_case("WORDS"))
+            {
+                self.operands.push(tokens.pop_front().unwrap());
+            }
+        }
+
+        operands_check!(self, session);
+
+        tokens
+    }
+}
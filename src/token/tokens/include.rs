@@ -1,8 +1,11 @@
 use std::collections::VecDeque;
 
-use crate::token::{
-    tokens::{expected, too_few_operands, traits::Requirements},
-    Token,
+use crate::{
+    session::Session,
+    token::{
+        tokens::{expected, too_few_operands, traits::Requirements},
+        Token,
+    },
 };
 
 token!(Include);
@@ -12,10 +15,10 @@ impl Requirements for Include {
         0
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, String);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, String);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
@@ -2,10 +2,10 @@ use std::collections::VecDeque;
 
 use crate::{
     listing,
-    notifier::{self, DiagType, Diagnostic, Highlight},
+    session::Session,
     token::{
         tokens::{
-            expected, too_few_operands,
+            expected, pc_offset, too_few_operands,
             traits::{Assemble, Requirements},
         },
         Token,
@@ -16,27 +16,28 @@ use crate::{
 token!(Jsr);
 
 impl Assemble for Jsr {
-    fn assembled(self, program_counter: &mut i16, symbols: &SymbolTable, symbol: &str) -> Listings {
-        *program_counter += 1;
+    fn assembled(
+        self,
+        session: &mut Session,
+        program_counter: &mut i16,
+        symbols: &SymbolTable,
+        symbol: &str,
+    ) -> Listings {
+        *program_counter = program_counter.wrapping_add(1);
 
-        let value = match self.operands.first().unwrap() {
-            Token::Immediate(imm) => imm.value,
-            Token::Label(label) => {
-                if let Some(symbol) = symbols.get(label.token()) {
-                    symbol.address() as i16 - *program_counter
-                } else {
-                    undefined!(label);
-                    0
-                }
-            }
-            _ => unreachable!(),
-        } as u16;
+        let value = pc_offset(
+            session,
+            self.operands.first().unwrap(),
+            *program_counter,
+            symbols,
+            11,
+        );
 
-        let instruction = 0x4800 | value & 0x7FF;
+        let instruction = 0x4800 | value;
 
         vec![listing!(
             instruction,
-            *program_counter - 1,
+            program_counter.wrapping_sub(1),
             self.line,
             symbol,
             "JSR",
@@ -54,10 +55,10 @@ impl Requirements for Jsr {
         1
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Label, Immediate);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Label, Immediate, Expression);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
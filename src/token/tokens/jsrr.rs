@@ -2,6 +2,7 @@ use std::collections::VecDeque;
 
 use crate::{
     listing,
+    session::Session,
     token::{
         tokens::{
             expected, too_few_operands,
@@ -17,11 +18,12 @@ token!(Jsrr);
 impl Assemble for Jsrr {
     fn assembled(
         self,
+        _session: &mut Session,
         program_counter: &mut i16,
         _symbols: &SymbolTable,
         symbol: &str,
     ) -> Listings {
-        *program_counter += 1;
+        *program_counter = program_counter.wrapping_add(1);
 
         let register = if let Token::Register(register) = self.operands.first().unwrap() {
             register.register
@@ -33,7 +35,7 @@ impl Assemble for Jsrr {
 
         vec![listing!(
             instruction,
-            *program_counter - 1,
+            program_counter.wrapping_sub(1),
             instruction,
             self.line,
             symbol,
@@ -48,10 +50,10 @@ impl Requirements for Jsrr {
         1
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Register);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Register);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
@@ -1,3 +1,28 @@
 use crate::token::tokens::Token;
 
 token!(Label);
+
+impl Label {
+    /// Whether the label belongs to the global label before it, e.g. `.LOOP`
+    #[must_use]
+    pub fn is_local(&self) -> bool {
+        self.token.starts_with('.')
+    }
+
+    /// Whether the label can have local labels of its own, which excludes the labels
+    /// made for macro expansions (as they're already qualified)
+    #[must_use]
+    pub fn is_global(&self) -> bool {
+        !self.token.contains('.')
+    }
+
+    /// Prefix a local label with the global label it belongs to, e.g. `MAIN.LOOP`
+    pub(crate) fn qualify(&mut self, scope: &str) {
+        self.token = format!("{}{}", scope, self.token);
+    }
+
+    /// Give the label another name, while still pointing at what was written
+    pub(crate) fn rename(&mut self, token: String) {
+        self.token = token;
+    }
+}
@@ -2,10 +2,10 @@ use std::collections::VecDeque;
 
 use crate::{
     listing,
-    notifier::{self, DiagType, Diagnostic, Highlight},
+    session::Session,
     token::{
         tokens::{
-            expected, too_few_operands,
+            expected, pc_offset, too_few_operands,
             traits::{Assemble, Requirements},
         },
         Token,
@@ -16,8 +16,14 @@ use crate::{
 token!(Ldi);
 
 impl Assemble for Ldi {
-    fn assembled(self, program_counter: &mut i16, symbols: &SymbolTable, symbol: &str) -> Listings {
-        *program_counter += 1;
+    fn assembled(
+        self,
+        session: &mut Session,
+        program_counter: &mut i16,
+        symbols: &SymbolTable,
+        symbol: &str,
+    ) -> Listings {
+        *program_counter = program_counter.wrapping_add(1);
 
         let destination_register = if let Token::Register(register) = self.operands.first().unwrap()
         {
@@ -26,24 +32,19 @@ impl Assemble for Ldi {
             unreachable!()
         };
 
-        let offset = match self.operands.last().unwrap() {
-            Token::Immediate(imm) => imm.value,
-            Token::Label(label) => {
-                if let Some(symbol) = symbols.get(label.token()) {
-                    symbol.address() as i16 - *program_counter
-                } else {
-                    undefined!(label);
-                    0
-                }
-            }
-            _ => unreachable!(),
-        } as u16;
+        let offset = pc_offset(
+            session,
+            self.operands.last().unwrap(),
+            *program_counter,
+            symbols,
+            9,
+        );
 
-        let instruction = 0xA000 | destination_register << 9 | offset & 0x1FF;
+        let instruction = 0xA000 | destination_register << 9 | offset;
 
         vec![listing!(
             instruction,
-            *program_counter - 1,
+            program_counter.wrapping_sub(1),
             self.line,
             symbol,
             "LDI",
@@ -62,12 +63,12 @@ impl Requirements for Ldi {
         2
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Register);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Register);
 
-        expect!(self, tokens, Label, Immediate);
+        expect!(self, session, tokens, Label, Immediate, Expression);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
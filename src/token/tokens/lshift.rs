@@ -2,9 +2,10 @@ use std::{collections::VecDeque, iter};
 
 use crate::{
     listing,
+    session::Session,
     token::{
         tokens::{
-            expected, too_few_operands,
+            count_out_of_range, expected, too_few_operands,
             traits::{Assemble, Requirements},
         },
         Token,
@@ -14,9 +15,13 @@ use crate::{
 
 token!(Lshift);
 
+/// Shifting any further would only ever leave zero behind
+const MAX_SHIFT: i16 = 15;
+
 impl Assemble for Lshift {
     fn assembled(
         mut self,
+        session: &mut Session,
         program_counter: &mut i16,
         _symbols: &SymbolTable,
         symbol: &str,
@@ -27,12 +32,22 @@ impl Assemble for Lshift {
             unreachable!()
         };
 
+        // A count that came from an expression is only known now
+        if count_out_of_range(session, ".LSHIFT", &self.operands[0], 0, MAX_SHIFT) {
+            return Vec::new();
+        }
+
         let count = if let Token::Immediate(immediate) = self.operands.remove(0) {
             immediate.value as u16
         } else {
             unreachable!()
         };
 
+        // Shifting by nothing takes nothing
+        if count == 0 {
+            return Vec::new();
+        }
+
         let instruction = 0x1000 | register << 9 | register << 6 | register;
         let reg = format!("R{}", register);
 
@@ -50,12 +65,12 @@ impl Assemble for Lshift {
         iter::repeat(instruction)
             .take(count as usize - 1)
             .map(|val| {
-                *program_counter += 1;
+                *program_counter = program_counter.wrapping_add(1);
                 listing!(val, *program_counter, self.line, "", "ADD", reg, reg, reg)
             })
             .for_each(|line| assembled.push(line));
 
-        *program_counter += 1;
+        *program_counter = program_counter.wrapping_add(1);
 
         assembled
     }
@@ -67,19 +82,23 @@ impl Requirements for Lshift {
     }
 
     fn memory_requirement(&self) -> u16 {
-        if let Token::Immediate(immediate) = self.operands.last().unwrap() {
-            immediate.value as u16
-        } else {
-            unreachable!()
+        // Anything else has already been complained about
+        match self.operands.get(1) {
+            Some(Token::Immediate(immediate)) if (0..=MAX_SHIFT).contains(&immediate.value) => {
+                immediate.value as u16
+            }
+            _ => 0,
         }
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Register);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Register);
+
+        expect!(self, session, tokens, Immediate, Expression);
 
-        expect!(self, tokens, Immediate);
+        count_out_of_range(session, ".LSHIFT", &self.operands[1], 0, MAX_SHIFT);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
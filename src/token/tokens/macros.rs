@@ -3,9 +3,11 @@ macro_rules! token {
         #[derive(Debug, PartialEq, Clone)]
         pub struct $name {
             token: String,
+            width: usize,
             column: u64,
             line: u64,
             file: String,
+            expansion: Option<usize>,
             $( pub $field : $type, )*
             operands: Vec<Token>,
         }
@@ -14,10 +16,12 @@ macro_rules! token {
             #[must_use]
             pub fn new(token: String, file: String, column: u64, line: u64, $( $field: $type, )* ) -> Self {
                 Self {
+                    width: token.chars().count(),
                     token,
                     column,
                     line,
                     file,
+                    expansion: None,
                     $( $field, )*
                     operands: Vec::new(),
                 }
@@ -28,6 +32,13 @@ macro_rules! token {
                 &self.token
             }
 
+            /// How many characters the token was written with, which stays the same if
+            /// it's renamed (e.g. a local label being qualified)
+            #[must_use]
+            pub fn width(&self) -> usize {
+                self.width
+            }
+
             #[must_use]
             pub fn column(&self) -> u64 {
                 self.column
@@ -43,10 +54,24 @@ macro_rules! token {
                 &self.file
             }
 
+            /// The file the token is in, along with the macro expansion it came out of
+            #[must_use]
+            pub fn source(&self) -> crate::notifier::Source {
+                crate::notifier::Source::new(self.file.clone(), self.expansion)
+            }
+
+            pub(crate) fn set_expansion(&mut self, expansion: Option<usize>) {
+                self.expansion = expansion;
+            }
+
             #[must_use]
             pub fn operands(&self) -> &Vec<Token> {
                 &self.operands
             }
+
+            pub fn operands_mut(&mut self) -> &mut Vec<Token> {
+                &mut self.operands
+            }
         }
     };
 
@@ -56,22 +81,24 @@ macro_rules! token {
 }
 
 macro_rules! expect {
-    ( $self:expr, $tokens:expr, $( $token:ident ),* ) => {
+    ( $self:expr, $session:expr, $tokens:expr, $( $token:ident ),* ) => {
         match $tokens.front() {
             $( Some(Token::$token(_)) => { $self.operands.push($tokens.pop_front().unwrap()); } )+
             Some(tok) => {
                 expected(
-                    $self.file(),
+                    $session,
+                    $self.source(),
                     &[
                         $( stringify!($token), )+
                     ], Some(tok),
-                    (tok.column(), tok.line(), tok.token().len())
+                    (tok.column(), tok.line(), tok.width())
                 );
                 return $tokens;
             }
             None => {
                 expected(
-                    $self.file(),
+                    $session,
+                    $self.source(),
                     &[
                         $( stringify!($token), )+
                     ], None,
@@ -93,13 +120,14 @@ macro_rules! maybe_expect {
 }
 
 macro_rules! operands_check {
-    ( $self:expr ) => {
+    ( $self:expr, $session:expr ) => {
         let min = $self.min_operands();
         let received = $self.operands.len() as u64;
 
         if received < min {
             too_few_operands(
-                $self.file(),
+                $session,
+                $self.source(),
                 min,
                 received,
                 $self.token(),
@@ -110,14 +138,27 @@ macro_rules! operands_check {
 }
 
 macro_rules! undefined {
-    ( $label:expr ) => {
+    ( $session:expr, $label:expr, $symbols:expr ) => {
         crate::err!(
+            $session,
+            "undefined-label",
             Highlight,
-            $label.file().to_string(),
+            $label.source(),
             $label.column(),
             $label.line(),
-            $label.token().len(),
+            $label.width(),
             String::from("Undefined reference to label")
         );
+
+        if let Some(suggestion) = crate::suggest::label($label.token(), $symbols) {
+            $session.add_diagnostic(Diagnostic::Highlight(Highlight::new(
+                DiagType::Note,
+                $label.source(),
+                $label.column(),
+                $label.line(),
+                $label.width(),
+                format!("Did you mean '{}'?", suggestion),
+            )));
+        }
     };
 }
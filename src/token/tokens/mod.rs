@@ -1,18 +1,31 @@
+use std::collections::VecDeque;
+
 use crate::{
     err,
-    notifier::{self, DiagType, Diagnostic, Highlight},
+    linker::{Relocation, RelocationKind},
+    notifier::{DiagType, Diagnostic, Highlight, Note, Source},
+    session::Session,
     token::r#type::Token,
+    types::SymbolTable,
 };
 
 #[macro_use]
 pub mod macros;
 pub mod traits;
 
-pub fn expected(file: &str, expect: &[&str], found: Option<&Token>, at: (u64, u64, usize)) {
+pub fn expected(
+    session: &mut Session,
+    source: Source,
+    expect: &[&str],
+    found: Option<&Token>,
+    at: (u64, u64, usize),
+) {
     if let Some(found) = found {
         err!(
+            session,
+            "unexpected-operand",
             Highlight,
-            (*file).to_string(),
+            source,
             at.0,
             at.1,
             at.2,
@@ -24,8 +37,10 @@ pub fn expected(file: &str, expect: &[&str], found: Option<&Token>, at: (u64, u6
         )
     } else {
         err!(
+            session,
+            "missing-operand",
             Highlight,
-            (*file).to_string(),
+            source,
             at.0,
             at.1,
             at.2,
@@ -37,10 +52,19 @@ pub fn expected(file: &str, expect: &[&str], found: Option<&Token>, at: (u64, u6
     };
 }
 
-pub fn too_few_operands(file: &str, required: u64, found: u64, token: &str, at: (u64, u64, usize)) {
+pub fn too_few_operands(
+    session: &mut Session,
+    source: Source,
+    required: u64,
+    found: u64,
+    token: &str,
+    at: (u64, u64, usize),
+) {
     err!(
+        session,
+        "operand-count",
         Highlight,
-        (*file).to_string(),
+        source,
         at.0,
         at.1,
         at.2,
@@ -56,6 +80,185 @@ pub fn too_few_operands(file: &str, required: u64, found: u64, token: &str, at:
     );
 }
 
+/// Complain about a count (e.g. of words to reserve) outside of what the directive can
+/// do, returning whether it was. A count that's still an expression can't be checked yet.
+pub fn count_out_of_range(
+    session: &mut Session,
+    directive: &str,
+    count: &Token,
+    minimum: i16,
+    maximum: i16,
+) -> bool {
+    match count {
+        Token::Immediate(immediate) if immediate.value < minimum || immediate.value > maximum => {
+            err!(
+                session,
+                "count-out-of-range",
+                Highlight,
+                count.source(),
+                count.column(),
+                count.line(),
+                count.width(),
+                format!(
+                    "{} needs a count from #{} to #{}, but was given #{}",
+                    directive, minimum, maximum, immediate.value
+                )
+            );
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Why an external symbol can't be used where its value is needed before linking
+#[must_use]
+pub fn unlinked(symbol: &str, directive: &str) -> String {
+    format!(
+        "External symbol {} can't be used by {}, as its address isn't known until it's linked",
+        symbol, directive
+    )
+}
+
+/// Complain about (and skip) an external symbol coming up as an operand whose value
+/// decides the directive's size, returning whether there was one.
+pub fn unlinked_operand(
+    session: &mut Session,
+    directive: &str,
+    tokens: &mut VecDeque<Token>,
+) -> bool {
+    match tokens.front() {
+        Some(Token::Label(label)) if session.linkage().is_external(label.token()) => {
+            err!(
+                session,
+                "unlinked-external",
+                Highlight,
+                label.source(),
+                label.column(),
+                label.line(),
+                label.token().len(),
+                unlinked(label.token(), directive)
+            );
+            tokens.pop_front();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Resolve the operand of a PC-relative instruction into an offset that fits in a
+/// `bits` wide PCoffset field, complaining if the label (or immediate) is out of reach.
+pub fn pc_offset(
+    session: &mut Session,
+    operand: &Token,
+    program_counter: i16,
+    symbols: &SymbolTable,
+    bits: u32,
+) -> u16 {
+    let offset = match operand {
+        Token::Immediate(imm) => imm.value,
+        Token::Label(label) => {
+            if let Some(symbol) = symbols.get(label.token()) {
+                (symbol.address() as i16).wrapping_sub(program_counter)
+            } else if session.linkage().is_external(label.token()) {
+                // The linker will fill the offset in once it knows where the label is
+                session.linkage_mut().relocate(Relocation::new(
+                    program_counter.wrapping_sub(1) as u16,
+                    label.token().to_string(),
+                    RelocationKind::Offset(bits),
+                ));
+                return 0;
+            } else {
+                undefined!(session, label, symbols);
+                return 0;
+            }
+        }
+        _ => unreachable!(),
+    };
+
+    let (minimum, maximum) = field_range(bits, true);
+
+    if offset < minimum || offset > maximum {
+        err!(
+            session,
+            "offset-out-of-range",
+            Highlight,
+            operand.source(),
+            operand.column(),
+            operand.line(),
+            operand.width(),
+            format!(
+                "{} is #{} away, but PCoffset{} can only reach #{} to #{}",
+                match operand {
+                    Token::Label(label) => format!("Label '{}'", label.token()),
+                    _ => String::from("Offset"),
+                },
+                offset,
+                bits,
+                minimum,
+                maximum
+            )
+        );
+    }
+
+    offset as u16 & ((1 << bits) - 1)
+}
+
+/// The smallest and largest values that can be held in a field `bits` wide
+fn field_range(bits: u32, signed: bool) -> (i16, i16) {
+    if signed {
+        (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+    } else {
+        (0, (1 << bits) - 1)
+    }
+}
+
+/// Truncate an immediate operand into the `bits` wide field of an instruction, complaining
+/// (and suggesting an alternative, if there is one) when it doesn't fit.
+pub fn immediate_field(
+    session: &mut Session,
+    operand: &Token,
+    bits: u32,
+    signed: bool,
+    field: &str,
+    suggestion: Option<&str>,
+) -> u16 {
+    let value = if let Token::Immediate(imm) = operand {
+        imm.value
+    } else {
+        unreachable!()
+    };
+
+    let (minimum, maximum) = field_range(bits, signed);
+
+    if value < minimum || value > maximum {
+        err!(
+            session,
+            "value-out-of-range",
+            Highlight,
+            operand.source(),
+            operand.column(),
+            operand.line(),
+            operand.width(),
+            format!(
+                "Value #{} does not fit in {}, which can only hold #{} to #{}",
+                value, field, minimum, maximum
+            )
+        );
+
+        if let Some(suggestion) = suggestion {
+            session.add_diagnostic(Diagnostic::Note(Note::new(
+                DiagType::Note,
+                operand.source(),
+                operand.column(),
+                operand.line(),
+                suggestion.to_string(),
+            )));
+        }
+    }
+
+    value as u16 & ((1 << bits) - 1)
+}
+
 // Instructions
 pub mod add;
 pub mod and;
@@ -86,6 +289,7 @@ pub mod putsp;
 
 // Types
 pub mod character;
+pub mod expression;
 pub mod immediate;
 pub mod label;
 pub mod register;
@@ -93,8 +297,18 @@ pub mod string;
 
 // Directives
 pub mod blkw;
+pub mod define;
+pub mod r#else;
 pub mod end;
+pub mod endif;
+pub mod equ;
+pub mod external;
 pub mod fill;
+pub mod global;
+pub mod r#if;
+pub mod ifdef;
+pub mod ifndef;
+pub mod incbin;
 pub mod include;
 pub mod lshift;
 pub mod neg;
@@ -2,6 +2,7 @@ use std::collections::VecDeque;
 
 use crate::{
     listing,
+    session::Session,
     token::{
         tokens::{
             expected, too_few_operands,
@@ -17,11 +18,12 @@ token!(Neg);
 impl Assemble for Neg {
     fn assembled(
         self,
+        _session: &mut Session,
         program_counter: &mut i16,
         _symbols: &SymbolTable,
         symbol: &str,
     ) -> Listings {
-        *program_counter += self.memory_requirement() as i16;
+        *program_counter = program_counter.wrapping_add(self.memory_requirement() as i16);
 
         let destination_register = if let Token::Register(register) = self.operands.first().unwrap()
         {
@@ -44,7 +46,7 @@ impl Assemble for Neg {
         vec![
             listing!(
                 not_instruction,
-                *program_counter - 2,
+                program_counter.wrapping_sub(2),
                 self.line,
                 symbol,
                 "NOT",
@@ -53,7 +55,7 @@ impl Assemble for Neg {
             ),
             listing!(
                 add_instruction,
-                *program_counter - 1,
+                program_counter.wrapping_sub(1),
                 self.line,
                 "",
                 "ADD",
@@ -74,12 +76,12 @@ impl Requirements for Neg {
         2
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Register);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Register);
 
         maybe_expect!(self, tokens, Register);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
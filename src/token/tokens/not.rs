@@ -2,6 +2,7 @@ use std::collections::VecDeque;
 
 use crate::{
     listing,
+    session::Session,
     token::{
         tokens::{
             expected, too_few_operands,
@@ -17,11 +18,12 @@ token!(Not);
 impl Assemble for Not {
     fn assembled(
         mut self,
+        _session: &mut Session,
         program_counter: &mut i16,
         _symbols: &SymbolTable,
         symbol: &str,
     ) -> Listings {
-        *program_counter += 1;
+        *program_counter = program_counter.wrapping_add(1);
 
         let destination_register = if let Token::Register(register) = self.operands.remove(0) {
             register.register
@@ -39,7 +41,7 @@ impl Assemble for Not {
 
         vec![listing!(
             instruction,
-            *program_counter - 1,
+            program_counter.wrapping_sub(1),
             self.line,
             symbol,
             "NOT",
@@ -54,12 +56,12 @@ impl Requirements for Not {
         1
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Register);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Register);
 
         maybe_expect!(self, tokens, Register);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
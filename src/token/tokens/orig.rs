@@ -2,6 +2,7 @@ use std::collections::VecDeque;
 
 use crate::{
     listing,
+    session::Session,
     token::{
         tokens::{
             expected, too_few_operands,
@@ -17,6 +18,7 @@ token!(Orig);
 impl Assemble for Orig {
     fn assembled(
         mut self,
+        _session: &mut Session,
         program_counter: &mut i16,
         _symbols: &SymbolTable,
         symbol: &str,
@@ -46,17 +48,17 @@ impl Requirements for Orig {
     }
 
     fn memory_requirement(&self) -> u16 {
-        if let Token::Immediate(imm) = self.operands.first().unwrap() {
-            imm.value as u16
-        } else {
-            unreachable!()
+        // Anything else has already been complained about
+        match self.operands.first() {
+            Some(Token::Immediate(imm)) => imm.value as u16,
+            _ => 0,
         }
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Immediate);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Immediate, Expression);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
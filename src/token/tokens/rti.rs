@@ -1,5 +1,6 @@
 use crate::{
     listing,
+    session::Session,
     token::{
         tokens::{traits::Assemble, Token},
         traits::Requirements,
@@ -12,15 +13,16 @@ token!(Rti);
 impl Assemble for Rti {
     fn assembled(
         self,
+        _session: &mut Session,
         program_counter: &mut i16,
         _symbols: &SymbolTable,
         symbol: &str,
     ) -> Listings {
-        *program_counter += 1;
+        *program_counter = program_counter.wrapping_add(1);
 
         vec![listing!(
             0x8000,
-            *program_counter - 1,
+            program_counter.wrapping_sub(1),
             self.line,
             symbol,
             "RTI"
@@ -2,10 +2,12 @@ use std::collections::VecDeque;
 
 use crate::{
     listing,
+    session::Session,
     token::{
         tokens::{
             expected, too_few_operands,
             traits::{Assemble, Requirements},
+            unlinked_operand,
         },
         Token,
     },
@@ -17,6 +19,7 @@ token!(Set);
 impl Assemble for Set {
     fn assembled(
         self,
+        _session: &mut Session,
         program_counter: &mut i16,
         _symbols: &SymbolTable,
         symbol: &str,
@@ -36,7 +39,7 @@ impl Assemble for Set {
         let reg = format!("R{}", register);
 
         if immediate >= -16 && immediate <= 15 {
-            *program_counter += 2;
+            *program_counter = program_counter.wrapping_add(2);
             let clear_instruction = 0x5020 | register << 9 | register << 6;
             let set_instruction =
                 0x1020 | register << 9 | register << 6 | (immediate as u16 & 0x1F);
@@ -44,7 +47,7 @@ impl Assemble for Set {
             vec![
                 listing!(
                     clear_instruction,
-                    *program_counter - 2,
+                    program_counter.wrapping_sub(2),
                     self.line,
                     symbol,
                     "AND",
@@ -54,7 +57,7 @@ impl Assemble for Set {
                 ),
                 listing!(
                     set_instruction,
-                    *program_counter - 1,
+                    program_counter.wrapping_sub(1),
                     self.line,
                     "",
                     "ADD",
@@ -64,11 +67,11 @@ impl Assemble for Set {
                 ),
             ]
         } else {
-            *program_counter += 3;
+            *program_counter = program_counter.wrapping_add(3);
             vec![
                 listing!(
                     0x0E01,
-                    *program_counter - 3,
+                    program_counter.wrapping_sub(3),
                     self.line,
                     symbol,
                     "BRnzp",
@@ -76,7 +79,7 @@ impl Assemble for Set {
                 ),
                 listing!(
                     immediate as u16,
-                    *program_counter - 2,
+                    program_counter.wrapping_sub(2),
                     self.line,
                     "",
                     ".FILL",
@@ -84,7 +87,7 @@ impl Assemble for Set {
                 ),
                 listing!(
                     0x21FE | register << 9,
-                    *program_counter - 1,
+                    program_counter.wrapping_sub(1),
                     self.line,
                     "",
                     "LD",
@@ -102,23 +105,25 @@ impl Requirements for Set {
     }
 
     fn memory_requirement(&self) -> u16 {
-        if let Token::Immediate(immediate) = self.operands.last().unwrap() {
-            if immediate.value > 15 || immediate.value < -16 {
-                3
-            } else {
-                2
-            }
-        } else {
-            unreachable!()
+        // Anything else has already been complained about
+        match self.operands.get(1) {
+            Some(Token::Immediate(immediate)) if immediate.value > 15 || immediate.value < -16 => 3,
+            Some(Token::Immediate(_)) => 2,
+            _ => 0,
         }
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Register);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Register);
+
+        // The value decides how many words are needed, so it can't wait for the linker
+        if unlinked_operand(session, ".SET", &mut tokens) {
+            return tokens;
+        }
 
-        expect!(self, tokens, Register, Immediate, Label);
+        expect!(self, session, tokens, Immediate, Expression);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
@@ -2,9 +2,10 @@ use std::collections::VecDeque;
 
 use crate::{
     listing,
+    session::Session,
     token::{
         tokens::{
-            expected, too_few_operands,
+            expected, immediate_field, too_few_operands,
             traits::{Assemble, Requirements},
         },
         Token,
@@ -17,11 +18,12 @@ token!(Str);
 impl Assemble for Str {
     fn assembled(
         mut self,
+        session: &mut Session,
         program_counter: &mut i16,
         _symbols: &SymbolTable,
         symbol: &str,
     ) -> Listings {
-        *program_counter += 1;
+        *program_counter = program_counter.wrapping_add(1);
 
         let destination_register = if let Token::Register(register) = self.operands.remove(0) {
             register.register
@@ -35,17 +37,20 @@ impl Assemble for Str {
             unreachable!()
         };
 
-        let source_two = if let Token::Immediate(immediate) = self.operands.remove(0) {
-            (immediate.value & 0x3F) as u16
-        } else {
-            unreachable!()
-        };
+        let source_two = immediate_field(
+            session,
+            self.operands.first().unwrap(),
+            6,
+            true,
+            "STR's offset6 field",
+            None,
+        );
 
         let instruction: u16 = 0x7000 | destination_register << 9 | source_one << 6 | source_two;
 
         vec![listing!(
             instruction,
-            *program_counter - 1,
+            program_counter.wrapping_sub(1),
             self.line,
             symbol,
             "STR",
@@ -61,14 +66,14 @@ impl Requirements for Str {
         3
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Register);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Register);
 
-        expect!(self, tokens, Register);
+        expect!(self, session, tokens, Register);
 
-        expect!(self, tokens, Immediate);
+        expect!(self, session, tokens, Immediate, Expression);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
@@ -1,11 +1,14 @@
 use std::string;
 
+use crate::notifier::Source;
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct String {
     token: string::String,
     column: u64,
     line: u64,
     file: string::String,
+    expansion: Option<usize>,
 }
 
 impl String {
@@ -16,6 +19,7 @@ impl String {
             column,
             line,
             file,
+            expansion: None,
         }
     }
 
@@ -24,6 +28,12 @@ impl String {
         &self.token
     }
 
+    /// How many characters the string was written with, including its quotes
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.token.chars().count() + 2
+    }
+
     #[must_use]
     pub fn column(&self) -> u64 {
         self.column
@@ -38,4 +48,14 @@ impl String {
     pub fn file(&self) -> &string::String {
         &self.file
     }
+
+    /// The file the string is in, along with the macro expansion it came out of
+    #[must_use]
+    pub fn source(&self) -> Source {
+        Source::new(self.file.clone(), self.expansion)
+    }
+
+    pub(crate) fn set_expansion(&mut self, expansion: Option<usize>) {
+        self.expansion = expansion;
+    }
 }
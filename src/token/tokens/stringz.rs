@@ -2,6 +2,7 @@ use std::collections::VecDeque;
 
 use crate::{
     listing,
+    session::Session,
     token::{
         tokens::{
             expected, too_few_operands,
@@ -17,6 +18,7 @@ token!(Stringz);
 impl Assemble for Stringz {
     fn assembled(
         self,
+        _session: &mut Session,
         program_counter: &mut i16,
         _symbols: &SymbolTable,
         symbol: &str,
@@ -35,7 +37,7 @@ impl Assemble for Stringz {
                 ));
             }
             string.token().chars().skip(1).for_each(|c| {
-                *program_counter += 1;
+                *program_counter = program_counter.wrapping_add(1);
                 assembled.push(listing!(
                     c as u16,
                     *program_counter,
@@ -45,7 +47,7 @@ impl Assemble for Stringz {
                     format!("#{}", c as u16)
                 ));
             });
-            *program_counter += 1;
+            *program_counter = program_counter.wrapping_add(1);
             assembled.push(listing!(0, *program_counter, self.line, "", ".FILL", "#0"))
         } else {
             unreachable!()
@@ -54,7 +56,7 @@ impl Assemble for Stringz {
         self.operands.iter().skip(1).for_each(|token| {
             if let Token::String(string) = token {
                 string.token().chars().for_each(|c| {
-                    *program_counter += 1;
+                    *program_counter = program_counter.wrapping_add(1);
                     assembled.push(listing!(
                         c as u16,
                         *program_counter,
@@ -70,7 +72,7 @@ impl Assemble for Stringz {
             }
         });
 
-        *program_counter += 1;
+        *program_counter = program_counter.wrapping_add(1);
 
         assembled
     }
@@ -92,15 +94,15 @@ impl Requirements for Stringz {
         })
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, String);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, String);
 
         // Get all of the strings that belong to this .STRINGZ
         while let Some(Token::String(_)) = tokens.front() {
             self.operands.push(tokens.pop_front().unwrap());
         }
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
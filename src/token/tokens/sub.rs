@@ -2,6 +2,7 @@ use std::collections::VecDeque;
 
 use crate::{
     listing,
+    session::Session,
     token::tokens::{
         expected, too_few_operands,
         traits::{Assemble, Requirements},
@@ -15,11 +16,12 @@ token!(Sub);
 impl Assemble for Sub {
     fn assembled(
         self,
+        _session: &mut Session,
         program_counter: &mut i16,
         _symbols: &SymbolTable,
         symbol: &str,
     ) -> Listings {
-        *program_counter += 1;
+        *program_counter = program_counter.wrapping_add(1);
 
         let destination_register = if let Token::Register(register) = self.operands.first().unwrap()
         {
@@ -48,7 +50,7 @@ impl Assemble for Sub {
             let instruction = 0x5000 | destination_register << 9 | source_register_one << 6 | 0x20;
             vec![listing!(
                 instruction,
-                *program_counter - 1,
+                program_counter.wrapping_sub(1),
                 self.line,
                 symbol,
                 "AND",
@@ -57,7 +59,7 @@ impl Assemble for Sub {
                 "#0"
             )]
         } else {
-            *program_counter += 2;
+            *program_counter = program_counter.wrapping_add(2);
             let not_instruction = 0x903F | source_register_two << 9 | source_register_two << 6;
             let add_instruction = 0x1021 | source_register_two << 9 | source_register_two << 6;
             let subtract_instruction =
@@ -68,7 +70,7 @@ impl Assemble for Sub {
             let mut assembled = vec![
                 listing!(
                     not_instruction,
-                    *program_counter - 3,
+                    program_counter.wrapping_sub(3),
                     self.line,
                     symbol,
                     "NOT",
@@ -77,7 +79,7 @@ impl Assemble for Sub {
                 ),
                 listing!(
                     add_instruction,
-                    *program_counter - 2,
+                    program_counter.wrapping_sub(2),
                     self.line,
                     "",
                     "ADD",
@@ -87,7 +89,7 @@ impl Assemble for Sub {
                 ),
                 listing!(
                     subtract_instruction,
-                    *program_counter - 1,
+                    program_counter.wrapping_sub(1),
                     self.line,
                     "",
                     "ADD",
@@ -98,10 +100,10 @@ impl Assemble for Sub {
             ];
 
             if destination_register != source_register_two {
-                *program_counter += 2;
+                *program_counter = program_counter.wrapping_add(2);
                 assembled.push(listing!(
                     not_instruction,
-                    *program_counter - 2,
+                    program_counter.wrapping_sub(2),
                     self.line,
                     "",
                     "NOT",
@@ -110,7 +112,7 @@ impl Assemble for Sub {
                 ));
                 assembled.push(listing!(
                     add_instruction,
-                    *program_counter - 1,
+                    program_counter.wrapping_sub(1),
                     self.line,
                     "",
                     "ADD",
@@ -131,28 +133,22 @@ impl Requirements for Sub {
     }
 
     fn memory_requirement(&self) -> u16 {
-        let destination_register = if let Token::Register(register) = self.operands.first().unwrap()
-        {
-            register.register
-        } else {
-            unreachable!()
-        };
-
-        let source_register_one = if self.operands.len() > 2 {
-            if let Token::Register(ref register) = &self.operands[1] {
-                register.register
-            } else {
-                unreachable!()
-            }
-        } else {
-            destination_register
-        };
-
-        let source_register_two = if let Token::Register(register) = self.operands.last().unwrap() {
-            register.register
-        } else {
-            unreachable!()
-        };
+        let registers = self
+            .operands
+            .iter()
+            .filter_map(|operand| match operand {
+                Token::Register(register) => Some(register.register),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // Missing registers have already been complained about
+        let (destination_register, source_register_one, source_register_two) =
+            match registers.as_slice() {
+                [destination, source] => (*destination, *destination, *source),
+                [destination, first, second] => (*destination, *first, *second),
+                _ => return 0,
+            };
 
         if source_register_one == source_register_two {
             1
@@ -163,14 +159,14 @@ impl Requirements for Sub {
         }
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Register);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Register);
 
-        expect!(self, tokens, Register);
+        expect!(self, session, tokens, Register);
 
         maybe_expect!(self, tokens, Register);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
@@ -1,12 +1,19 @@
 use std::collections::VecDeque;
 
 use crate::{
+    session::Session,
     token::Token,
     types::{Listings, SymbolTable},
 };
 
 pub trait Assemble {
-    fn assembled(self, program_counter: &mut i16, symbols: &SymbolTable, symbol: &str) -> Listings;
+    fn assembled(
+        self,
+        session: &mut Session,
+        program_counter: &mut i16,
+        symbols: &SymbolTable,
+        symbol: &str,
+    ) -> Listings;
 }
 
 pub trait Requirements {
@@ -25,7 +32,7 @@ pub trait Requirements {
 
     /// Consume from the token stream until we have all the required
     /// operands for this token
-    fn consume(&mut self, tokens: VecDeque<Token>) -> VecDeque<Token> {
+    fn consume(&mut self, _session: &mut Session, tokens: VecDeque<Token>) -> VecDeque<Token> {
         tokens
     }
 }
@@ -2,9 +2,10 @@ use std::collections::VecDeque;
 
 use crate::{
     listing,
+    session::Session,
     token::{
         tokens::{
-            expected, too_few_operands,
+            expected, immediate_field, too_few_operands,
             traits::{Assemble, Requirements},
         },
         Token,
@@ -17,21 +18,26 @@ token!(Trap);
 impl Assemble for Trap {
     fn assembled(
         self,
+        session: &mut Session,
         program_counter: &mut i16,
         _symbols: &SymbolTable,
         symbol: &str,
     ) -> Listings {
-        *program_counter += 1;
-
-        let instruction = if let Token::Immediate(immediate) = self.operands.first().unwrap() {
-            0xF000 | (immediate.value as u16 & 0xFF)
-        } else {
-            unreachable!()
-        };
+        *program_counter = program_counter.wrapping_add(1);
+
+        let instruction = 0xF000
+            | immediate_field(
+                session,
+                self.operands.first().unwrap(),
+                8,
+                false,
+                "TRAP's trapvect8 field",
+                None,
+            );
 
         vec![listing!(
             instruction,
-            *program_counter - 1,
+            program_counter.wrapping_sub(1),
             self.line,
             symbol,
             "TRAP",
@@ -45,10 +51,10 @@ impl Requirements for Trap {
         1
     }
 
-    fn consume(&mut self, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
-        expect!(self, tokens, Immediate);
+    fn consume(&mut self, session: &mut Session, mut tokens: VecDeque<Token>) -> VecDeque<Token> {
+        expect!(self, session, tokens, Immediate, Expression);
 
-        operands_check!(self);
+        operands_check!(self, session);
 
         tokens
     }
@@ -1,9 +1,12 @@
 use std::{collections::VecDeque, fmt};
 
 use crate::{
+    notifier::Source,
+    session::Session,
     token::tokens::{
-        add, and, blkw, br, character, end, expected, fill, getc, halt, immediate, include, jmp,
-        jmpt, jsr, jsrr, label, ld, ldi, ldr, lea, lshift, neg, not, orig, out, puts, putsp, r#in,
+        add, and, blkw, br, character, define, end, endif, equ, expected, expression, external,
+        fill, getc, global, halt, ifdef, ifndef, immediate, incbin, include, jmp, jmpt, jsr, jsrr,
+        label, ld, ldi, ldr, lea, lshift, neg, not, orig, out, puts, putsp, r#else, r#if, r#in,
         register, ret, rti, set, st, sti, str, string, stringz, sub,
         traits::{Assemble, Requirements},
         trap,
@@ -43,6 +46,7 @@ pub enum Token {
 
     // Types
     Immediate(immediate::Immediate),
+    Expression(expression::Expression),
     Character(character::Character),
     Label(label::Label),
     Register(register::Register),
@@ -50,12 +54,22 @@ pub enum Token {
 
     // Directives
     Blkw(blkw::Blkw),
+    Define(define::Define),
+    Else(r#else::Else),
     End(end::End),
+    Endif(endif::Endif),
+    Equ(equ::Equ),
+    External(external::External),
     Fill(fill::Fill),
+    Global(global::Global),
+    If(r#if::If),
+    Ifdef(ifdef::Ifdef),
+    Ifndef(ifndef::Ifndef),
+    Incbin(incbin::Incbin),
     Include(include::Include),
     Lshift(lshift::Lshift),
-    Orig(orig::Orig),
     Neg(neg::Neg),
+    Orig(orig::Orig),
     Set(set::Set),
     Stringz(stringz::Stringz),
     Sub(sub::Sub),
@@ -93,7 +107,75 @@ impl Token {
             Token::Puts,
             Token::Putsp,
             Token::Blkw,
+            Token::Define,
+            Token::Else,
+            Token::Endif,
+            Token::Equ,
+            Token::External,
+            Token::Fill,
+            Token::Global,
+            Token::If,
+            Token::Ifdef,
+            Token::Ifndef,
+            Token::Incbin,
+            Token::Include,
+            Token::Lshift,
+            Token::Orig,
+            Token::Neg,
+            Token::Set,
+            Token::Stringz,
+            Token::Sub,
+            Token::Immediate,
+            Token::Expression,
+            Token::Label,
+            Token::Character,
+            Token::String,
+            Token::End,
+            Token::Register
+        )
+    }
+
+    /// How many characters the token was written with
+    #[must_use]
+    pub fn width(&self) -> usize {
+        width_of!(
+            self,
+            Token::Add,
+            Token::And,
+            Token::Br,
+            Token::Jmp,
+            Token::Jmpt,
+            Token::Jsr,
+            Token::Jsrr,
+            Token::Ld,
+            Token::Ldi,
+            Token::Ldr,
+            Token::Lea,
+            Token::Not,
+            Token::Ret,
+            Token::Rti,
+            Token::St,
+            Token::Sti,
+            Token::Str,
+            Token::Trap,
+            Token::Getc,
+            Token::Halt,
+            Token::In,
+            Token::Out,
+            Token::Puts,
+            Token::Putsp,
+            Token::Blkw,
+            Token::Define,
+            Token::Else,
+            Token::Endif,
+            Token::Equ,
+            Token::External,
             Token::Fill,
+            Token::Global,
+            Token::If,
+            Token::Ifdef,
+            Token::Ifndef,
+            Token::Incbin,
             Token::Include,
             Token::Lshift,
             Token::Orig,
@@ -102,6 +184,7 @@ impl Token {
             Token::Stringz,
             Token::Sub,
             Token::Immediate,
+            Token::Expression,
             Token::Label,
             Token::Character,
             Token::String,
@@ -139,7 +222,17 @@ impl Token {
             Token::Puts,
             Token::Putsp,
             Token::Blkw,
+            Token::Define,
+            Token::Else,
+            Token::Endif,
+            Token::Equ,
+            Token::External,
             Token::Fill,
+            Token::Global,
+            Token::If,
+            Token::Ifdef,
+            Token::Ifndef,
+            Token::Incbin,
             Token::Include,
             Token::Lshift,
             Token::Orig,
@@ -148,6 +241,7 @@ impl Token {
             Token::Stringz,
             Token::Sub,
             Token::Immediate,
+            Token::Expression,
             Token::Label,
             Token::Character,
             Token::String,
@@ -185,7 +279,17 @@ impl Token {
             Token::Puts,
             Token::Putsp,
             Token::Blkw,
+            Token::Define,
+            Token::Else,
+            Token::Endif,
+            Token::Equ,
+            Token::External,
             Token::Fill,
+            Token::Global,
+            Token::If,
+            Token::Ifdef,
+            Token::Ifndef,
+            Token::Incbin,
             Token::Include,
             Token::Lshift,
             Token::Orig,
@@ -194,6 +298,7 @@ impl Token {
             Token::Stringz,
             Token::Sub,
             Token::Immediate,
+            Token::Expression,
             Token::Label,
             Token::Character,
             Token::String,
@@ -231,7 +336,233 @@ impl Token {
             Token::Puts,
             Token::Putsp,
             Token::Blkw,
+            Token::Define,
+            Token::Else,
+            Token::Endif,
+            Token::Equ,
+            Token::External,
+            Token::Fill,
+            Token::Global,
+            Token::If,
+            Token::Ifdef,
+            Token::Ifndef,
+            Token::Incbin,
+            Token::Include,
+            Token::Lshift,
+            Token::Orig,
+            Token::Neg,
+            Token::Set,
+            Token::Stringz,
+            Token::Sub,
+            Token::Immediate,
+            Token::Expression,
+            Token::Label,
+            Token::Character,
+            Token::String,
+            Token::End,
+            Token::Register
+        )
+    }
+
+    /// The file the token is in, along with the macro expansion it came out of
+    #[must_use]
+    pub fn source(&self) -> Source {
+        source_of!(
+            self,
+            Token::Add,
+            Token::And,
+            Token::Br,
+            Token::Jmp,
+            Token::Jmpt,
+            Token::Jsr,
+            Token::Jsrr,
+            Token::Ld,
+            Token::Ldi,
+            Token::Ldr,
+            Token::Lea,
+            Token::Not,
+            Token::Ret,
+            Token::Rti,
+            Token::St,
+            Token::Sti,
+            Token::Str,
+            Token::Trap,
+            Token::Getc,
+            Token::Halt,
+            Token::In,
+            Token::Out,
+            Token::Puts,
+            Token::Putsp,
+            Token::Blkw,
+            Token::Define,
+            Token::Else,
+            Token::Endif,
+            Token::Equ,
+            Token::External,
+            Token::Fill,
+            Token::Global,
+            Token::If,
+            Token::Ifdef,
+            Token::Ifndef,
+            Token::Incbin,
+            Token::Include,
+            Token::Lshift,
+            Token::Orig,
+            Token::Neg,
+            Token::Set,
+            Token::Stringz,
+            Token::Sub,
+            Token::Immediate,
+            Token::Expression,
+            Token::Label,
+            Token::Character,
+            Token::String,
+            Token::End,
+            Token::Register
+        )
+    }
+
+    /// The operands the token has consumed, if it's the kind of token that has any
+    pub(crate) fn operands(&self) -> Option<&Vec<Token>> {
+        operands_of!(
+            self,
+            Token::Add,
+            Token::And,
+            Token::Br,
+            Token::Jmp,
+            Token::Jmpt,
+            Token::Jsr,
+            Token::Jsrr,
+            Token::Ld,
+            Token::Ldi,
+            Token::Ldr,
+            Token::Lea,
+            Token::Not,
+            Token::Ret,
+            Token::Rti,
+            Token::St,
+            Token::Sti,
+            Token::Str,
+            Token::Trap,
+            Token::Getc,
+            Token::Halt,
+            Token::In,
+            Token::Out,
+            Token::Puts,
+            Token::Putsp,
+            Token::Blkw,
+            Token::Define,
+            Token::Else,
+            Token::Endif,
+            Token::Equ,
+            Token::External,
+            Token::Fill,
+            Token::Global,
+            Token::If,
+            Token::Ifdef,
+            Token::Ifndef,
+            Token::Incbin,
+            Token::Include,
+            Token::Lshift,
+            Token::Orig,
+            Token::Neg,
+            Token::Set,
+            Token::Stringz,
+            Token::Sub
+        )
+    }
+
+    /// The operands the token has consumed, if it's the kind of token that has any
+    pub(crate) fn operands_mut(&mut self) -> Option<&mut Vec<Token>> {
+        operands_of_mut!(
+            self,
+            Token::Add,
+            Token::And,
+            Token::Br,
+            Token::Jmp,
+            Token::Jmpt,
+            Token::Jsr,
+            Token::Jsrr,
+            Token::Ld,
+            Token::Ldi,
+            Token::Ldr,
+            Token::Lea,
+            Token::Not,
+            Token::Ret,
+            Token::Rti,
+            Token::St,
+            Token::Sti,
+            Token::Str,
+            Token::Trap,
+            Token::Getc,
+            Token::Halt,
+            Token::In,
+            Token::Out,
+            Token::Puts,
+            Token::Putsp,
+            Token::Blkw,
+            Token::Define,
+            Token::Else,
+            Token::Endif,
+            Token::Equ,
+            Token::External,
+            Token::Fill,
+            Token::Global,
+            Token::If,
+            Token::Ifdef,
+            Token::Ifndef,
+            Token::Incbin,
+            Token::Include,
+            Token::Lshift,
+            Token::Orig,
+            Token::Neg,
+            Token::Set,
+            Token::Stringz,
+            Token::Sub
+        )
+    }
+
+    /// Mark the token as having come out of a macro expansion
+    pub(crate) fn set_expansion(&mut self, expansion: Option<usize>) {
+        set_expansion_of!(
+            self,
+            expansion,
+            Token::Add,
+            Token::And,
+            Token::Br,
+            Token::Jmp,
+            Token::Jmpt,
+            Token::Jsr,
+            Token::Jsrr,
+            Token::Ld,
+            Token::Ldi,
+            Token::Ldr,
+            Token::Lea,
+            Token::Not,
+            Token::Ret,
+            Token::Rti,
+            Token::St,
+            Token::Sti,
+            Token::Str,
+            Token::Trap,
+            Token::Getc,
+            Token::Halt,
+            Token::In,
+            Token::Out,
+            Token::Puts,
+            Token::Putsp,
+            Token::Blkw,
+            Token::Define,
+            Token::Else,
+            Token::Endif,
+            Token::Equ,
+            Token::External,
             Token::Fill,
+            Token::Global,
+            Token::If,
+            Token::Ifdef,
+            Token::Ifndef,
+            Token::Incbin,
             Token::Include,
             Token::Lshift,
             Token::Orig,
@@ -240,6 +571,7 @@ impl Token {
             Token::Stringz,
             Token::Sub,
             Token::Immediate,
+            Token::Expression,
             Token::Label,
             Token::Character,
             Token::String,
@@ -279,7 +611,17 @@ impl fmt::Debug for Token {
             Token::Puts,
             Token::Putsp,
             Token::Blkw,
+            Token::Define,
+            Token::Else,
+            Token::Endif,
+            Token::Equ,
+            Token::External,
             Token::Fill,
+            Token::Global,
+            Token::If,
+            Token::Ifdef,
+            Token::Ifndef,
+            Token::Incbin,
             Token::Include,
             Token::Lshift,
             Token::Orig,
@@ -288,6 +630,7 @@ impl fmt::Debug for Token {
             Token::Stringz,
             Token::Sub,
             Token::Immediate,
+            Token::Expression,
             Token::Label,
             Token::Character,
             Token::String,
@@ -326,7 +669,17 @@ impl Requirements for Token {
             Token::Puts,
             Token::Putsp,
             Token::Blkw,
+            Token::Define,
+            Token::Else,
+            Token::Endif,
+            Token::Equ,
+            Token::External,
             Token::Fill,
+            Token::Global,
+            Token::If,
+            Token::Ifdef,
+            Token::Ifndef,
+            Token::Incbin,
             Token::Include,
             Token::Lshift,
             Token::Orig,
@@ -337,9 +690,10 @@ impl Requirements for Token {
         )
     }
 
-    fn consume(&mut self, tokens: VecDeque<Token>) -> VecDeque<Token> {
+    fn consume(&mut self, session: &mut Session, tokens: VecDeque<Token>) -> VecDeque<Token> {
         consume!(
             self,
+            session,
             tokens,
             Token::Add,
             Token::And,
@@ -366,7 +720,17 @@ impl Requirements for Token {
             Token::Puts,
             Token::Putsp,
             Token::Blkw,
+            Token::Define,
+            Token::Else,
+            Token::Endif,
+            Token::Equ,
+            Token::External,
             Token::Fill,
+            Token::Global,
+            Token::If,
+            Token::Ifdef,
+            Token::Ifndef,
+            Token::Incbin,
             Token::Include,
             Token::Lshift,
             Token::Orig,
@@ -376,6 +740,7 @@ impl Requirements for Token {
             Token::Sub,
             0, // Just a way of delimiting between the two types of tokens (consumable, and not)
             Token::Immediate,
+            Token::Expression,
             Token::Character,
             Token::String,
             Token::Register
@@ -384,9 +749,16 @@ impl Requirements for Token {
 }
 
 impl Assemble for Token {
-    fn assembled(self, program_counter: &mut i16, symbols: &SymbolTable, symbol: &str) -> Listings {
+    fn assembled(
+        self,
+        session: &mut Session,
+        program_counter: &mut i16,
+        symbols: &SymbolTable,
+        symbol: &str,
+    ) -> Listings {
         assembled!(
             self,
+            session,
             program_counter,
             symbols,
             symbol,
@@ -416,6 +788,7 @@ impl Assemble for Token {
             Token::Putsp,
             Token::Blkw,
             Token::Fill,
+            Token::Incbin,
             Token::Lshift,
             Token::Orig,
             Token::Neg,
@@ -5,4 +5,80 @@ use crate::token::Symbol;
 pub type SymbolTable = HashMap<String, Symbol>;
 pub type Listing = (u16, String);
 pub type Listings = Vec<Listing>;
-pub type Program = (SymbolTable, Listings);
+pub type Program = (SymbolTable, Vec<Segment>);
+
+/// A contiguous block of memory, starting at the address given by a `.ORIG`
+#[derive(Debug)]
+pub struct Segment {
+    origin: u16,
+    header: Option<Listing>,
+    listings: Listings,
+}
+
+impl Segment {
+    #[must_use]
+    pub fn new(origin: u16, header: Option<Listing>) -> Self {
+        Self {
+            origin,
+            header,
+            listings: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn origin(&self) -> u16 {
+        self.origin
+    }
+
+    /// The listing for the `.ORIG` that started this segment, if there was one
+    #[must_use]
+    pub fn header(&self) -> Option<&Listing> {
+        self.header.as_ref()
+    }
+
+    pub fn set_header(&mut self, header: Listing) {
+        self.header = Some(header);
+    }
+
+    #[must_use]
+    pub fn listings(&self) -> &Listings {
+        &self.listings
+    }
+
+    #[must_use]
+    pub fn listings_mut(&mut self) -> &mut Listings {
+        &mut self.listings
+    }
+
+    /// The words that make up this segment, without its origin
+    pub fn words(&self) -> impl Iterator<Item = u16> + '_ {
+        self.listings.iter().map(|(word, _)| *word)
+    }
+
+    /// The last address this segment occupies (or its origin, if it's empty)
+    #[must_use]
+    pub fn end(&self) -> u16 {
+        self.origin
+            .wrapping_add(self.listings.len().max(1) as u16)
+            .wrapping_sub(1)
+    }
+
+    /// Whether any part of this segment would be loaded over the other
+    #[must_use]
+    pub fn overlaps(&self, other: &Self) -> bool {
+        let (start, end) = (
+            u32::from(self.origin),
+            u32::from(self.origin) + self.listings.len() as u32,
+        );
+        let (other_start, other_end) = (
+            u32::from(other.origin),
+            u32::from(other.origin) + other.listings.len() as u32,
+        );
+
+        start < other_end && other_start < end
+    }
+
+    pub fn extend(&mut self, listings: Listings) {
+        self.listings.extend(listings);
+    }
+}
@@ -1,9 +1,18 @@
 use std::{
     fs::{File, OpenOptions},
-    io::{Error, Write},
+    io::{Error, Read, Write},
 };
 
-use crate::{token::Symbol, types::Listing, types::Program};
+use crate::{
+    token::Symbol,
+    types::{Program, Segment},
+};
+
+/// Programs with more than one segment can't be described by a plain object file (which
+/// is just an origin followed by the words to load there). Instead, they're written as a
+/// container starting with these two words, then the number of segments, and then each
+/// segment as its origin, its length, and its words.
+pub const OBJECT_CONTAINER_MAGIC: [u16; 2] = [0x4C43, 0x3353];
 
 #[derive(Default)]
 pub struct Writer<W: Write> {
@@ -32,25 +41,93 @@ impl Format {
         Ok(())
     }
 
-    /// Write a symbol to the symbol table if that's the specified format
+    /// Write a symbol to the symbol table if that's the specified format. Constants are
+    /// marked as such, as their values aren't addresses.
     fn write_symbol<W: Write>(&self, out: &mut W, symbol: &Symbol) -> Result<(), Error> {
         if let Format::SymbolTable = self {
-            writeln!(out, "{: <20} {:04X}", symbol.symbol(), symbol.address())?
+            writeln!(
+                out,
+                "{: <20} {:04X}{}",
+                symbol.symbol(),
+                symbol.address(),
+                if symbol.is_constant() {
+                    " (constant)"
+                } else {
+                    ""
+                }
+            )?
         };
 
         Ok(())
     }
 
-    /// Write the listing to the writer in the specified format
-    fn write_listing<W: Write>(&self, out: &mut W, listing: &Listing) -> Result<(), Error> {
-        let (binary, listing) = listing;
+    /// Write anything that comes before the segments themselves
+    fn write_preamble<W: Write>(&self, out: &mut W, segments: &[Segment]) -> Result<(), Error> {
+        match self {
+            Format::Binary | Format::Hex => self.write_image(out, segments)?,
+            Format::Object if segments.len() > 1 => {
+                OBJECT_CONTAINER_MAGIC
+                    .iter()
+                    .try_for_each(|word| write_word(out, *word))?;
+                write_word(out, segments.len() as u16)?;
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Write the whole program as a single origin followed by every word from there on,
+    /// which is all the binary and hex formats can describe. The gaps between segments
+    /// are filled with zeroes, so that each word still ends up at its own address.
+    fn write_image<W: Write>(&self, out: &mut W, segments: &[Segment]) -> Result<(), Error> {
+        let write = |out: &mut W, word: u16| match self {
+            Format::Binary => writeln!(out, "{:016b}", word),
+            _ => writeln!(out, "{:04X}", word),
+        };
+
+        let mut segments = segments.iter().collect::<Vec<_>>();
+        segments.sort_by_key(|segment| segment.origin());
+
+        let mut address = match segments.first() {
+            Some(segment) => segment.origin(),
+            None => return Ok(()),
+        };
+        write(out, address)?;
 
+        segments.into_iter().try_for_each(|segment| {
+            (address..segment.origin()).try_for_each(|_| write(out, 0))?;
+            address = segment
+                .origin()
+                .wrapping_add(segment.listings().len() as u16);
+
+            segment.words().try_for_each(|word| write(out, word))
+        })
+    }
+
+    /// Write a segment to the writer in the specified format
+    fn write_segment<W: Write>(
+        &self,
+        out: &mut W,
+        segment: &Segment,
+        in_container: bool,
+    ) -> Result<(), Error> {
         match self {
-            Format::Binary => writeln!(out, "{:016b}", binary)?,
-            Format::Hex => writeln!(out, "{:04X}", binary)?,
-            Format::Listing => writeln!(out, "{}", listing)?,
+            Format::Listing => {
+                if let Some((_, header)) = segment.header() {
+                    writeln!(out, "{}", header)?;
+                }
+                segment
+                    .listings()
+                    .iter()
+                    .try_for_each(|(_, listing)| writeln!(out, "{}", listing))?
+            }
             Format::Object => {
-                out.write_all(&[(binary >> 8 & 0xFF) as u8, (binary & 0xFF) as u8])?
+                write_word(out, segment.origin())?;
+                if in_container {
+                    write_word(out, segment.listings().len() as u16)?;
+                }
+                segment.words().try_for_each(|word| write_word(out, word))?
             }
             _ => (),
         };
@@ -59,6 +136,64 @@ impl Format {
     }
 }
 
+/// Write a single word, big endian, as the LC-3 expects
+fn write_word<W: Write>(out: &mut W, word: u16) -> Result<(), Error> {
+    out.write_all(&[(word >> 8 & 0xFF) as u8, (word & 0xFF) as u8])
+}
+
+/// Read the segments back out of an object file, as origins along with their words.
+/// Both plain objects and containers of several segments can be read.
+///
+/// Containers are only written for more than one segment, and a file is only read as
+/// one if its segments take up exactly the rest of it. A plain object starting at
+/// x4C43 with x3353 is still ambiguous, but only if the words after happen to fit too.
+///
+/// # Errors
+///   If the object can't be read
+pub fn read_object<R: Read>(mut object: R) -> Result<Vec<(u16, Vec<u16>)>, Error> {
+    let mut bytes = Vec::new();
+    object.read_to_end(&mut bytes)?;
+
+    let words = bytes
+        .chunks(2)
+        .map(|word| u16::from(word[0]) << 8 | u16::from(*word.get(1).unwrap_or(&0)))
+        .collect::<Vec<_>>();
+
+    if let Some(segments) = read_container(&words) {
+        return Ok(segments);
+    }
+
+    Ok(match words.split_first() {
+        Some((origin, segment)) => vec![(*origin, segment.to_vec())],
+        None => Vec::new(),
+    })
+}
+
+/// The segments in a container, if the words make up one
+fn read_container(words: &[u16]) -> Option<Vec<(u16, Vec<u16>)>> {
+    let count = match words {
+        [first, second, count, ..] if [*first, *second] == OBJECT_CONTAINER_MAGIC && *count > 1 => {
+            *count
+        }
+        _ => return None,
+    };
+
+    let mut segments = Vec::new();
+    let mut rest = &words[3..];
+
+    for _ in 0..count {
+        let (origin, length) = match rest {
+            [origin, length, ..] => (*origin, usize::from(*length)),
+            _ => return None,
+        };
+
+        segments.push((origin, rest.get(2..2 + length)?.to_vec()));
+        rest = &rest[2 + length..];
+    }
+
+    rest.is_empty().then_some(segments)
+}
+
 impl<W: Write> Writer<W> {
     #[must_use]
     pub fn new() -> Self {
@@ -80,7 +215,7 @@ impl<W: Write> Writer<W> {
     /// # Errors
     ///   Fails if there is an error writing to the writer
     pub fn write(mut self, program: Program) -> Result<(), Error> {
-        let (symbols, listings) = program;
+        let (symbols, segments) = program;
 
         self.outputs
             .iter_mut()
@@ -92,10 +227,16 @@ impl<W: Write> Writer<W> {
                 .try_for_each(|(format, writer)| format.write_symbol(writer, &symbol))
         })?;
 
-        listings.into_iter().try_for_each(|listing| {
-            self.outputs
-                .iter_mut()
-                .try_for_each(|(format, writer)| format.write_listing(writer, &listing))
+        self.outputs
+            .iter_mut()
+            .try_for_each(|(format, writer)| format.write_preamble(writer, &segments))?;
+
+        let in_container = segments.len() > 1;
+
+        segments.iter().try_for_each(|segment| {
+            self.outputs.iter_mut().try_for_each(|(format, writer)| {
+                format.write_segment(writer, segment, in_container)
+            })
         })?;
 
         Ok(())
@@ -0,0 +1,515 @@
+extern crate serde_json;
+
+mod testing {
+    use std::{
+        env, fs,
+        io::Write,
+        path::PathBuf,
+        process::{Command, Stdio},
+    };
+
+    /// A scratch directory for the files the assembler writes out
+    fn scratch(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("lc3as-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn batch_exit_codes() {
+        let directory = scratch("batch");
+
+        let good = directory.join("good.asm");
+        let bad = directory.join("bad.asm");
+        let missing = directory.join("missing.asm");
+
+        fs::write(&good, ".ORIG x3000\nHALT\n.END\n").unwrap();
+        fs::write(&bad, ".ORIG x3000\nBR NOWHERE\n.END\n").unwrap();
+
+        let run = |files: &[&PathBuf]| {
+            Command::new(env!("CARGO_BIN_EXE_lc3as"))
+                .args(files)
+                .output()
+                .unwrap()
+        };
+
+        let output = run(&[&good]);
+        assert!(output.status.success());
+        assert!(directory.join("good.obj").exists());
+
+        let output = run(&[&good, &bad, &missing]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(stdout.contains("bad.asm: 1 error, 0 warnings"));
+        assert!(stdout.contains("missing.asm: 1 error, 0 warnings"));
+        assert!(stdout.contains("1 of 3 files assembled successfully"));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn colours() {
+        let directory = scratch("colours");
+        let file = directory.join("bad.asm");
+
+        fs::write(&file, ".ORIG x3000\nBR NOWHERE\n.END\n").unwrap();
+
+        let run = |args: &[&str]| {
+            let output = Command::new(env!("CARGO_BIN_EXE_lc3as"))
+                .args(args)
+                .arg(&file)
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        };
+
+        // Piped output is only coloured when asked for
+        assert!(run(&[]).contains("bad.asm:2:4: Error: Undefined reference to label"));
+        assert!(run(&["--color", "never"]).contains("bad.asm:2:4: Error: "));
+        assert!(run(&["--color", "always"]).contains("bad.asm:2:4: \u{1b}[31mError\u{1b}[0m: "));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn command_line_defines() {
+        let directory = scratch("defines");
+        let file = directory.join("debug.asm");
+
+        fs::write(
+            &file,
+            ".ORIG x3000\n.IF DEBUG\nBR NOWHERE\n.ENDIF\nHALT\n.END\n",
+        )
+        .unwrap();
+
+        let run = |args: &[&str]| {
+            Command::new(env!("CARGO_BIN_EXE_lc3as"))
+                .args(args)
+                .arg(&file)
+                .output()
+                .unwrap()
+        };
+
+        assert!(run(&["-D", "DEBUG=0"]).status.success());
+        assert_eq!(run(&["-D", "DEBUG"]).status.code(), Some(1));
+        assert_eq!(run(&["-D", "DEBUG=oops"]).status.code(), Some(2));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn warning_flags() {
+        let directory = scratch("warnings");
+        let file = directory.join("escape.asm");
+
+        fs::write(&file, ".ORIG x3000\n.STRINGZ \"\\q\"\n.END\n").unwrap();
+
+        let run = |args: &[&str]| {
+            Command::new(env!("CARGO_BIN_EXE_lc3as"))
+                .args(args)
+                .arg(&file)
+                .output()
+                .unwrap()
+        };
+
+        let output = run(&[]);
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("0 errors, 1 warning"));
+
+        let output = run(&["-Werror"]);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stdout).contains("1 error, 0 warnings"));
+
+        let output = run(&["-Werror", "-W", "no-unknown-escape"]);
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("0 errors, 0 warnings"));
+
+        assert_eq!(run(&["-W", "error=unknown-escape"]).status.code(), Some(1));
+        assert_eq!(run(&["-W", "no-such-warning"]).status.code(), Some(2));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn json_diagnostics() {
+        let directory = scratch("json");
+        let file = directory.join("macro.asm");
+
+        fs::write(
+            &file,
+            ".ORIG x3000\n.MACRO JUMP\nBR NOWHERE\n.ENDM\nJUMP\n.STRINGZ \"\\q\"\n.END\n",
+        )
+        .unwrap();
+
+        let output = Command::new(env!("CARGO_BIN_EXE_lc3as"))
+            .arg("--diagnostics-format=json")
+            .arg(&file)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+
+        let objects = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        let file = file.display().to_string();
+
+        assert_eq!(
+            objects,
+            vec![
+                serde_json::json!({
+                    "severity": "warning",
+                    "file": file,
+                    "line": 6,
+                    "column": 11,
+                    "width": 2,
+                    "message": "Unknown escape sequence '\\q'",
+                    "code": "unknown-escape",
+                    "lint": "unknown-escape",
+                    "notes": [],
+                }),
+                serde_json::json!({
+                    "severity": "error",
+                    "file": file,
+                    "line": 3,
+                    "column": 4,
+                    "width": 7,
+                    "message": "Undefined reference to label",
+                    "code": "undefined-label",
+                    "lint": null,
+                    "notes": [{
+                        "severity": "note",
+                        "file": file,
+                        "line": 5,
+                        "column": 1,
+                        "width": 4,
+                        "message": "In expansion of macro JUMP",
+                        "code": null,
+                        "lint": null,
+                        "notes": [],
+                    }],
+                }),
+            ]
+        );
+        assert!(String::from_utf8_lossy(&output.stderr).contains("1 error, 1 warning"));
+
+        // A file that can't be read is still reported, in either format
+        let missing = directory.join("missing.asm");
+        let run = |format: &str| {
+            let output = Command::new(env!("CARGO_BIN_EXE_lc3as"))
+                .arg(format!("--diagnostics-format={}", format))
+                .arg(&missing)
+                .output()
+                .unwrap();
+            assert_eq!(output.status.code(), Some(1));
+            String::from_utf8_lossy(&output.stdout).to_string()
+        };
+
+        let object = serde_json::from_str::<serde_json::Value>(&run("json")).unwrap();
+        assert_eq!(object["severity"], "error");
+        assert_eq!(object["code"], "io-error");
+        assert_eq!(object["file"], missing.display().to_string());
+
+        let sarif = serde_json::from_str::<serde_json::Value>(&run("sarif")).unwrap();
+        let result = &sarif["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "io-error");
+        assert_eq!(result["level"], "error");
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            missing.display().to_string()
+        );
+        assert!(result["locations"][0]["physicalLocation"]
+            .get("region")
+            .is_none());
+        assert_eq!(
+            sarif["runs"][0]["invocations"][0]["executionSuccessful"],
+            true
+        );
+
+        // As is one that fails without an error to say why
+        let external = directory.join("external.asm");
+        fs::write(&external, ".ORIG x3000\n.EXTERNAL PRINT\nJSR PRINT\n.END\n").unwrap();
+
+        let output = Command::new(env!("CARGO_BIN_EXE_lc3as"))
+            .arg("--diagnostics-format=sarif")
+            .arg(&external)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+
+        let sarif = serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap();
+        let invocation = &sarif["runs"][0]["invocations"][0];
+        assert_eq!(invocation["executionSuccessful"], false);
+        assert_eq!(
+            invocation["toolExecutionNotifications"][0]["locations"][0]["physicalLocation"]
+                ["artifactLocation"]["uri"],
+            external.display().to_string()
+        );
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn include_directories() {
+        let directory = scratch("includes");
+        let library = directory.join("lib");
+        fs::create_dir_all(&library).unwrap();
+
+        let main = directory.join("main.asm");
+        let cycle = directory.join("cycle.asm");
+
+        fs::write(
+            &main,
+            ".ORIG x3000\n.INCLUDE \"io.h\"\n.INCLUDE \"io.h\"\nHALT\n.END\n",
+        )
+        .unwrap();
+        fs::write(library.join("io.h"), "NEWLINE .EQU #10\n").unwrap();
+        fs::write(&cycle, ".ORIG x3000\n.INCLUDE \"lib/loop.h\"\nHALT\n.END\n").unwrap();
+        fs::write(library.join("loop.h"), ".INCLUDE \"../cycle.asm\"\n").unwrap();
+
+        let run = |args: &[&str], file: &PathBuf| {
+            Command::new(env!("CARGO_BIN_EXE_lc3as"))
+                .args(args)
+                .arg(file)
+                .output()
+                .unwrap()
+        };
+
+        let output = run(&[], &main);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stdout).contains("Can't find 'io.h' to include"));
+
+        // Included twice, but only defined once
+        assert!(run(&["-I", library.to_str().unwrap()], &main)
+            .status
+            .success());
+
+        let output = run(&[], &cycle);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stdout).contains(&format!(
+            "as it's already being included: {} -> {}",
+            cycle.display(),
+            library.join("loop.h").display()
+        )));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn debugging() {
+        let directory = scratch("debugger");
+        let file = directory.join("debug.asm");
+
+        fs::write(
+            &file,
+            ".ORIG x3000\nMAIN LD R1, COUNT\nJSR DOUBLE\nHALT\nDOUBLE ADD R1, R1, R1\nRET\nCOUNT .FILL #3\n.END\n",
+        )
+        .unwrap();
+
+        let mut debugger = Command::new(env!("CARGO_BIN_EXE_lc3db"))
+            .arg(&file)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        debugger
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"break DOUBLE\ncontinue\nstep\nregisters\nfinish\nprint COUNT\ncontinue\n")
+            .unwrap();
+
+        let output = debugger.wait_with_output().unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        assert!(stdout.contains("Breakpoint at x3003 <DOUBLE>  "));
+        assert!(stdout.contains("debug.asm:5: DOUBLE ADD R1, R1, R1"));
+        assert!(stdout.contains("R1 x0006 #6"));
+        assert!(stdout.contains("x3002 <MAIN+2>  "));
+        assert!(stdout.contains("x3005 <COUNT>  x0003 #3"));
+        assert!(stdout.contains("The program has halted"));
+
+        // The program reads what's typed after a command, and a TRAP with a routine of
+        // its own is a call that finish doesn't stop in
+        fs::write(
+            &file,
+            ".ORIG x3000\nJSR ECHO\nHALT\nECHO ST R7, SAVED\nGETC\nOUT\nLD R7, SAVED\nRET\nSAVED .FILL #0\n.ORIG x0021\n.FILL x4000\n.ORIG x4000\nADD R2, R0, #0\nRET\n.END\n",
+        )
+        .unwrap();
+
+        let mut debugger = Command::new(env!("CARGO_BIN_EXE_lc3db"))
+            .arg(&file)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        debugger
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"step\nfinish\nkregisters\n")
+            .unwrap();
+
+        let output = debugger.wait_with_output().unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        assert!(stdout.contains("x3001  "));
+        assert!(stdout.contains("R2 x006B #107"));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn language_server() {
+        let uri = "file:///tmp/lsp/program.asm";
+        let text = ".ORIG x3000\nMAIN LEA R0, MESSAGE\n.LOOP PUTS\nBR .LOOP\nMESSAGE .STRINGZ \"Hi\"\nBR NOWHERE\n.END\n";
+
+        let messages = [
+            serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+            serde_json::json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": { "textDocument": { "uri": uri, "languageId": "lc3", "version": 1, "text": text } },
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "textDocument/definition",
+                "params": { "textDocument": { "uri": uri }, "position": { "line": 3, "character": 4 } },
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "textDocument/references",
+                "params": {
+                    "textDocument": { "uri": uri },
+                    "position": { "line": 1, "character": 14 },
+                    "context": { "includeDeclaration": false },
+                },
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didChange",
+                "params": {
+                    "textDocument": { "uri": uri, "version": 2 },
+                    "contentChanges": [{ "text": text.replace("BR NOWHERE\n", "") }],
+                },
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 4,
+                "method": "textDocument/hover",
+                "params": { "textDocument": { "uri": uri }, "position": { "line": 1, "character": 6 } },
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 5,
+                "method": "textDocument/hover",
+                "params": { "textDocument": { "uri": uri }, "position": { "line": 4, "character": 2 } },
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 6,
+                "method": "textDocument/completion",
+                "params": { "textDocument": { "uri": uri }, "position": { "line": 3, "character": 0 } },
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didChange",
+                "params": {
+                    "textDocument": { "uri": uri, "version": 3 },
+                    "contentChanges": [{ "text": ".ORIG x3000\n.SET R0\n.BLKW\n.BLKW 3-4\n.LSHIFT R0, x7FFF\n.LSHIFT R0, FOO\n.ORIG\n" }],
+                },
+            }),
+            serde_json::json!({ "jsonrpc": "2.0", "id": 7, "method": "shutdown" }),
+            serde_json::json!({ "jsonrpc": "2.0", "method": "exit" }),
+        ];
+
+        let mut server = Command::new(env!("CARGO_BIN_EXE_lc3-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let mut stdin = server.stdin.take().unwrap();
+        messages.iter().for_each(|message| {
+            let body = message.to_string();
+            write!(stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        });
+        drop(stdin);
+
+        let output = server.wait_with_output().unwrap();
+        assert!(output.status.success());
+
+        let mut replies = Vec::new();
+        let mut stdout = output.stdout.as_slice();
+        while let Some(start) = stdout.windows(4).position(|window| window == b"\r\n\r\n") {
+            let header = String::from_utf8_lossy(&stdout[..start]).to_string();
+            let length = header["Content-Length: ".len()..].parse::<usize>().unwrap();
+            let body = &stdout[start + 4..start + 4 + length];
+
+            replies.push(serde_json::from_slice::<serde_json::Value>(body).unwrap());
+            stdout = &stdout[start + 4 + length..];
+        }
+
+        let reply = |id: u64| {
+            replies
+                .iter()
+                .find(|reply| reply["id"] == id)
+                .map(|reply| reply["result"].clone())
+                .unwrap()
+        };
+        let diagnostics = replies
+            .iter()
+            .filter(|reply| reply["method"] == "textDocument/publishDiagnostics")
+            .map(|reply| reply["params"]["diagnostics"].clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(reply(1)["capabilities"]["hoverProvider"], true);
+
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0][0]["message"], "Undefined reference to label");
+        assert_eq!(
+            diagnostics[0][0]["range"],
+            serde_json::json!({ "start": { "line": 5, "character": 3 }, "end": { "line": 5, "character": 10 } })
+        );
+        assert_eq!(diagnostics[1], serde_json::json!([]));
+
+        // Half-typed lines are errors like any other
+        let errors = diagnostics[2].as_array().unwrap();
+        assert!(!errors.is_empty());
+        assert!(errors.iter().all(|error| error["severity"] == 1));
+
+        assert_eq!(
+            reply(2)["range"]["start"],
+            serde_json::json!({ "line": 2, "character": 0 })
+        );
+        assert_eq!(reply(3).as_array().unwrap().len(), 1);
+        assert_eq!(
+            reply(3)[0]["range"]["start"],
+            serde_json::json!({ "line": 1, "character": 13 })
+        );
+
+        assert_eq!(
+            reply(4)["contents"]["value"],
+            "```\nx3000  xE002  1110 0000 0000 0010\n```"
+        );
+        assert_eq!(reply(5)["contents"]["value"], "**MESSAGE** is at x3003");
+
+        let completions = reply(6)
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|completion| completion["label"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        ["LEA", ".STRINGZ", "MAIN", ".LOOP", "MESSAGE"]
+            .iter()
+            .for_each(|label| assert!(completions.contains(&label.to_string()), "{}", label));
+    }
+}
@@ -0,0 +1,929 @@
+extern crate lc3lib;
+extern crate serde_json;
+use lc3lib::{assembler, lint, notifier, session};
+
+mod testing {
+    use assembler::Assembler;
+    use lint::{Level, Lint};
+    use notifier::{DiagType, Diagnostic, Highlight, NoColour, Notifier, Notify, Sarif};
+    use session::{FileController, Session};
+    use std::sync::{Arc, Mutex};
+
+    fn diagnostics_for(source: &str) -> Vec<Diagnostic> {
+        Assembler::from_string(String::from(source))
+            .assemble(false)
+            .err()
+            .unwrap_or_default()
+    }
+
+    fn messages_for(source: &str) -> Vec<String> {
+        diagnostics_for(source)
+            .iter()
+            .map(|diagnostic| diagnostic.message().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn pc_offsets_out_of_range() {
+        let diagnostics = messages_for(
+            "
+            .ORIG x3000
+            BR FAR
+            LD R0, FAR
+            JSR FAR
+            .BLKW #300, #0
+            FAR .FILL #0
+            .END
+            ",
+        );
+
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics[0].contains("Label 'FAR' is #302 away"));
+        assert!(diagnostics[0].contains("PCoffset9 can only reach #-256 to #255"));
+        assert!(diagnostics[1].contains("Label 'FAR' is #301 away"));
+    }
+
+    #[test]
+    fn pc_offset_immediates_out_of_range() {
+        let diagnostics = messages_for(
+            "
+            .ORIG x3000
+            BRnz #-257
+            JSR #1024
+            JSR #1023
+            LEA R1, #255
+            .END
+            ",
+        );
+
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics[0].contains("Offset is #-257 away"));
+        assert!(diagnostics[1].contains("PCoffset11 can only reach #-1024 to #1023"));
+    }
+
+    #[test]
+    fn immediate_fields_out_of_range() {
+        let diagnostics = messages_for(
+            "
+            .ORIG x3000
+            ADD R0, R0, #40
+            AND R1, R1, #-16
+            LDR R2, R6, #-33
+            STR R2, R6, #31
+            TRAP x100
+            .END
+            ",
+        );
+
+        assert_eq!(diagnostics.len(), 4);
+        assert!(diagnostics[0].contains("Value #40 does not fit in ADD's imm5 field"));
+        assert!(diagnostics[0].contains("#-16 to #15"));
+        assert!(diagnostics[1].contains("Consider loading it into a register with .SET"));
+        assert!(diagnostics[2].contains("LDR's offset6 field, which can only hold #-32 to #31"));
+        assert!(diagnostics[3].contains("TRAP's trapvect8 field, which can only hold #0 to #255"));
+    }
+
+    #[test]
+    fn diagnostics_are_structured() {
+        let diagnostics = diagnostics_for(".ORIG x3000\n  ADD R0, R0, #40\n.END");
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity(), DiagType::Error);
+        assert_eq!(diagnostics[0].file(), "temp.asm");
+        assert_eq!(diagnostics[0].line(), 2);
+        assert_eq!(diagnostics[0].column(), 15);
+        assert_eq!(diagnostics[0].width(), 3);
+        assert_eq!(diagnostics[1].severity(), DiagType::Note);
+    }
+
+    #[test]
+    fn string_literals_are_located() {
+        let diagnostics = diagnostics_for(".ORIG x3000\n\n  .FILL \"hi\"\n.END");
+
+        assert_eq!(diagnostics[0].line(), 3);
+        assert_eq!(diagnostics[0].column(), 9);
+    }
+
+    #[test]
+    fn warnings_are_kept_on_success() {
+        let output = Assembler::from_string(String::from(".ORIG x3000\n.STRINGZ \"\\q\"\n.END"))
+            .assemble(false)
+            .unwrap();
+
+        assert_eq!(output.warnings().len(), 1);
+        assert_eq!(output.warnings()[0].severity(), DiagType::Warning);
+        assert_eq!(
+            output.warnings()[0].message(),
+            "Unknown escape sequence '\\q'"
+        );
+    }
+
+    #[test]
+    fn overlapping_segments() {
+        let diagnostics = diagnostics_for(
+            "
+            .ORIG x3000
+            .BLKW #16
+            .ORIG x300F
+            .FILL #0
+            .ORIG x3010
+            .FILL #0
+            .END
+            ",
+        );
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line(), 4);
+        assert_eq!(
+            diagnostics[0].message(),
+            "Segment x300F-x300F overlaps the segment at x3000-x300F"
+        );
+    }
+
+    #[test]
+    fn macro_diagnostics_point_at_the_call_site() {
+        let diagnostics = diagnostics_for(
+            "
+            .ORIG x3000
+            .MACRO JUMP
+            BR NOWHERE
+            .ENDM
+            .MACRO OUTER
+            JUMP
+            .ENDM
+            OUTER
+            .END
+            ",
+        );
+
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0].severity(), DiagType::Error);
+        assert_eq!(diagnostics[0].file(), "temp.asm");
+        assert_eq!(diagnostics[0].line(), 4);
+        assert_eq!(diagnostics[1].severity(), DiagType::Note);
+        assert_eq!(diagnostics[1].line(), 7);
+        assert_eq!(diagnostics[1].message(), "In expansion of macro JUMP");
+        assert_eq!(diagnostics[2].line(), 9);
+        assert_eq!(diagnostics[2].message(), "In expansion of macro OUTER");
+
+        // Notes made in the body point back at the call site too
+        let diagnostics = diagnostics_for(
+            "
+            .ORIG x3000
+            .MACRO BUMP
+            ADD R0, R0, #20
+            .ENDM
+            BUMP
+            .END
+            ",
+        );
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.severity(), diagnostic.line()))
+                .collect::<Vec<_>>(),
+            vec![
+                (DiagType::Error, 4),
+                (DiagType::Note, 6),
+                (DiagType::Note, 4),
+                (DiagType::Note, 6)
+            ]
+        );
+        assert_eq!(diagnostics[3].message(), "In expansion of macro BUMP");
+    }
+
+    #[test]
+    fn invalid_macros() {
+        let diagnostics = messages_for(
+            "
+            .ORIG x3000
+            .MACRO ONE A
+            ADD R0, R0, A
+            .ENDM
+            ONE
+            .MACRO SELF
+            SELF
+            .ENDM
+            SELF
+            .ENDM
+            .MACRO OPEN
+            .END
+            ",
+        );
+
+        assert_eq!(
+            diagnostics,
+            vec![
+                "Macro ONE expects 1 argument, but 0 were found",
+                "Macro SELF can't be expanded inside of itself",
+                "In expansion of macro SELF",
+                "Found .ENDM without a matching .MACRO",
+                "Macro OPEN is never closed with .ENDM",
+            ]
+        );
+    }
+
+    #[test]
+    fn inactive_branches_are_not_checked() {
+        let diagnostics = diagnostics_for(
+            "
+            .ORIG x3000
+            .IFDEF NEVER
+            ADD R0, R0, #100
+            BR NOWHERE
+            .STRINGZ \"unterminated
+            .IF UNDEFINED
+            .ENDIF
+            .ENDIF
+            HALT
+            .END
+            ",
+        );
+
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn unbalanced_conditions() {
+        let diagnostics = messages_for(
+            "
+            .ORIG x3000
+            .ENDIF
+            .ELSE
+            .IF UNDEFINED
+            .ELSE
+            .ELSE
+            .ENDIF
+            .IFNDEF OPEN
+            .END
+            ",
+        );
+
+        assert_eq!(
+            diagnostics,
+            vec![
+                "Found .ENDIF without a matching .IF",
+                "Found .ELSE without a matching .IF",
+                "UNDEFINED has not been defined",
+                ".IF already has an .ELSE",
+                ".IFNDEF is never closed with .ENDIF",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        let diagnostics = messages_for(
+            "
+            .ORIG x3000
+            .FILL 1/0
+            .FILL (1+2
+            .FILL x8000*2
+            .END
+            ",
+        );
+
+        assert_eq!(
+            diagnostics,
+            vec![
+                "Expression '1/0' divides by zero",
+                "Expected a ')' to close the '(' in this expression",
+                "Expression 'x8000*2' can't be represented in 16 bits",
+            ]
+        );
+
+        let diagnostics = messages_for(
+            "
+            .ORIG x3000
+            .BLKW END-START, END
+            START HALT
+            END HALT
+            .END
+            ",
+        );
+
+        assert_eq!(
+            diagnostics,
+            vec![
+                "END must be defined before it's used by .BLKW, as its value is needed in the first pass"
+            ]
+        );
+
+        assert_eq!(
+            messages_for(".ORIG x3000\n.FILL NOWHERE+1\n.END\n"),
+            vec!["Undefined reference to NOWHERE"]
+        );
+    }
+
+    #[test]
+    fn single_slashes_after_operands() {
+        let output = Assembler::from_string(String::from(
+            "
+            .ORIG x3000
+            HALT / comment
+            SIZE .EQU #8
+            .FILL SIZE / 2
+            .FILL #8/-2
+            .FILL LABEL / #1
+            LABEL HALT
+            .END
+            ",
+        ))
+        .assemble(false)
+        .unwrap();
+
+        // A '/' after an operand always divides, otherwise it starts a comment
+        assert_eq!(
+            output
+                .warnings()
+                .iter()
+                .map(|warning| (warning.line(), warning.lint()))
+                .collect::<Vec<_>>(),
+            vec![(3, Some(Lint::SingleSlash))]
+        );
+
+        let (_, segments) = output.into_program();
+        assert_eq!(
+            segments[0].words().collect::<Vec<_>>(),
+            vec![0xF025, 0x0004, 0xFFFC, 0x3004, 0xF025]
+        );
+
+        // Whatever follows the '/' is divided by, even if it's only defined in an include
+        let directory = std::env::temp_dir().join(format!("lc3as-divide-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("sizes.h"), "SIZE .EQU #8\n").unwrap();
+
+        let file = directory.join("main.asm");
+        std::fs::write(
+            &file,
+            ".ORIG x3000\n.INCLUDE \"sizes.h\"\n.FILL #16/SIZE\n.FILL #16/ comment\n.END\n",
+        )
+        .unwrap();
+
+        let messages = Assembler::from_file(file.to_str().unwrap().to_string())
+            .unwrap()
+            .assemble(false)
+            .err()
+            .unwrap_or_default()
+            .iter()
+            .map(|diagnostic| (diagnostic.line(), diagnostic.message().to_string()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            vec![(4, String::from("Undefined reference to comment"))]
+        );
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn external_symbols_needed_before_linking() {
+        assert_eq!(
+            messages_for(
+                ".ORIG x3000\n.EXTERNAL EXT\n.SET R0, EXT\n.SET R1, EXT+1\n.BLKW EXT\n.END\n"
+            ),
+            vec![
+                "External symbol EXT can't be used by .SET, as its address isn't known until it's linked",
+                "External symbol EXT can't be used by .SET, as its address isn't known until it's linked",
+                "External symbol EXT can't be used by .BLKW, as its address isn't known until it's linked",
+            ]
+        );
+
+        assert_eq!(
+            messages_for(".ORIG x3000\n.EXTERNAL EXT\n.FILL EXT+1\nLD R0, EXT-1\n.END\n"),
+            vec![
+                "External symbol EXT can't be used by .FILL, as its address isn't known until it's linked",
+                "External symbol EXT can't be used by LD, as its address isn't known until it's linked",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_constants() {
+        assert_eq!(
+            messages_for(".ORIG x3000\n.EQU #1\n.END\n"),
+            vec![".EQU needs a label to name the constant, e.g. NAME .EQU #10"]
+        );
+
+        assert_eq!(
+            messages_for(".ORIG x3000\nSIZE .EQU #1\n.DEFINE SIZE #2\n.END\n"),
+            vec!["Duplicate symbol found SIZE"]
+        );
+
+        assert_eq!(
+            messages_for(".ORIG x3000\n.BLKW SIZE, #0\nSIZE .EQU #1\n.END\n"),
+            vec![
+                "SIZE must be defined before it's used by .BLKW, as its value is needed in the first pass"
+            ]
+        );
+
+        assert_eq!(
+            messages_for(".ORIG x3000\n.SET R0, N\n.LSHIFT R0, N\nN .EQU #2\n.END\n"),
+            vec![
+                "N must be defined before it's used by .SET, as its value is needed in the first pass",
+                "N must be defined before it's used by .LSHIFT, as its value is needed in the first pass",
+            ]
+        );
+    }
+
+    #[test]
+    fn shift_counts_out_of_range() {
+        let diagnostics = diagnostics_for(".ORIG x3000\n.LSHIFT R0, #-1\n.END\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), Some("count-out-of-range"));
+        assert_eq!(
+            diagnostics[0].message(),
+            ".LSHIFT needs a count from #0 to #15, but was given #-1"
+        );
+        assert_eq!((diagnostics[0].column(), diagnostics[0].width()), (13, 3));
+
+        assert_eq!(
+            messages_for(".ORIG x3000\n.LSHIFT R0, x7FFF\n.END\n"),
+            vec![".LSHIFT needs a count from #0 to #15, but was given #32767"]
+        );
+
+        // Only known once the expression's been worked out
+        assert_eq!(
+            messages_for(".ORIG x3000\nN .EQU #1\n.LSHIFT R0, N-3\n.END\n"),
+            vec![".LSHIFT needs a count from #0 to #15, but was given #-2"]
+        );
+    }
+
+    #[test]
+    fn block_counts_out_of_range() {
+        let diagnostics = diagnostics_for(".ORIG x3000\n.BLKW 3-4\n.END\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), Some("count-out-of-range"));
+        assert_eq!(
+            diagnostics[0].message(),
+            ".BLKW needs a count from #1 to #32767, but was given #-1"
+        );
+        assert_eq!((diagnostics[0].column(), diagnostics[0].width()), (7, 3));
+
+        assert_eq!(
+            messages_for(".ORIG x3000\n.BLKW #0\n.BLKW #-2, #5\n.END\n"),
+            vec![
+                ".BLKW needs a count from #1 to #32767, but was given #0",
+                ".BLKW needs a count from #1 to #32767, but was given #-2",
+            ]
+        );
+    }
+
+    #[test]
+    fn past_the_end_of_memory() {
+        // Addresses from x8000 on are still in memory, even if they're negative as an i16
+        assert!(
+            messages_for(".ORIG x7FFF\nADD R0, R0, R0\nADD R0, R0, R0\nBR #-2\n.END\n").is_empty()
+        );
+        assert!(messages_for(".ORIG xFFFF\nHALT\n.END\n").is_empty());
+
+        assert_eq!(
+            messages_for(".ORIG xFFFF\nHALT\nHALT\n.END\n"),
+            vec!["HALT doesn't fit in memory, which ends at xFFFF"]
+        );
+        assert_eq!(
+            messages_for(".ORIG xFFF0\n.LSHIFT R0, #15\n.LSHIFT R0, #2\n.END\n"),
+            vec![".LSHIFT doesn't fit in memory, which ends at xFFFF"]
+        );
+    }
+
+    #[test]
+    fn half_typed_lines() {
+        // Each of these used to panic while working out how much memory they need
+        [
+            ".ORIG",
+            ".ORIG x3000\n.BLKW",
+            ".ORIG x3000\n.BLKW FOO\n.END\n",
+            ".ORIG x3000\n.SET R0",
+            ".ORIG x3000\n.SET R0, FOO\n.END\n",
+            ".ORIG x3000\n.SET R0, R1\n.END\n",
+            ".ORIG x3000\n.LSHIFT R0, FOO\n.END\n",
+        ]
+        .iter()
+        .for_each(|source| {
+            assert!(
+                diagnostics_for(source)
+                    .iter()
+                    .any(|diagnostic| diagnostic.severity() == DiagType::Error),
+                "{:?} should fail to assemble",
+                source
+            );
+        });
+    }
+
+    #[test]
+    fn local_labels_need_a_global_label() {
+        assert_eq!(
+            messages_for(".ORIG x3000\n.LOOP BRnzp .LOOP\n.END\n"),
+            vec![
+                "Local label .LOOP must come after a global label for it to belong to",
+                "Local label .LOOP must come after a global label for it to belong to",
+            ]
+        );
+
+        assert_eq!(
+            messages_for(".ORIG x3000\n.FILL .TOP-.BOTTOM\n.END\n"),
+            vec![
+                "Local labels in .TOP-.BOTTOM must come after a global label for them to belong to"
+            ]
+        );
+
+        // OTHER and OTHER.LOOP are unused as well, which isn't the point here
+        assert_eq!(
+            diagnostics_for(".ORIG x3000\nMAIN BRnzp .LOOP\nOTHER HALT\n.LOOP HALT\n.END\n")
+                .iter()
+                .filter(|diagnostic| diagnostic.severity() == DiagType::Error)
+                .map(Diagnostic::message)
+                .collect::<Vec<_>>(),
+            vec!["Undefined reference to label"]
+        );
+
+        // Diagnostics cover the label as it was written, rather than its qualified name
+        let at = |source: &str| {
+            diagnostics_for(source)
+                .iter()
+                .filter(|diagnostic| diagnostic.severity() != DiagType::Note)
+                .map(|diagnostic| (diagnostic.line(), diagnostic.column(), diagnostic.width()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(at(".ORIG x3000\nMAIN BR .NOPE\n.END\n"), vec![(2, 9, 5)]);
+        assert_eq!(
+            at(".ORIG x3000\nMAIN HALT\n.MSG .STRINZ \"hi\"\n.END\n")[..2],
+            [(3, 6, 7), (3, 14, 4)]
+        );
+    }
+
+    #[test]
+    fn invalid_binary_includes() {
+        let directory =
+            std::env::temp_dir().join(format!("lc3as-incbin-errors-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("data.bin"), [0x12, 0x34, 0x56]).unwrap();
+
+        let messages = |source: &str| {
+            let file = directory.join("main.asm");
+            std::fs::write(&file, source).unwrap();
+
+            Assembler::from_file(file.to_str().unwrap().to_string())
+                .unwrap()
+                .assemble(false)
+                .err()
+                .unwrap_or_default()
+                .iter()
+                .map(|diagnostic| diagnostic.message().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            messages(".ORIG x3000\n.INCBIN \"data.bin\", 4\n.END\n"),
+            vec![format!(
+                "Offset #4 is past the end of '{}', which is only 3 bytes long",
+                directory.join("data.bin").display()
+            )]
+        );
+        assert_eq!(
+            messages(".ORIG x3000\n.INCBIN \"data.bin\", 0, 4, BYTES\n.END\n"),
+            vec![format!(
+                "Can't include 4 words from '{}', as it only has 3 after offset #0",
+                directory.join("data.bin").display()
+            )]
+        );
+        assert_eq!(
+            messages(".ORIG x3000\n.INCBIN \"nothing.bin\"\n.END\n"),
+            vec![format!(
+                "Can't find 'nothing.bin' to include, having looked in {}",
+                directory.display()
+            )]
+        );
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn lints() {
+        let warnings = |source: &str, session: &mut Session| -> Vec<(u64, String)> {
+            Assembler::from_string(String::from(source))
+                .assemble_with(session, false)
+                .unwrap()
+                .warnings()
+                .iter()
+                .map(|warning| (warning.line(), warning.message().to_string()))
+                .collect()
+        };
+        let source = "
+            .ORIG x3000
+            MAIN .LSHIFT R1, #0
+            RET
+            JSRR R7
+            BRnzp #-1
+            LOOP BR LOOP
+            UNUSED ADD R0, R0, #1
+            JSR FUNCTION
+            HALT
+            FUNCTION ADD R7, R7, #0
+            RET
+            .END
+            ADD R0, R0, #0
+            ";
+
+        assert_eq!(
+            warnings(source, &mut Session::new()),
+            vec![
+                (3, String::from(".LSHIFT by #0 doesn't shift anything")),
+                (
+                    5,
+                    String::from(
+                        "JSRR R7 jumps through the register the return address is saved in, \
+                         which not every machine handles the same way"
+                    )
+                ),
+                (
+                    6,
+                    String::from(
+                        "BRnzp jumps back to itself (an offset of #-1), so it never gets any further"
+                    )
+                ),
+                (
+                    7,
+                    String::from(
+                        "BR jumps back to itself (an offset of #-1), so it never gets any further"
+                    )
+                ),
+                (8, String::from("Label UNUSED is never used")),
+                (
+                    4,
+                    String::from(
+                        "RET returns to the address in R7, but nothing before it has set R7"
+                    )
+                ),
+                (
+                    14,
+                    String::from("ADD comes after .END, but is still assembled")
+                ),
+            ]
+        );
+
+        let mut session = Session::new();
+        Lint::ALL
+            .iter()
+            .for_each(|lint| session.set_lint(*lint, false));
+        assert!(warnings(source, &mut session).is_empty());
+
+        assert_eq!(Lint::from_name("unused-label"), Some(Lint::UnusedLabel));
+        assert_eq!(
+            warnings(".ORIG x3000\nHALT\n", &mut Session::new()),
+            vec![(2, String::from("The program doesn't finish with .END"))]
+        );
+    }
+
+    #[test]
+    fn warnings_can_be_allowed_or_made_errors() {
+        let source = "
+            .ORIG x3000
+            A
+            B .FILL #1 ; lc3as: allow(multiple-symbols)
+            ; lc3as: allow(unknown-escape, multiple-symbols)
+            C .STRINGZ \"\\q\"
+            D
+            E .STRINGZ \"\\q\"
+            LD R0, A
+            LEA R0, C
+            LEA R0, D
+            .END
+            ";
+        let assemble = |session: &mut Session| {
+            Assembler::from_string(String::from(source)).assemble_with(session, false)
+        };
+
+        let warnings = assemble(&mut Session::new()).unwrap();
+        let warnings = warnings
+            .warnings()
+            .iter()
+            .map(|warning| (warning.line(), warning.lint()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            vec![
+                (8, Some(Lint::UnknownEscape)),
+                (8, Some(Lint::MultipleSymbols))
+            ]
+        );
+
+        let mut session = Session::new();
+        session.set_lint_level(Lint::UnknownEscape, Level::Error);
+        let diagnostics = assemble(&mut session).err().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity(), DiagType::Error);
+        assert_eq!(diagnostics[0].line(), 8);
+
+        let mut session = Session::new();
+        session.set_warnings_as_errors(true);
+        session.set_lint(Lint::UnknownEscape, false);
+        let diagnostics = assemble(&mut session).err().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].lint(), Some(Lint::MultipleSymbols));
+        assert_eq!(diagnostics[0].severity(), DiagType::Error);
+
+        // Notes go with the warning before them, so they're dropped along with it
+        let raise = |session: &mut Session, severity: DiagType, lint: Option<Lint>| {
+            let diagnostic = Diagnostic::Highlight(Highlight::new(
+                severity,
+                String::from("temp.asm"),
+                1,
+                3,
+                1,
+                String::from("Multiple symbols found for address 0x3000"),
+            ));
+            session.add_diagnostic(match lint {
+                Some(lint) => diagnostic.with_lint(lint),
+                None => diagnostic,
+            });
+        };
+
+        let mut session = Session::new();
+        session.set_lint(Lint::MultipleSymbols, false);
+        raise(&mut session, DiagType::Warning, Some(Lint::MultipleSymbols));
+        raise(&mut session, DiagType::Note, None);
+        assert!(session.diagnostics().is_empty());
+
+        raise(&mut session, DiagType::Error, None);
+        raise(&mut session, DiagType::Note, None);
+        assert_eq!(session.diagnostics().len(), 2);
+    }
+
+    #[test]
+    fn diagnostics_can_go_to_callbacks() {
+        struct Rendered(Arc<Mutex<Vec<String>>>);
+
+        impl Notify for Rendered {
+            fn notify(&mut self, diagnostic: &Diagnostic, files: &FileController) {
+                self.0.lock().unwrap().push(diagnostic.uncoloured(files));
+            }
+        }
+
+        let heard = Arc::new(Mutex::new(Vec::new()));
+        let rendered = Arc::new(Mutex::new(Vec::new()));
+
+        let mut session = Session::new();
+        let callback = Arc::clone(&heard);
+        session.register(
+            String::from("callback"),
+            Notifier::custom(move |diagnostic: &Diagnostic| {
+                callback
+                    .lock()
+                    .unwrap()
+                    .push((diagnostic.severity(), diagnostic.line()));
+            }),
+        );
+        session.register(
+            String::from("rendered"),
+            Notifier::custom(Rendered(Arc::clone(&rendered))),
+        );
+
+        let result = Assembler::from_string(String::from(".ORIG x3000\nBR NOWHERE\n.END"))
+            .assemble_with(&mut session, false);
+
+        assert!(result.is_err());
+        assert_eq!(*heard.lock().unwrap(), vec![(DiagType::Error, 2)]);
+        assert_eq!(
+            *rendered.lock().unwrap(),
+            vec!["temp.asm:2:4: Error: Undefined reference to label\nBR NOWHERE\n   ~~~~~~~"]
+        );
+    }
+
+    #[test]
+    fn sarif_logs() {
+        let mut sarif = Sarif::new();
+        sarif.add(&diagnostics_for(
+            ".ORIG x3000\nLD R0, FAR\n.BLKW #300, #0\nFAR .FILL #0\n.STRINGZ \"\\q\"\n.END",
+        ));
+        sarif.add(&diagnostics_for(
+            ".ORIG x3000\n.MACRO JUMP\nBR NOWHERE\n.ENDM\nJUMP\n.END",
+        ));
+
+        let log = sarif.to_json();
+        let run = &log["runs"][0];
+        let results = run["results"].as_array().unwrap();
+
+        assert_eq!(log["version"], "2.1.0");
+        assert_eq!(
+            run["tool"]["driver"]["rules"],
+            serde_json::json!([
+                { "id": "unknown-escape" },
+                { "id": "offset-out-of-range" },
+                { "id": "undefined-label" },
+            ])
+        );
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["level"], "warning");
+        assert_eq!(results[0]["ruleIndex"], 0);
+        assert_eq!(results[1]["level"], "error");
+        assert_eq!(results[1]["ruleId"], "offset-out-of-range");
+        assert_eq!(
+            results[1]["locations"][0]["physicalLocation"]["region"],
+            serde_json::json!({ "startLine": 2, "startColumn": 8, "endColumn": 11 })
+        );
+        assert_eq!(results[2]["ruleId"], "undefined-label");
+        assert_eq!(
+            results[2]["relatedLocations"][0]["message"]["text"],
+            "In expansion of macro JUMP"
+        );
+        assert_eq!(run["invocations"][0]["executionSuccessful"], true);
+
+        // A file that failed without a diagnostic is noted against the invocation
+        sarif.fail("crash.asm", "the assembler panicked");
+
+        let invocation = &sarif.to_json()["runs"][0]["invocations"][0];
+        assert_eq!(invocation["executionSuccessful"], false);
+        assert_eq!(
+            invocation["toolExecutionNotifications"][0]["message"]["text"],
+            "the assembler panicked"
+        );
+        assert_eq!(
+            invocation["toolExecutionNotifications"][0]["locations"][0]["physicalLocation"]
+                ["artifactLocation"]["uri"],
+            "crash.asm"
+        );
+    }
+
+    #[test]
+    fn suggestions_for_typos() {
+        // The labels that were misspelled are unused as well, which isn't the point here
+        let without_warnings = |source: &str| {
+            diagnostics_for(source)
+                .iter()
+                .filter(|diagnostic| diagnostic.severity() != DiagType::Warning)
+                .map(|diagnostic| diagnostic.message().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            without_warnings(
+                "
+                .ORIG x3000
+                LEA R0, MESAGE
+                PUTS
+                LOOP BRnzp LOPO
+                BR FARAWAY
+                MESSAGE .STRINGZ \"Hi\"
+                .END
+                "
+            ),
+            vec![
+                "Undefined reference to label",
+                "Did you mean 'MESSAGE'?",
+                "Undefined reference to label",
+                "Did you mean 'LOOP'?",
+                "Undefined reference to label",
+            ]
+        );
+
+        assert_eq!(
+            without_warnings(".ORIG x3000\nMAIN BRnzp .LOPO\n.LOOP HALT\n.END\n")[1],
+            "Did you mean '.LOOP'?"
+        );
+
+        let diagnostics = diagnostics_for(".ORIG x3000\nADDD R1, R2, R3\n.END\n");
+        assert_eq!(diagnostics[0].severity(), DiagType::Error);
+        assert_eq!(diagnostics[1].severity(), DiagType::Note);
+        assert_eq!(
+            diagnostics[1].message(),
+            "'ADDD' looks like a misspelled 'ADD'"
+        );
+        assert_eq!(diagnostics[1].column(), 1);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity() == DiagType::Note)
+                .count(),
+            1
+        );
+
+        // A label can come before the misspelled word too
+        let notes = |source: &str| {
+            diagnostics_for(source)
+                .iter()
+                .filter(|diagnostic| diagnostic.severity() == DiagType::Note)
+                .map(|diagnostic| (diagnostic.message().to_string(), diagnostic.column()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            notes(".ORIG x3000\nMSG .STRINZ \"hi\"\n.END\n"),
+            vec![(
+                "'.STRINZ' looks like a misspelled '.STRINGZ'".to_string(),
+                5
+            )]
+        );
+        assert_eq!(
+            notes(".ORIG x3000\nLOOP ADDD R1, R2, R3\n.END\n"),
+            vec![("'ADDD' looks like a misspelled 'ADD'".to_string(), 6)]
+        );
+
+        // Only a label with operands after it is taken for an instruction
+        assert!(messages_for(".ORIG x3000\nADDD\nBR ADDD\n.END\n").is_empty());
+    }
+}
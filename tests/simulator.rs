@@ -0,0 +1,147 @@
+extern crate lc3lib;
+use lc3lib::{assembler, simulator, types, writer};
+
+mod testing {
+    use std::io::{empty, Empty};
+
+    use assembler::Assembler;
+    use simulator::{Error, Simulator};
+    use types::Program;
+    use writer::{Format, Writer};
+
+    fn assemble(source: &str) -> Program {
+        Assembler::from_string(String::from(source))
+            .assemble(false)
+            .ok()
+            .unwrap()
+            .into_program()
+    }
+
+    fn simulate(source: &str, input: &'static [u8]) -> Simulator<&'static [u8], Vec<u8>> {
+        let mut simulator = Simulator::new(input, Vec::new());
+        simulator.load_program(&assemble(source));
+        simulator.run(10_000).unwrap();
+
+        assert!(simulator.is_halted());
+        simulator
+    }
+
+    #[test]
+    fn standard_traps() {
+        let simulator = simulate(
+            "
+            .ORIG x3000
+            LEA R0, HELLO
+            PUTS
+            LEA R0, PACKED
+            PUTSP
+            LOOP GETC
+            ADD R1, R0, #-10
+            BRz DONE
+            ADD R0, R0, #1
+            OUT
+            BR LOOP
+            DONE HALT
+            HELLO .STRINGZ \"Hello, \"
+            PACKED .FILL x6F77
+            .FILL x6C72
+            .FILL x0064
+            .FILL #0
+            .END
+            ",
+            b"HAL\n",
+        );
+
+        assert_eq!(simulator.into_output(), b"Hello, worldIBM".to_vec());
+    }
+
+    #[test]
+    fn instructions() {
+        let simulator = simulate(
+            "
+            .ORIG x3000
+            LD R1, SIX
+            JSR DOUBLE
+            ST R1, RESULT
+            LEA R2, RESULT
+            LDR R3, R2, #0
+            NOT R4, R3
+            AND R5, R4, #15
+            LDI R6, POINTER
+            STI R5, POINTER
+            HALT
+            DOUBLE ADD R1, R1, R1
+            RET
+            SIX .FILL #6
+            RESULT .FILL #0
+            POINTER .FILL SIX
+            .END
+            ",
+            b"",
+        );
+
+        assert_eq!(simulator.register(1), 12);
+        assert_eq!(simulator.register(3), 12);
+        assert_eq!(simulator.register(4), !12);
+        assert_eq!(simulator.register(5), 3);
+        assert_eq!(simulator.register(6), 6);
+        assert_eq!(simulator.memory(0x300C), 3);
+        assert_eq!(simulator.condition(), "p");
+    }
+
+    #[test]
+    fn privilege() {
+        let program = assemble(
+            "
+            .ORIG x3000
+            LEA R0, USER
+            JMPT R0
+            USER RTI
+            .END
+            ",
+        );
+
+        let mut simulator = Simulator::new(empty(), Vec::new());
+        simulator.load_program(&program);
+        simulator.set_psr(0x0002);
+
+        simulator.step().unwrap();
+        simulator.step().unwrap();
+        assert!(!simulator.is_supervisor());
+        assert_eq!(simulator.pc(), 0x3002);
+
+        match simulator.step() {
+            Err(Error::PrivilegeViolation(address)) => assert_eq!(address, 0x3002),
+            result => panic!(
+                "Expected a privilege mode violation, but found {:?}",
+                result
+            ),
+        }
+    }
+
+    #[test]
+    fn loading_objects() {
+        let program = assemble(
+            "
+            .ORIG x3000
+            LD R0, DATA
+            HALT
+            .ORIG x3100
+            DATA .FILL x1234
+            .END
+            ",
+        );
+
+        let mut object = Vec::new();
+        Writer::new()
+            .register(Format::Object, &mut object)
+            .write(program)
+            .unwrap();
+
+        let mut simulator: Simulator<Empty, Vec<u8>> = Simulator::new(empty(), Vec::new());
+        simulator.load_object(&object[..]).unwrap();
+
+        assert_eq!(simulator.pc(), 0x3000);
+        assert_eq!(simulator.memory(0x3100), 0x1234);
+    }
+}
@@ -1,11 +1,20 @@
 #![feature(test)]
 
 extern crate lc3lib;
-use lc3lib::{assembler, lexer};
+use lc3lib::{assembler, disassembler, formatter, lexer, linker, lint, notifier, session, writer};
 
 mod testing {
+    use std::thread;
+
     use assembler::Assembler;
+    use disassembler::{read_symbols, Disassembler};
+    use formatter::{Case, Formatter, Immediates};
     use lexer::tokenizer::Tokenizer;
+    use linker::{link, Object};
+    use lint::Lint;
+    use notifier::{Diagnostic, Notifier};
+    use session::Session;
+    use writer::{read_object, Format, Writer};
 
     #[test]
     fn assemble_from_string() {
@@ -20,7 +29,7 @@ mod testing {
 
         let program = assembler.assemble(false);
 
-        assert!(program.is_some());
+        assert!(program.is_ok());
     }
 
     #[test]
@@ -89,13 +98,16 @@ mod testing {
 
         let program = assembler.assemble(false);
 
-        assert!(program.is_some());
+        assert!(program.is_ok());
 
-        let (symbols, listings) = program.unwrap();
+        let (symbols, segments) = program.unwrap().into_program();
 
         // Make sure none of them come up as symbols
         assert!(symbols.is_empty());
 
+        assert!(segments.len() == 1);
+        let listings = segments[0].listings();
+
         // Make sure all of them are captured
         assert!(listings.len() == 11);
 
@@ -111,4 +123,591 @@ mod testing {
         assert!(listings[9].0 == 0x0600);
         assert!(listings[10].0 == 0x0600);
     }
+
+    #[test]
+    fn sessions_are_independent() {
+        let failing = thread::spawn(|| {
+            let mut session = Session::new();
+            session.register(String::from("test"), Notifier::Stringify(Vec::new()));
+
+            let result = Assembler::from_string(String::from(".ORIG x3000\nBR NOWHERE\n.END"))
+                .assemble_with(&mut session, false);
+
+            (result.is_err(), session.notifications())
+        });
+
+        let passing = thread::spawn(|| {
+            (0..50).all(|_| {
+                Assembler::from_string(String::from(".ORIG x3000\nHALT\n.END"))
+                    .assemble(false)
+                    .is_ok()
+            })
+        });
+
+        let (failed, notifications) = failing.join().unwrap();
+
+        assert!(failed);
+        assert!(passing.join().unwrap());
+        assert_eq!(notifications.len(), 1);
+        assert!(notifications[0].contains("Undefined reference to label\nBR NOWHERE"));
+    }
+
+    #[test]
+    fn multiple_segments() {
+        let program = Assembler::from_string(String::from(
+            "
+            .ORIG x3000
+            LD R0, POINTER
+            HALT
+            POINTER .FILL DATA
+            .ORIG x4000
+            DATA .FILL #1
+            .FILL #2
+            .END
+            ",
+        ))
+        .assemble(false);
+
+        let (symbols, segments) = program.unwrap().into_program();
+
+        assert_eq!(symbols["DATA"].address(), 0x4000);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].origin(), 0x3000);
+        assert_eq!(
+            segments[0].words().collect::<Vec<_>>(),
+            vec![0x2001, 0xF025, 0x4000]
+        );
+        assert_eq!(segments[1].origin(), 0x4000);
+        assert_eq!(segments[1].words().collect::<Vec<_>>(), vec![1, 2]);
+
+        let (mut object, mut hex) = (Vec::new(), Vec::new());
+        Writer::new()
+            .register(Format::Object, &mut object)
+            .register(Format::Hex, &mut hex)
+            .write((symbols, segments))
+            .unwrap();
+
+        // The gap between the segments is padded, so the second still starts at x4000
+        let hex = String::from_utf8(hex).unwrap();
+        assert_eq!(hex.lines().count(), 1 + 0x1002);
+        assert!(hex.starts_with("3000\n2001\nF025\n4000\n0000\n"));
+        assert!(hex.ends_with("0000\n0001\n0002\n"));
+
+        assert_eq!(
+            object,
+            vec![
+                0x4C, 0x43, 0x33, 0x53, 0x00, 0x02, // Container header
+                0x30, 0x00, 0x00, 0x03, 0x20, 0x01, 0xF0, 0x25, 0x40, 0x00, // First segment
+                0x40, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x02, // Second segment
+            ]
+        );
+        assert_eq!(
+            read_object(object.as_slice()).unwrap(),
+            vec![(0x3000, vec![0x2001, 0xF025, 0x4000]), (0x4000, vec![1, 2])]
+        );
+
+        // A plain object that happens to start like a container is still read as it is
+        let plain = [0x4C, 0x43, 0x33, 0x53, 0x00, 0x02, 0x30, 0x00];
+        assert_eq!(
+            read_object(&plain[..]).unwrap(),
+            vec![(0x4C43, vec![0x3353, 0x0002, 0x3000])]
+        );
+    }
+
+    #[test]
+    fn linking_objects() {
+        let relocatable = |name: &str, source: &str| {
+            let (program, linkage) = Assembler::from_string(String::from(source))
+                .assemble(false)
+                .unwrap()
+                .into_parts();
+
+            // Make sure the object survives being written out and read back in
+            let mut written = Vec::new();
+            Object::new(name.to_string(), program, linkage)
+                .write(&mut written)
+                .unwrap();
+            Object::read(name.to_string(), &written[..]).unwrap()
+        };
+
+        let main = relocatable(
+            "main.rel",
+            "
+            .ORIG x3000
+            .EXTERNAL PRINT
+            .EXTERNAL MESSAGE
+            LD R0, POINTER
+            JSR PRINT
+            HALT
+            POINTER .FILL MESSAGE
+            .BLKW #2, MESSAGE
+            .END
+            ",
+        );
+        let library = relocatable(
+            "library.rel",
+            "
+            .ORIG x3010
+            .GLOBAL PRINT
+            .GLOBAL MESSAGE
+            PRINT PUTS
+            RET
+            MESSAGE .STRINGZ \"Hi\"
+            .END
+            ",
+        );
+
+        assert_eq!(main.linkage().relocations().len(), 4);
+
+        let (symbols, segments) = link(vec![main, library]).unwrap();
+
+        assert_eq!(symbols["PRINT"].address(), 0x3010);
+        assert_eq!(
+            segments[0].words().collect::<Vec<_>>(),
+            vec![0x2002, 0x480E, 0xF025, 0x3012, 0x3012, 0x3012]
+        );
+        assert!(segments[0].listings()[1].1.contains("480E"));
+
+        let unresolved = relocatable(
+            "unresolved.rel",
+            ".ORIG x3000\n.EXTERNAL NOWHERE\nBR NOWHERE\n.END\n",
+        );
+
+        assert_eq!(
+            link(vec![unresolved]).err().unwrap(),
+            vec!["unresolved.rel: Undefined reference to NOWHERE at x3000"]
+        );
+    }
+
+    #[test]
+    fn macro_expansion() {
+        let program = Assembler::from_string(String::from(
+            "
+            .ORIG x3000
+            .MACRO COUNTDOWN REGISTER, FROM
+            AND REGISTER, REGISTER, #0
+            ADD REGISTER, REGISTER, FROM
+            LOOP ADD REGISTER, REGISTER, #-1
+            BRp LOOP
+            .ENDM
+            .MACRO TWICE FROM
+            COUNTDOWN R1, FROM
+            COUNTDOWN R2, FROM
+            .ENDM
+            START TWICE #5
+            HALT
+            .END
+            ",
+        ))
+        .assemble(false);
+
+        let (symbols, segments) = program.unwrap().into_program();
+
+        assert_eq!(symbols["START"].address(), 0x3000);
+        assert_eq!(symbols["COUNTDOWN.1.LOOP"].address(), 0x3002);
+        assert_eq!(symbols["COUNTDOWN.2.LOOP"].address(), 0x3006);
+        assert_eq!(
+            segments[0].words().collect::<Vec<_>>(),
+            vec![0x5260, 0x1265, 0x127F, 0x03FE, 0x54A0, 0x14A5, 0x14BF, 0x03FE, 0xF025]
+        );
+    }
+
+    #[test]
+    fn conditional_assembly() {
+        let source = "
+            .ORIG x3000
+            .IFDEF DEBUG
+            .IF LEVEL
+            ADD R0, R0, #2
+            .ELSE
+            ADD R0, R0, #1
+            .ENDIF
+            .ENDIF
+            .IFNDEF DEBUG
+            ADD R0, R0, #3
+            .ENDIF
+            HALT
+            .END
+            ";
+
+        let words = |defines: &[(&str, i16)]| {
+            let mut session = Session::new();
+            defines
+                .iter()
+                .for_each(|(name, value)| session.define(name.to_string(), *value));
+
+            let (_, segments) = Assembler::from_string(String::from(source))
+                .assemble_with(&mut session, false)
+                .unwrap()
+                .into_program();
+            segments[0].words().collect::<Vec<_>>()
+        };
+
+        assert_eq!(words(&[]), vec![0x1023, 0xF025]);
+        assert_eq!(words(&[("DEBUG", 1), ("LEVEL", 0)]), vec![0x1021, 0xF025]);
+        assert_eq!(words(&[("DEBUG", 1), ("LEVEL", 2)]), vec![0x1022, 0xF025]);
+    }
+
+    #[test]
+    fn constant_expressions() {
+        let program = Assembler::from_string(String::from(
+            "
+            .ORIG x3000
+            .MACRO LOAD REGISTER, INDEX
+            LDR REGISTER, R6, #INDEX*2+1
+            .ENDM
+            START LD R0, DATA+1
+            ADD R1, R1, #2*3-1
+            AND R2, R2, x1F & ~x10
+            LOAD R3, 2
+            .BLKW (1 << 2) / 2
+            BR START+1
+            DATA .FILL END-START
+            .FILL 'A'+1
+            END .FILL -(START|1)
+            .END
+            ",
+        ))
+        .assemble(false);
+
+        let (_, segments) = program.unwrap().into_program();
+
+        assert_eq!(
+            segments[0].words().collect::<Vec<_>>(),
+            vec![0x2007, 0x1265, 0x54AF, 0x6785, 0x0000, 0x0000, 0x0FFA, 0x0009, 0x0042, 0xCFFF]
+        );
+    }
+
+    #[test]
+    fn pc_relative_expressions() {
+        // Only an expression referring to a label is an address, anything else is an offset
+        let program = Assembler::from_string(String::from(
+            "
+            K .EQU #0
+            .ORIG x3000
+            START BR #0-1
+            BR #K-1
+            BR START+1
+            LD R0, #K+2
+            .END
+            ",
+        ))
+        .assemble(false);
+
+        let (_, segments) = program.unwrap().into_program();
+
+        assert_eq!(
+            segments[0].words().collect::<Vec<_>>(),
+            vec![0x0FFF, 0x0FFF, 0x0FFE, 0x2002]
+        );
+    }
+
+    #[test]
+    fn named_constants() {
+        let program = Assembler::from_string(String::from(
+            "
+            KBSR .EQU xFE00
+            NEWLINE .EQU #10
+            .DEFINE MINUS_ONE #-1
+            .DEFINE TOP KBSR+2
+            .ORIG x3000
+            ADD R0, R0, MINUS_ONE
+            AND R1, R1, #MINUS_ONE+1
+            LEA R2, DATA
+            BR MINUS_ONE+1
+            LD R3, NEWLINE
+            DATA .FILL NEWLINE
+            .FILL KBSR
+            .FILL TOP
+            .END
+            ",
+        ))
+        .assemble(false);
+
+        let (symbols, segments) = program.unwrap().into_program();
+
+        assert_eq!(
+            segments[0].words().collect::<Vec<_>>(),
+            vec![0x103F, 0x5260, 0xE402, 0x0E00, 0x260A, 0x000A, 0xFE00, 0xFE02]
+        );
+
+        assert!(symbols["NEWLINE"].is_constant());
+        assert_eq!(symbols["MINUS_ONE"].value(), -1);
+        assert!(!symbols["DATA"].is_constant());
+
+        let mut output = Vec::new();
+        Writer::new()
+            .register(Format::SymbolTable, &mut output)
+            .write((symbols, Vec::new()))
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("KBSR                 FE00 (constant)\n"));
+        assert!(output.contains("DATA                 3005\n"));
+    }
+
+    #[test]
+    fn constants_used_before_they_are_defined() {
+        let program = Assembler::from_string(String::from(
+            "
+            .ORIG x3000
+            ADD R0, R0, K
+            LD R1, K
+            .FILL K*2
+            K .EQU #3
+            .END
+            ",
+        ))
+        .assemble(false);
+
+        let (_, segments) = program.unwrap().into_program();
+
+        assert_eq!(
+            segments[0].words().collect::<Vec<_>>(),
+            vec![0x1023, 0x2203, 0x0006]
+        );
+    }
+
+    #[test]
+    fn local_labels() {
+        let program = Assembler::from_string(String::from(
+            "
+            .ORIG x3000
+            MAIN JSR PRINT
+            .LOOP BRnzp .LOOP
+            PRINT LD R0, .CHAR
+            .LOOP ADD R0, R0, #-1
+            BRp .LOOP
+            RET
+            .CHAR .FILL x41
+            .FILL .CHAR-.LOOP
+            .END
+            ",
+        ))
+        .assemble(false);
+
+        let (symbols, segments) = program.unwrap().into_program();
+
+        assert_eq!(
+            segments[0].words().collect::<Vec<_>>(),
+            vec![0x4801, 0x0FFF, 0x2003, 0x103F, 0x03FE, 0xC1C0, 0x0041, 0x0003]
+        );
+
+        assert_eq!(symbols["MAIN.LOOP"].address(), 0x3001);
+        assert_eq!(symbols["PRINT.LOOP"].address(), 0x3003);
+        assert_eq!(symbols["PRINT.CHAR"].address(), 0x3006);
+    }
+
+    #[test]
+    fn including_binary_files() {
+        let directory = std::env::temp_dir().join(format!("lc3as-incbin-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("data.bin"), [0x12, 0x34, 0x56, 0x78, 0x9A]).unwrap();
+
+        let file = directory.join("main.asm");
+        std::fs::write(
+            &file,
+            "
+            .ORIG x3000
+            LEA R0, TABLE
+            TABLE .INCBIN \"data.bin\", 0, 2
+            .INCBIN \"data.bin\", 3, BYTES
+            AFTER HALT
+            .END
+            ",
+        )
+        .unwrap();
+
+        let padded = |warnings: &[Diagnostic]| {
+            warnings
+                .iter()
+                .filter(|warning| warning.lint() == Some(Lint::IncbinPadding))
+                .count()
+        };
+        let assemble = || {
+            Assembler::from_file(file.to_str().unwrap().to_string())
+                .unwrap()
+                .assemble(false)
+                .ok()
+                .unwrap()
+        };
+
+        let program = assemble();
+
+        // Only the words included are checked for being cut short
+        assert_eq!(padded(program.warnings()), 0);
+
+        let (symbols, segments) = program.into_program();
+
+        assert_eq!(
+            segments[0].words().collect::<Vec<_>>(),
+            vec![0xE000, 0x1234, 0x5678, 0x0078, 0x009A, 0xF025]
+        );
+        assert_eq!(symbols["AFTER"].address(), 0x3005);
+
+        std::fs::write(&file, ".ORIG x3000\n.INCBIN \"data.bin\", 2\n.END\n").unwrap();
+        let program = assemble();
+
+        std::fs::remove_dir_all(directory).unwrap();
+
+        assert_eq!(padded(program.warnings()), 1);
+        assert_eq!(
+            program.into_program().1[0].words().collect::<Vec<_>>(),
+            vec![0x5678, 0x9A00]
+        );
+    }
+
+    #[test]
+    fn disassembling_examples() {
+        let object = |assembler: Assembler| {
+            let program = assembler.assemble(false).ok()?.into_program();
+
+            let (mut object, mut symbols) = (Vec::new(), Vec::new());
+            Writer::new()
+                .register(Format::Object, &mut object)
+                .register(Format::SymbolTable, &mut symbols)
+                .write(program)
+                .unwrap();
+
+            Some((object, symbols))
+        };
+
+        // Test.asm is full of mistakes on purpose, but every other example should assemble
+        let mut examples = std::fs::read_dir("Examples")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "asm"))
+            .filter(|path| !path.ends_with("Test.asm"))
+            .collect::<Vec<_>>();
+        examples.sort();
+
+        let disassembled = examples
+            .iter()
+            .map(|example| {
+                object(Assembler::from_file(example.to_str().unwrap().to_string()).unwrap())
+                    .unwrap_or_else(|| panic!("{} doesn't assemble", example.display()))
+            })
+            .map(|(original, symbols)| {
+                let segments = read_object(original.as_slice()).unwrap();
+                let symbols = read_symbols(symbols.as_slice()).unwrap();
+                let source = Disassembler::new(&segments, &symbols).disassemble();
+
+                let (object, _) = object(Assembler::from_string(source.clone()))
+                    .unwrap_or_else(|| panic!("Disassembled source doesn't assemble:\n{}", source));
+                assert_eq!(original, object, "{}", source);
+            })
+            .count();
+
+        assert!(disassembled > 5);
+    }
+
+    #[test]
+    fn disassembling() {
+        let segments = vec![(
+            0x3000,
+            vec![0xE002, 0xF022, 0x0FFD, 0x0048, 0x0069, 0x0000, 0xD000],
+        )];
+        let symbols = read_symbols(
+            "Symbol               Assembler\n\
+             -------------------- -------\n\
+             START                3000\n\
+             COUNT                000A (constant)\n"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        assert!(symbols["COUNT"].is_constant());
+        assert_eq!(
+            Disassembler::new(&segments, &symbols)
+                .disassemble()
+                .lines()
+                .map(str::trim_end)
+                .collect::<Vec<_>>(),
+            vec![
+                ".ORIG x3000",
+                "START                LEA R0, L3003",
+                "                     PUTS",
+                "                     BRnzp START",
+                "L3003                .STRINGZ \"Hi\"",
+                "                     .FILL xD000",
+                ".END",
+            ]
+        );
+    }
+
+    #[test]
+    fn formatting() {
+        let formatter = Formatter::new()
+            .instruction_column(8)
+            .comment_column(28)
+            .immediates(Immediates::Hexadecimal);
+
+        let source = "; The start\n  .orig x3000\nloop: add r1,r1,#-1   ; Count down\n   brp loop\n\tLABEL\n  ; Done\nhalt\n.stringz \"a;b\"   \n.end";
+        let formatted = formatter.format(source);
+
+        assert_eq!(
+            formatted,
+            "; The start\n        .ORIG x3000\nloop    ADD R1, R1, xFFFF   ; Count down\n        BRp loop\nLABEL\n        ; Done\n        HALT\n        .STRINGZ \"a;b\"\n        .END\n"
+        );
+        assert_eq!(formatter.format(&formatted), formatted);
+
+        let lower = Formatter::new()
+            .mnemonics(Case::Lower)
+            .registers(Case::Keep)
+            .immediates(Immediates::Decimal)
+            .format(&formatted);
+        assert!(lower.contains("loop        add R1, R1, #-1"));
+        assert!(lower.contains("            brp loop"));
+    }
+
+    #[test]
+    fn formatting_examples() {
+        let object = |assembler: Assembler| {
+            let mut object = Vec::new();
+            Writer::new()
+                .register(Format::Object, &mut object)
+                .write(assembler.assemble(false).ok()?.into_program())
+                .unwrap();
+            Some(object)
+        };
+
+        let formatters = [
+            Formatter::new(),
+            Formatter::new()
+                .instruction_column(4)
+                .comment_column(0)
+                .mnemonics(Case::Lower)
+                .registers(Case::Lower)
+                .immediates(Immediates::Hexadecimal),
+            Formatter::new()
+                .mnemonics(Case::Keep)
+                .registers(Case::Keep)
+                .immediates(Immediates::Decimal),
+        ];
+
+        let mut examples = std::fs::read_dir("Examples")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "asm"))
+            .collect::<Vec<_>>();
+        examples.sort();
+
+        examples.iter().for_each(|example| {
+            let file = example.to_str().unwrap().to_string();
+            let source = std::fs::read_to_string(example).unwrap();
+            let original = object(Assembler::from_file(file.clone()).unwrap());
+
+            formatters.iter().for_each(|formatter| {
+                let formatted = formatter.format(&source);
+
+                assert_eq!(formatter.format(&formatted), formatted, "{}", file);
+                assert_eq!(
+                    object(Assembler::from_source(file.clone(), formatted)),
+                    original,
+                    "{}",
+                    file
+                );
+            });
+        });
+    }
 }